@@ -0,0 +1,113 @@
+use crate::console::ConsoleOutcome;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+/// A privileged action someone took, or tried to take
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// Unix seconds
+    pub at: i64,
+    /// Account that acted, e.g. `discord:<user id>`. `None` for the server itself, like config
+    /// reloads and idle shutdowns.
+    pub actor: Option<SmolStr>,
+    pub username: Option<SmolStr>,
+    pub action: AuditAction,
+    pub server: Option<SmolStr>,
+    pub outcome: AuditOutcome,
+    pub detail: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Login,
+    Logout,
+    /// A server password was sent to the user
+    SecretReveal,
+    ConsoleCommand,
+    /// Starting, stopping, restarting or waking a server
+    Lifecycle,
+    ConfigChange,
+    TokenCreate,
+    TokenRevoke,
+    /// Making the server write its save
+    ServerSave,
+    SaveDownload,
+    /// Enabling, disabling or uploading a mod
+    ModChange,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 11] = [
+        AuditAction::Login,
+        AuditAction::Logout,
+        AuditAction::SecretReveal,
+        AuditAction::ConsoleCommand,
+        AuditAction::Lifecycle,
+        AuditAction::ConfigChange,
+        AuditAction::TokenCreate,
+        AuditAction::TokenRevoke,
+        AuditAction::ServerSave,
+        AuditAction::SaveDownload,
+        AuditAction::ModChange,
+    ];
+
+    /// Name used in the database and the `action` query parameter
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Login => "login",
+            AuditAction::Logout => "logout",
+            AuditAction::SecretReveal => "secret_reveal",
+            AuditAction::ConsoleCommand => "console_command",
+            AuditAction::Lifecycle => "lifecycle",
+            AuditAction::ConfigChange => "config_change",
+            AuditAction::TokenCreate => "token_create",
+            AuditAction::TokenRevoke => "token_revoke",
+            AuditAction::ServerSave => "server_save",
+            AuditAction::SaveDownload => "save_download",
+            AuditAction::ModChange => "mod_change",
+        }
+    }
+
+    pub fn from_name(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.as_str() == value)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Ok,
+    /// The actor lacked the permission
+    Denied,
+    Error,
+}
+
+impl AuditOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditOutcome::Ok => "ok",
+            AuditOutcome::Denied => "denied",
+            AuditOutcome::Error => "error",
+        }
+    }
+}
+
+impl From<ConsoleOutcome> for AuditOutcome {
+    fn from(value: ConsoleOutcome) -> Self {
+        match value {
+            ConsoleOutcome::Ok => AuditOutcome::Ok,
+            ConsoleOutcome::Denied => AuditOutcome::Denied,
+            ConsoleOutcome::Error => AuditOutcome::Error,
+        }
+    }
+}
+
+/// Response of `/api/audit`, newest first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    /// Pass as `before` to get the next page, `None` on the last page
+    pub next_before: Option<i64>,
+}
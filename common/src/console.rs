@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+/// Body of `POST /api/servers/{id}/rcon`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsoleCommand {
+    pub command: String,
+}
+
+/// A console command someone ran, as recorded in the console log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsoleEntry {
+    /// Unix seconds
+    pub at: i64,
+    pub username: SmolStr,
+    pub command: String,
+    pub outcome: ConsoleOutcome,
+    /// What the server answered, or why the command didn't run
+    pub response: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsoleOutcome {
+    Ok,
+    /// Not on the user's allowlist
+    Denied,
+    /// Couldn't reach the server
+    Error,
+}
@@ -16,6 +16,10 @@ macro_rules! u64_id {
                     None => None,
                 }
             }
+
+            pub const fn get(self) -> u64 {
+                self.0.get()
+            }
         }
 
         impl From<u64> for $name {
@@ -11,4 +11,78 @@ pub struct FactorioStatus {
     pub players_online: Vec<SmolStr>,
     pub game_time: SmolStr,
     pub game_version: SmolStr,
+    /// Active mods, including `base`, sorted by name
+    pub mods: Vec<FactorioMod>,
+    pub research: Option<FactorioResearch>,
+    /// Science packs the player force has unlocked, in the order they're unlocked in vanilla
+    pub science_packs: Vec<SmolStr>,
+    /// Enemy evolution factor between 0 and 1
+    pub evolution: Option<f32>,
+    pub map_seed: Option<u32>,
+    /// Newest save in `saves_dir` that isn't an autosave. Factorio doesn't report the loaded
+    /// save, but it writes back to it on `/save` and on shutdown, so this is usually the one.
+    pub save_name: Option<SmolStr>,
+    /// Updates per second, measured between the last two polls
+    pub ups: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FactorioMod {
+    pub name: SmolStr,
+    pub version: SmolStr,
+}
+
+/// The technology the player force is researching
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FactorioResearch {
+    pub name: SmolStr,
+    /// Between 0 and 1
+    pub progress: f32,
+}
+
+/// A save file on the server's disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub name: SmolStr,
+    /// Bytes
+    pub size: u64,
+    /// Unix seconds
+    pub modified: i64,
+}
+
+/// Response of `/api/servers/{id}/saves`, newest first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveList {
+    pub saves: Vec<SaveFile>,
+    /// Copies of autosaves, empty if the server has no backup policy
+    pub backups: Vec<SaveFile>,
+}
+
+/// A mod in the server's `mod-list.json` or `mods` directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModInfo {
+    pub name: SmolStr,
+    pub enabled: bool,
+    /// Versions in the mods directory, oldest first. Empty for mods that ship with the game.
+    pub installed: Vec<SmolStr>,
+    /// Version the server loads on its next start, the pinned one or else the newest
+    pub expected: Option<SmolStr>,
+    /// Version the running server reports
+    pub running: Option<SmolStr>,
+    /// The running server differs from what it would load now, so it needs a restart
+    pub mismatch: bool,
+}
+
+/// Response of `/api/servers/{id}/mods`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModOverview {
+    pub mods: Vec<ModInfo>,
+    /// Whether the server reported its mods, without it there are no mismatches
+    pub running_known: bool,
+}
+
+/// Body of `PUT /api/servers/{id}/mods/{name}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModState {
+    pub enabled: bool,
 }
@@ -9,4 +9,6 @@ pub struct GenericStatus {
     pub health: HealthStatus,
     pub url: SmolStr,
     pub game_password: SmolStr,
+    /// Round-trip time of the last successful probe
+    pub latency_ms: Option<u32>,
 }
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use crate::status::HealthStatus;
+
+/// Recorded activity of a single server. Timestamps are Unix seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerHistory {
+    pub samples: Vec<StatusSample>,
+    pub player_events: Vec<PlayerEvent>,
+    /// Last time the server was seen running, even if that was before the requested range
+    pub last_online: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusSample {
+    pub at: i64,
+    pub health: HealthStatus,
+    pub players_online: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerEvent {
+    pub at: i64,
+    pub player: SmolStr,
+    pub kind: PlayerEventKind,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayerEventKind {
+    Join,
+    Leave,
+}
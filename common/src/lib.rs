@@ -1,6 +1,10 @@
+pub mod audit;
+pub mod console;
 pub mod discord;
 pub mod factorio;
-pub mod secret;
+pub mod history;
+pub mod minecraft;
+pub mod source_query;
 pub mod status;
 pub mod user;
 pub mod generic;
@@ -0,0 +1,27 @@
+use crate::status::HealthStatus;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MinecraftStatus {
+    pub name: SmolStr,
+    pub health: HealthStatus,
+    pub url: SmolStr,
+    pub motd: SmolStr,
+    pub version_name: SmolStr,
+    pub protocol_version: i32,
+    pub players_online: u32,
+    pub players_max: u32,
+    /// Sample of online players reported by the server. Usually truncated to ~12 names
+    pub player_sample: Vec<SmolStr>,
+    /// Server icon as a `data:image/png;base64,...` URL
+    pub favicon: Option<String>,
+    /// Complete list of online players. Only available when Query or RCON is configured
+    pub player_list: Option<Vec<SmolStr>>,
+    pub world: Option<SmolStr>,
+    /// Server software as reported by Query, e.g. `Paper on Bukkit 1.21.1-R0.1-SNAPSHOT`
+    pub server_software: Option<SmolStr>,
+    pub plugins: Vec<SmolStr>,
+    /// Ticks per second averaged over the last minute
+    pub tps: Option<f32>,
+}
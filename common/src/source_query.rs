@@ -0,0 +1,31 @@
+use crate::status::HealthStatus;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceQueryStatus {
+    pub name: SmolStr,
+    pub health: HealthStatus,
+    pub url: SmolStr,
+    pub game_password: SmolStr,
+    /// Server name as advertised in the Steam server browser
+    pub server_name: SmolStr,
+    /// Name of the game, NOT the name of the server
+    pub game: SmolStr,
+    pub map: SmolStr,
+    pub version: SmolStr,
+    pub player_count: u8,
+    pub max_players: u8,
+    pub bots: u8,
+    pub vac: bool,
+    pub players: Vec<SourcePlayer>,
+    pub rules: Vec<(SmolStr, SmolStr)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourcePlayer {
+    pub name: SmolStr,
+    pub score: i32,
+    /// Seconds the player has been connected
+    pub duration: f32,
+}
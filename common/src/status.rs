@@ -1,12 +1,116 @@
 use crate::factorio::FactorioStatus;
 use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 use crate::generic::GenericStatus;
+use crate::minecraft::MinecraftStatus;
+use crate::source_query::SourceQueryStatus;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ServerStatus {
     Factorio(FactorioStatus),
     Generic(GenericStatus),
+    Minecraft(MinecraftStatus),
+    SourceQuery(SourceQueryStatus),
+}
+
+impl ServerStatus {
+    pub fn name(&self) -> &SmolStr {
+        match self {
+            ServerStatus::Factorio(status) => &status.name,
+            ServerStatus::Generic(status) => &status.name,
+            ServerStatus::Minecraft(status) => &status.name,
+            ServerStatus::SourceQuery(status) => &status.name,
+        }
+    }
+
+    pub fn url(&self) -> &SmolStr {
+        match self {
+            ServerStatus::Factorio(status) => &status.url,
+            ServerStatus::Generic(status) => &status.url,
+            ServerStatus::Minecraft(status) => &status.url,
+            ServerStatus::SourceQuery(status) => &status.url,
+        }
+    }
+
+    pub fn health(&self) -> HealthStatus {
+        match self {
+            ServerStatus::Factorio(status) => status.health,
+            ServerStatus::Generic(status) => status.health,
+            ServerStatus::Minecraft(status) => status.health,
+            ServerStatus::SourceQuery(status) => status.health,
+        }
+    }
+
+    /// Number of connected players, if the game reports it
+    pub fn players_online(&self) -> Option<u32> {
+        match self {
+            ServerStatus::Factorio(status) => Some(status.players_online.len() as u32),
+            ServerStatus::Generic(_) => None,
+            ServerStatus::Minecraft(status) => Some(status.players_online),
+            ServerStatus::SourceQuery(status) => Some(status.player_count.into()),
+        }
+    }
+
+    /// Names of the connected players, if the game reports the full list
+    pub fn player_names(&self) -> Option<Vec<SmolStr>> {
+        match self {
+            ServerStatus::Factorio(status) => Some(status.players_online.clone()),
+            ServerStatus::Generic(_) => None,
+            ServerStatus::Minecraft(status) => match &status.player_list {
+                Some(list) => Some(list.clone()),
+                // The sample is capped by the server, so it's only complete for small player counts
+                None if status.player_sample.len() as u32 >= status.players_online => {
+                    Some(status.player_sample.clone())
+                }
+                None => None,
+            },
+            ServerStatus::SourceQuery(status) => {
+                Some(status.players.iter().map(|p| p.name.clone()).collect())
+            }
+        }
+    }
+
+    /// Version of the game the server runs, if the game reports it
+    pub fn version(&self) -> Option<&SmolStr> {
+        match self {
+            ServerStatus::Factorio(status) => Some(&status.game_version),
+            ServerStatus::Generic(_) => None,
+            ServerStatus::Minecraft(status) => Some(&status.version_name),
+            ServerStatus::SourceQuery(status) => Some(&status.version),
+        }
+    }
+
+    /// Blanks out passwords for users who may only see that the server exists
+    pub fn redact_secrets(&mut self) {
+        match self {
+            ServerStatus::Factorio(status) => status.game_password = SmolStr::default(),
+            ServerStatus::Generic(status) => status.game_password = SmolStr::default(),
+            ServerStatus::Minecraft(_) => {}
+            ServerStatus::SourceQuery(status) => status.game_password = SmolStr::default(),
+        }
+    }
+
+    /// Whether the status carries a password that [ServerStatus::redact_secrets] would remove
+    pub fn has_secrets(&self) -> bool {
+        match self {
+            ServerStatus::Factorio(status) => !status.game_password.is_empty(),
+            ServerStatus::Generic(status) => !status.game_password.is_empty(),
+            ServerStatus::Minecraft(_) => false,
+            ServerStatus::SourceQuery(status) => !status.game_password.is_empty(),
+        }
+    }
+
+    pub fn set_health(&mut self, health: HealthStatus) {
+        match self {
+            ServerStatus::Factorio(status) => status.health = health,
+            ServerStatus::Generic(status) => status.health = health,
+            ServerStatus::Minecraft(status) => status.health = health,
+            ServerStatus::SourceQuery(status) => status.health = health,
+        }
+    }
 }
 
 impl From<FactorioStatus> for ServerStatus {
@@ -21,6 +125,68 @@ impl From<GenericStatus> for ServerStatus {
     }
 }
 
+impl From<MinecraftStatus> for ServerStatus {
+    fn from(value: MinecraftStatus) -> Self {
+        Self::Minecraft(value)
+    }
+}
+
+impl From<SourceQueryStatus> for ServerStatus {
+    fn from(value: SourceQueryStatus) -> Self {
+        Self::SourceQuery(value)
+    }
+}
+
+/// A server's status along with what the current user is allowed to do with it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub id: SmolStr,
+    pub status: ServerStatus,
+    /// What the current user is allowed to do with the server
+    pub capabilities: HashSet<Capability>,
+}
+
+impl ServerInfo {
+    pub fn can(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// See that the server exists and its public status
+    View,
+    /// See passwords like `game_password`
+    ViewSecrets,
+    /// Run console commands
+    Console,
+    /// Start, stop and restart the server
+    Control,
+    /// Start the server after it was stopped for being idle. Implied by `View` on servers with
+    /// an idle policy.
+    Wake,
+    /// Change the server's configuration. Reserved, there is no way to edit it through the
+    /// server manager yet, so granting it has no effect.
+    EditConfig,
+    /// List and download saves, and make the server save. Factorio servers with a saves
+    /// directory only.
+    Saves,
+    /// Enable, disable and upload mods. Factorio servers with a mods directory only.
+    Mods,
+}
+
+/// Message sent over the live status stream
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StatusEvent {
+    /// Every visible server. Always the first event of a stream
+    Snapshot(Vec<ServerInfo>),
+    /// A server was added or its status changed
+    Updated(Box<ServerInfo>),
+    /// The server with this ID is no longer visible
+    Removed(SmolStr),
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum HealthStatus {
     Running,
@@ -39,3 +205,17 @@ impl Display for HealthStatus {
         }
     }
 }
+
+impl FromStr for HealthStatus {
+    type Err = SmolStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Running" => Ok(HealthStatus::Running),
+            "Starting" => Ok(HealthStatus::Starting),
+            "Offline" => Ok(HealthStatus::Offline),
+            "Unknown" => Ok(HealthStatus::Unknown),
+            _ => Err(s.into()),
+        }
+    }
+}
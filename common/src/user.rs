@@ -1,7 +1,56 @@
+use crate::status::Capability;
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
+use std::collections::HashSet;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct UserData {
     pub name: SmolStr,
+    /// May view the audit log
+    #[serde(default)]
+    pub admin: bool,
+}
+
+/// A way to log in, linked to at `/auth/{id}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginProvider {
+    pub id: SmolStr,
+    pub name: SmolStr,
+}
+
+/// Response of `/api/me`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "user", rename_all = "snake_case")]
+pub enum LoginState {
+    LoggedOut,
+    /// The provider stopped accepting the user's tokens, they have to log in again
+    Expired,
+    LoggedIn(UserData),
+}
+
+/// A personal API token, without its secret
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: SmolStr,
+    /// The token can do at most this, and never more than its owner
+    pub capabilities: HashSet<Capability>,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    /// The token stops working after this
+    pub expires_at: i64,
+}
+
+/// Request to create an API token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewApiToken {
+    pub name: SmolStr,
+    pub capabilities: HashSet<Capability>,
+}
+
+/// A newly created API token. The secret is only ever shown this once.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreatedApiToken {
+    pub token: ApiToken,
+    pub secret: String,
 }
@@ -1,5 +1,11 @@
 use crate::app::user_actions::UserActions;
+use crate::pages::audit::AuditLogPage;
+use crate::pages::console::ConsolePage;
 use crate::pages::games::GamePage;
+use crate::pages::mods::ModsPage;
+use crate::pages::saves::SavesPage;
+use crate::pages::server::ServerPage;
+use crate::pages::tokens::TokensPage;
 use patternfly_yew::prelude::*;
 use yew::prelude::*;
 use yew_router::prelude::{*, Switch, Redirect};
@@ -17,6 +23,18 @@ pub enum AppRoute {
     Index,
     #[at("/game/:game")]
     Game { game: String },
+    #[at("/server/:id")]
+    Server { id: String },
+    #[at("/server/:id/console")]
+    Console { id: String },
+    #[at("/server/:id/saves")]
+    Saves { id: String },
+    #[at("/server/:id/mods")]
+    Mods { id: String },
+    #[at("/tokens")]
+    Tokens,
+    #[at("/audit")]
+    Audit,
 }
 
 #[function_component(Application)]
@@ -36,6 +54,12 @@ fn switch_app_route(target: AppRoute) -> Html {
     match target {
         AppRoute::Index => html! { <Redirect<AppRoute> to={AppRoute::Game { game: "Factorio".to_owned() }} /> },
         AppRoute::Game { game } => html! {<AppPage><GamePage key={game.clone()} game={game.clone()} /></AppPage>},
+        AppRoute::Server { id } => html! {<AppPage><ServerPage key={id.clone()} id={id.clone()} /></AppPage>},
+        AppRoute::Console { id } => html! {<AppPage><ConsolePage key={id.clone()} id={id.clone()} /></AppPage>},
+        AppRoute::Saves { id } => html! {<AppPage><SavesPage key={id.clone()} id={id.clone()} /></AppPage>},
+        AppRoute::Mods { id } => html! {<AppPage><ModsPage key={id.clone()} id={id.clone()} /></AppPage>},
+        AppRoute::Tokens => html! {<AppPage><TokensPage /></AppPage>},
+        AppRoute::Audit => html! {<AppPage><AuditLogPage /></AppPage>},
     }
 }
 
@@ -48,6 +72,8 @@ pub struct PageProps {
 fn page(props: &PageProps) -> Html {
     let games = [
         "Factorio",
+        "Minecraft",
+        "SourceQuery",
         "Generic"
     ];
     let sidebar = html_nested! {
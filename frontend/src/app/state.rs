@@ -4,16 +4,28 @@ use yewdux::{Reducer, Store};
 
 pub enum AppAction {
     UpdateUser(Rc<Option<UserData>>),
+    /// The login expired and the user has to log in again
+    SessionExpired,
 }
 
 #[derive(Default, Clone, Debug, PartialEq, Store)]
 pub struct AppState {
     pub user_data: Rc<Option<UserData>>,
+    pub session_expired: bool,
 }
 impl Reducer<AppState> for AppAction {
     fn apply(self, _state: Rc<AppState>) -> Rc<AppState> {
         match self {
-            AppAction::UpdateUser(user_data) => AppState { user_data }.into(),
+            AppAction::UpdateUser(user_data) => AppState {
+                user_data,
+                session_expired: false,
+            }
+            .into(),
+            AppAction::SessionExpired => AppState {
+                user_data: None.into(),
+                session_expired: true,
+            }
+            .into(),
         }
     }
 }
@@ -1,5 +1,5 @@
 use crate::app::state::AppState;
-use common::user::UserData;
+use common::user::{LoginProvider, LoginState};
 use gloo_net::http::Request;
 use patternfly_yew::prelude::*;
 use wasm_bindgen_futures::spawn_local;
@@ -26,14 +26,23 @@ pub fn user_actions() -> Html {
                 }
             };
 
-            let user_data = resp.json::<Option<UserData>>().await;
-            match user_data {
+            let login_state = resp.json::<LoginState>().await;
+            match login_state {
                 #[allow(unused_variables)]
-                Ok(user_data) => {
-                    // user dispatcher directly so we don't re-render for all state changes
+                Ok(login_state) => {
                     #[cfg(target_arch = "wasm32")]
-                    yewdux::Dispatch::<AppState>::global()
-                        .apply(crate::app::state::AppAction::UpdateUser(user_data.into()));
+                    {
+                        use crate::app::state::AppAction;
+                        let action = match login_state {
+                            LoginState::LoggedIn(user_data) => {
+                                AppAction::UpdateUser(Some(user_data).into())
+                            }
+                            LoginState::LoggedOut => AppAction::UpdateUser(None.into()),
+                            LoginState::Expired => AppAction::SessionExpired,
+                        };
+                        // user dispatcher directly so we don't re-render for all state changes
+                        yewdux::Dispatch::<AppState>::global().apply(action);
+                    }
                     loading.set(false);
                 }
                 Err(e) => {
@@ -43,8 +52,31 @@ pub fn user_actions() -> Html {
         });
     });
 
+    let providers = use_state_eq(Vec::<LoginProvider>::new);
+    use_effect_with((), {
+        let providers = providers.clone();
+        move |_| {
+            spawn_local(async move {
+                let resp = match Request::get("/api/auth/providers").send().await {
+                    Ok(resp) => resp,
+                    Err(e) => {
+                        log::error!("Failed to get login providers: {}", e);
+                        return;
+                    }
+                };
+                match resp.json::<Vec<LoginProvider>>().await {
+                    Ok(list) => providers.set(list),
+                    Err(e) => log::error!("Failed to get login providers: {}", e),
+                }
+            });
+        }
+    });
+
     let username =
         use_selector(|state: &AppState| Option::as_ref(&state.user_data).map(|u| u.name.clone()));
+    let admin = *use_selector(|state: &AppState| Option::as_ref(&state.user_data).is_some_and(|u| u.admin));
+    let session_expired = *use_selector(|state: &AppState| state.session_expired);
+    let login_text = if session_expired { "Log in again" } else { "Login" };
 
     if *loading {
         return html! {
@@ -53,10 +85,31 @@ pub fn user_actions() -> Html {
     }
 
     let Some(username) = username.as_ref() else {
+        if providers.len() > 1 {
+            return html! {
+                <Dropdown
+                    position={Position::Right}
+                    text={login_text.to_string()}
+                    variant={MenuToggleVariant::Primary}
+                >
+                    { for providers.iter().map(|provider| html_nested! {
+                        <MenuLink href={format!("/auth/{}", provider.id)}>
+                            {format!("Login with {}", provider.name)}
+                        </MenuLink>
+                    }) }
+                </Dropdown>
+            };
+        }
+
+        // Discord is always available, so use it until we know better
+        let href = providers
+            .first()
+            .map(|provider| format!("/auth/{}", provider.id))
+            .unwrap_or_else(|| "/auth/discord".to_string());
         return html! {
-            <a href="/auth/discord">
+            <a {href}>
                 <Button variant={ButtonVariant::Primary}>
-                    {"Login"}
+                    {login_text}
                 </Button>
             </a>
         };
@@ -68,6 +121,10 @@ pub fn user_actions() -> Html {
             text={username.to_string()}
             variant={MenuToggleVariant::Plain}
         >
+            <MenuLink href={"/tokens"}>{"API tokens"}</MenuLink>
+            {for admin.then(|| html_nested! {
+                <MenuLink href={"/audit"}>{"Audit log"}</MenuLink>
+            })}
             <MenuLink href={"/logout"}>{"Logout"}</MenuLink>
         </Dropdown>
     }
@@ -1,33 +1,87 @@
 mod health_indicator;
+mod lifecycle_controls;
+
+pub use lifecycle_controls::LifecycleControls;
 
 use std::time::Duration;
 use gloo_utils::window;
 use crate::components::status::health_indicator::HealthIndicator;
+use crate::app::AppRoute;
 use common::factorio::FactorioStatus;
-use common::status::ServerStatus;
+use common::status::{Capability, ServerInfo, ServerStatus};
 use patternfly_yew::prelude::*;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsValue;
 use yew::prelude::*;
 use yew_hooks::{use_clipboard, UseClipboardHandle};
+use yew_router::prelude::Link;
 use common::generic::GenericStatus;
+use common::minecraft::MinecraftStatus;
+use common::source_query::SourceQueryStatus;
 
 #[derive(Properties, PartialEq)]
 pub struct StatusCardProps {
-    pub status: ServerStatus,
+    pub server: ServerInfo,
 }
 
 #[function_component(ServerStatusCard)]
 pub fn status_card(props: &StatusCardProps) -> Html {
     let toaster = use_toaster().unwrap();
-    let status = &props.status;
-    match status {
-        ServerStatus::Factorio(status) => factorio_card(status, toaster),
-        ServerStatus::Generic(status) => generic_card(status, toaster),
+    let server = &props.server;
+    let has_console =
+        server.can(Capability::Console) && matches!(server.status, ServerStatus::Factorio(_));
+    let footer = html! {
+        <>
+            if server.can(Capability::Control) || server.can(Capability::Wake) {
+                <CardBody>
+                    <LifecycleControls
+                        id={AttrValue::from(server.id.to_string())}
+                        health={server.status.health()}
+                        can_control={server.can(Capability::Control)}
+                    />
+                </CardBody>
+            }
+            <CardFooter>
+                <Flex>
+                    <FlexItem>
+                        <Link<AppRoute> to={AppRoute::Server { id: server.id.to_string() }}>
+                            {"History"}
+                        </Link<AppRoute>>
+                    </FlexItem>
+                    {for has_console.then(|| html_nested! {
+                        <FlexItem>
+                            <Link<AppRoute> to={AppRoute::Console { id: server.id.to_string() }}>
+                                {"Console"}
+                            </Link<AppRoute>>
+                        </FlexItem>
+                    })}
+                    {for server.can(Capability::Saves).then(|| html_nested! {
+                        <FlexItem>
+                            <Link<AppRoute> to={AppRoute::Saves { id: server.id.to_string() }}>
+                                {"Saves"}
+                            </Link<AppRoute>>
+                        </FlexItem>
+                    })}
+                    {for server.can(Capability::Mods).then(|| html_nested! {
+                        <FlexItem>
+                            <Link<AppRoute> to={AppRoute::Mods { id: server.id.to_string() }}>
+                                {"Mods"}
+                            </Link<AppRoute>>
+                        </FlexItem>
+                    })}
+                </Flex>
+            </CardFooter>
+        </>
+    };
+    match &server.status {
+        ServerStatus::Factorio(status) => factorio_card(status, footer, toaster),
+        ServerStatus::Generic(status) => generic_card(status, footer, toaster),
+        ServerStatus::Minecraft(status) => minecraft_card(status, footer, toaster),
+        ServerStatus::SourceQuery(status) => source_query_card(status, footer, toaster),
     }
 }
 
-fn factorio_card(status: &FactorioStatus, toaster: Toaster) -> Html {
+fn factorio_card(status: &FactorioStatus, footer: Html, toaster: Toaster) -> Html {
     let copy_pass = copy_to_clipboard("Password", &status.game_password, toaster.clone());
     let copy_url = copy_to_clipboard("URL", &status.url, toaster.clone());
     html! {
@@ -46,20 +100,53 @@ fn factorio_card(status: &FactorioStatus, toaster: Toaster) -> Html {
                             icon={Icon::Copy}
                             aria_label="Copy URL" />
                     </DescriptionGroup>
-                    <DescriptionGroup term="Password">
-                        {&*status.game_password}
-                        <Button
-                            onclick={copy_pass}
-                            variant={ButtonVariant::Plain}
-                            icon={Icon::Copy}
-                            aria_label="Copy Password" />
-                    </DescriptionGroup>
+                    // Blank if there is none or the user isn't allowed to see it
+                    if !status.game_password.is_empty() {
+                        <DescriptionGroup term="Password">
+                            {&*status.game_password}
+                            <Button
+                                onclick={copy_pass}
+                                variant={ButtonVariant::Plain}
+                                icon={Icon::Copy}
+                                aria_label="Copy Password" />
+                        </DescriptionGroup>
+                    }
                     <DescriptionGroup term="Game Time">
                         {&*status.game_time}
                     </DescriptionGroup>
                     <DescriptionGroup term="Game Version">
                         {&*status.game_version}
                     </DescriptionGroup>
+                    if let Some(save_name) = &status.save_name {
+                        <DescriptionGroup term="Save">
+                            {&**save_name}
+                        </DescriptionGroup>
+                    }
+                    if let Some(research) = &status.research {
+                        <DescriptionGroup term="Research">
+                            {format!("{} ({:.0}%)", research.name, research.progress * 100.0)}
+                        </DescriptionGroup>
+                    }
+                    if !status.science_packs.is_empty() {
+                        <DescriptionGroup term="Science">
+                            {status.science_packs.join(", ")}
+                        </DescriptionGroup>
+                    }
+                    if let Some(evolution) = status.evolution {
+                        <DescriptionGroup term="Evolution">
+                            {format!("{:.1}%", evolution * 100.0)}
+                        </DescriptionGroup>
+                    }
+                    if let Some(ups) = status.ups {
+                        <DescriptionGroup term="UPS">
+                            {format!("{ups:.1}")}
+                        </DescriptionGroup>
+                    }
+                    if let Some(seed) = status.map_seed {
+                        <DescriptionGroup term="Map Seed">
+                            {seed}
+                        </DescriptionGroup>
+                    }
                     <DescriptionGroup term="Online Players">
                         <ul>
                             {
@@ -75,13 +162,23 @@ fn factorio_card(status: &FactorioStatus, toaster: Toaster) -> Html {
                             }
                         </ul>
                     </DescriptionGroup>
+                    if !status.mods.is_empty() {
+                        <DescriptionGroup term="Mods">
+                            <ul>
+                                {for status.mods.iter().map(|m| html! {
+                                    <li key={&*m.name}>{format!("{} {}", m.name, m.version)}</li>
+                                })}
+                            </ul>
+                        </DescriptionGroup>
+                    }
                 </DescriptionList>
             </CardBody>
+            {footer}
         </Card>
     }
 }
 
-fn generic_card(status: &GenericStatus, toaster: Toaster) -> Html {
+fn generic_card(status: &GenericStatus, footer: Html, toaster: Toaster) -> Html {
     let copy_pass = copy_to_clipboard("Password", &status.game_password, toaster.clone());
     let copy_url = copy_to_clipboard("URL", &status.url, toaster.clone());
     html!{
@@ -95,6 +192,11 @@ fn generic_card(status: &GenericStatus, toaster: Toaster) -> Html {
                     <DescriptionGroup term="Game">
                         {&*status.game_name}
                     </DescriptionGroup>
+                    if let Some(latency) = status.latency_ms {
+                        <DescriptionGroup term="Latency">
+                            {format!("{latency} ms")}
+                        </DescriptionGroup>
+                    }
                     <DescriptionGroup term="URL">
                         {&*status.url}
                         <Button
@@ -103,21 +205,203 @@ fn generic_card(status: &GenericStatus, toaster: Toaster) -> Html {
                             icon={Icon::Copy}
                             aria_label="Copy URL" />
                     </DescriptionGroup>
-                    <DescriptionGroup term="Password">
-                        {&*status.game_password}
+                    // Blank if there is none or the user isn't allowed to see it
+                    if !status.game_password.is_empty() {
+                        <DescriptionGroup term="Password">
+                            {&*status.game_password}
+                            <Button
+                                onclick={copy_pass}
+                                variant={ButtonVariant::Plain}
+                                icon={Icon::Copy}
+                                aria_label="Copy Password" />
+                        </DescriptionGroup>
+                    }
+                </DescriptionList>
+            </CardBody>
+            {footer}
+        </Card>
+    }
+}
+
+fn minecraft_card(status: &MinecraftStatus, footer: Html, toaster: Toaster) -> Html {
+    let copy_url = copy_to_clipboard("URL", &status.url, toaster.clone());
+    // The ping sample is truncated, prefer the full list when Query or RCON is set up
+    let players = status.player_list.as_ref().unwrap_or(&status.player_sample);
+    html! {
+        <Card>
+            <CardTitle>
+                if let Some(favicon) = &status.favicon {
+                    <img src={favicon.clone()} alt="Server icon" width="32" height="32" style="margin-right: 8px; vertical-align: middle;" />
+                }
+                {&*status.name}
+            </CardTitle>
+            <CardBody>
+                <DescriptionList mode={[DescriptionListMode::Horizontal]}>
+                    <DescriptionGroup term="Status">
+                        <HealthIndicator health={status.health}/>
+                    </DescriptionGroup>
+                    <DescriptionGroup term="URL">
+                        {&*status.url}
                         <Button
-                            onclick={copy_pass}
+                            onclick={copy_url}
                             variant={ButtonVariant::Plain}
                             icon={Icon::Copy}
-                            aria_label="Copy Password" />
+                            aria_label="Copy URL" />
+                    </DescriptionGroup>
+                    <DescriptionGroup term="MOTD">
+                        {&*status.motd}
+                    </DescriptionGroup>
+                    <DescriptionGroup term="Game Version">
+                        {&*status.version_name}
+                    </DescriptionGroup>
+                    if let Some(software) = &status.server_software {
+                        <DescriptionGroup term="Software">
+                            {&**software}
+                        </DescriptionGroup>
+                    }
+                    if let Some(world) = &status.world {
+                        <DescriptionGroup term="World">
+                            {&**world}
+                        </DescriptionGroup>
+                    }
+                    if let Some(tps) = status.tps {
+                        <DescriptionGroup term="TPS">
+                            {format!("{tps:.1}")}
+                        </DescriptionGroup>
+                    }
+                    <DescriptionGroup term="Players">
+                        {format!("{} / {}", status.players_online, status.players_max)}
+                    </DescriptionGroup>
+                    <DescriptionGroup term="Online Players">
+                        <ul>
+                            {
+                                if players.is_empty() {
+                                    "None :(".into()
+                                } else {
+                                    players.iter().map(|name| {
+                                        html! {
+                                            <li key={&**name}>{&**name}</li>
+                                        }
+                                    }).collect::<Html>()
+                                }
+                            }
+                        </ul>
                     </DescriptionGroup>
+                    if !status.plugins.is_empty() {
+                        <DescriptionGroup term="Plugins">
+                            <ul>
+                                {for status.plugins.iter().map(|plugin| html! {
+                                    <li key={&**plugin}>{&**plugin}</li>
+                                })}
+                            </ul>
+                        </DescriptionGroup>
+                    }
                 </DescriptionList>
             </CardBody>
+            {footer}
         </Card>
     }
 }
 
-fn copy_to_clipboard(name: &str, text: &str, toaster: Toaster) -> Callback<MouseEvent> {
+fn source_query_card(status: &SourceQueryStatus, footer: Html, toaster: Toaster) -> Html {
+    let copy_pass = copy_to_clipboard("Password", &status.game_password, toaster.clone());
+    let copy_url = copy_to_clipboard("URL", &status.url, toaster.clone());
+    html! {
+        <Card>
+            <CardTitle>{&*status.name}</CardTitle>
+            <CardBody>
+                <DescriptionList mode={[DescriptionListMode::Horizontal]}>
+                    <DescriptionGroup term="Status">
+                        <HealthIndicator health={status.health}/>
+                    </DescriptionGroup>
+                    <DescriptionGroup term="Game">
+                        {&*status.game}
+                    </DescriptionGroup>
+                    <DescriptionGroup term="URL">
+                        {&*status.url}
+                        <Button
+                            onclick={copy_url}
+                            variant={ButtonVariant::Plain}
+                            icon={Icon::Copy}
+                            aria_label="Copy URL" />
+                    </DescriptionGroup>
+                    if !status.game_password.is_empty() {
+                        <DescriptionGroup term="Password">
+                            {&*status.game_password}
+                            <Button
+                                onclick={copy_pass}
+                                variant={ButtonVariant::Plain}
+                                icon={Icon::Copy}
+                                aria_label="Copy Password" />
+                        </DescriptionGroup>
+                    }
+                    <DescriptionGroup term="Map">
+                        {&*status.map}
+                    </DescriptionGroup>
+                    <DescriptionGroup term="Game Version">
+                        {&*status.version}
+                    </DescriptionGroup>
+                    <DescriptionGroup term="VAC">
+                        {if status.vac { "Secured" } else { "Not secured" }}
+                    </DescriptionGroup>
+                    <DescriptionGroup term="Players">
+                        {format!("{} / {}", status.player_count, status.max_players)}
+                        if status.bots > 0 {
+                            {format!(" ({} bots)", status.bots)}
+                        }
+                    </DescriptionGroup>
+                    <DescriptionGroup term="Online Players">
+                        <ul>
+                            {
+                                if status.players.is_empty() {
+                                    "None :(".into()
+                                } else {
+                                    status.players.iter().map(|player| {
+                                        html! {
+                                            <li key={&*player.name}>
+                                                {format!(
+                                                    "{} (score {}, {})",
+                                                    player.name,
+                                                    player.score,
+                                                    format_duration(player.duration),
+                                                )}
+                                            </li>
+                                        }
+                                    }).collect::<Html>()
+                                }
+                            }
+                        </ul>
+                    </DescriptionGroup>
+                    if !status.rules.is_empty() {
+                        <DescriptionGroup term="Rules">
+                            <details>
+                                <summary>{format!("{} rules", status.rules.len())}</summary>
+                                <ul>
+                                    {for status.rules.iter().map(|(key, value)| html! {
+                                        <li key={&**key}>{format!("{key} = {value}")}</li>
+                                    })}
+                                </ul>
+                            </details>
+                        </DescriptionGroup>
+                    }
+                </DescriptionList>
+            </CardBody>
+            {footer}
+        </Card>
+    }
+}
+
+fn format_duration(seconds: f32) -> String {
+    let minutes = (seconds / 60.0) as u32;
+    if minutes >= 60 {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+/// Copies `text` and toasts whether it worked, `name` says what was copied
+pub fn copy_to_clipboard(name: &str, text: &str, toaster: Toaster) -> Callback<MouseEvent> {
     let clipboard = window().navigator().clipboard();
     let text = text.to_owned();
 
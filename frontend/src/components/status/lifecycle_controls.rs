@@ -0,0 +1,96 @@
+use std::time::Duration;
+use common::status::HealthStatus;
+use gloo_net::http::Request;
+use patternfly_yew::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct LifecycleControlsProps {
+    pub id: AttrValue,
+    pub health: HealthStatus,
+    /// Show start, stop and restart instead of just wake
+    pub can_control: bool,
+}
+
+#[function_component(LifecycleControls)]
+pub fn lifecycle_controls(props: &LifecycleControlsProps) -> Html {
+    let toaster = use_toaster();
+    let pending = use_state_eq(|| false);
+
+    let run = {
+        let id = props.id.clone();
+        let pending = pending.clone();
+        Callback::from(move |action: &'static str| {
+            let url = format!("/api/servers/{id}/{action}");
+            let pending = pending.clone();
+            let toaster = toaster.clone();
+            pending.set(true);
+            spawn_local(async move {
+                let (title, r#type) = match Request::post(&url).send().await {
+                    Ok(resp) if resp.ok() => (format!("Requested server {action}"), AlertType::Success),
+                    Ok(resp) => (
+                        format!("Failed to {action} server: {}", resp.status_text()),
+                        AlertType::Danger,
+                    ),
+                    Err(e) => (format!("Failed to {action} server: {e}"), AlertType::Danger),
+                };
+                if let Some(toaster) = toaster {
+                    toaster.toast(Toast {
+                        title,
+                        timeout: Some(Duration::from_secs(3)),
+                        r#type,
+                        ..Default::default()
+                    });
+                }
+                pending.set(false);
+            });
+        })
+    };
+
+    let running = matches!(props.health, HealthStatus::Running | HealthStatus::Starting);
+
+    if !props.can_control {
+        return html! {
+            <Button
+                variant={ButtonVariant::Primary}
+                disabled={*pending || running}
+                onclick={run.reform(|_| "wake")}
+            >
+                {"Wake"}
+            </Button>
+        };
+    }
+
+    html! {
+        <Flex>
+            <FlexItem>
+                <Button
+                    variant={ButtonVariant::Secondary}
+                    disabled={*pending || running}
+                    onclick={run.reform(|_| "start")}
+                >
+                    {"Start"}
+                </Button>
+            </FlexItem>
+            <FlexItem>
+                <Button
+                    variant={ButtonVariant::Secondary}
+                    disabled={*pending || props.health == HealthStatus::Offline}
+                    onclick={run.reform(|_| "stop")}
+                >
+                    {"Stop"}
+                </Button>
+            </FlexItem>
+            <FlexItem>
+                <Button
+                    variant={ButtonVariant::Secondary}
+                    disabled={*pending}
+                    onclick={run.reform(|_| "restart")}
+                >
+                    {"Restart"}
+                </Button>
+            </FlexItem>
+        </Flex>
+    }
+}
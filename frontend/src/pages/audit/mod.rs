@@ -0,0 +1,264 @@
+use gloo_net::http::Request;
+use patternfly_yew::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+use yewdux::use_selector;
+use common::audit::{AuditAction, AuditEntry, AuditOutcome, AuditPage};
+use crate::app::AppState;
+use crate::pages::MyPage;
+use crate::pages::server::format_time;
+
+fn action_label(action: AuditAction) -> &'static str {
+    match action {
+        AuditAction::Login => "Login",
+        AuditAction::Logout => "Logout",
+        AuditAction::SecretReveal => "Password shown",
+        AuditAction::ConsoleCommand => "Console command",
+        AuditAction::Lifecycle => "Start/stop",
+        AuditAction::ConfigChange => "Config change",
+        AuditAction::TokenCreate => "Token created",
+        AuditAction::TokenRevoke => "Token revoked",
+        AuditAction::ServerSave => "Saved game",
+        AuditAction::SaveDownload => "Save downloaded",
+        AuditAction::ModChange => "Mod change",
+    }
+}
+
+/// Filters as sent to `/api/audit`, empty means any
+#[derive(Clone, Default, PartialEq)]
+struct Filters {
+    actor: String,
+    server: String,
+    action: String,
+}
+
+async fn fetch_page(filters: &Filters, before: Option<i64>) -> Result<AuditPage, String> {
+    let mut params = vec![
+        ("actor", filters.actor.trim().to_owned()),
+        ("server", filters.server.trim().to_owned()),
+        ("action", filters.action.clone()),
+    ];
+    params.retain(|(_, value)| !value.is_empty());
+    if let Some(before) = before {
+        params.push(("before", before.to_string()));
+    }
+
+    match Request::get("/api/audit").query(params).send().await {
+        Ok(resp) if resp.ok() => resp.json::<AuditPage>().await.map_err(|e| e.to_string()),
+        Ok(resp) => Err(resp.status_text()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[function_component(AuditLogPage)]
+pub fn audit_page() -> Html {
+    let admin = *use_selector(|s: &AppState| Option::as_ref(&s.user_data).is_some_and(|u| u.admin));
+    // What's typed into the filter fields, only applied on search
+    let draft = use_state_eq(Filters::default);
+    let filters = use_state_eq(Filters::default);
+    let entries = use_state_eq(|| None::<Result<Vec<AuditEntry>, String>>);
+    let next_before = use_state_eq(|| None::<i64>);
+    let toaster = use_toaster();
+
+    {
+        let entries = entries.clone();
+        let next_before = next_before.clone();
+        use_effect_with((admin, (*filters).clone()), move |(admin, filters)| {
+            if !admin {
+                return;
+            }
+            let filters = filters.clone();
+            entries.set(None);
+            spawn_local(async move {
+                let result = fetch_page(&filters, None).await;
+                match result {
+                    Ok(page) => {
+                        next_before.set(page.next_before);
+                        entries.set(Some(Ok(page.entries)));
+                    }
+                    Err(e) => {
+                        log::error!("Failed to fetch audit log: {e}");
+                        entries.set(Some(Err(e)));
+                    }
+                }
+            });
+        });
+    }
+
+    let onsearch = {
+        let draft = draft.clone();
+        let filters = filters.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            filters.set((*draft).clone());
+        })
+    };
+
+    let onmore = {
+        let filters = filters.clone();
+        let entries = entries.clone();
+        let next_before = next_before.clone();
+        Callback::from(move |_| {
+            let Some(before) = *next_before else {
+                return;
+            };
+            let filters = (*filters).clone();
+            let entries = entries.clone();
+            let next_before = next_before.clone();
+            let toaster = toaster.clone();
+            spawn_local(async move {
+                match fetch_page(&filters, Some(before)).await {
+                    Ok(page) => {
+                        let mut updated = match &*entries {
+                            Some(Ok(existing)) => existing.clone(),
+                            _ => Vec::new(),
+                        };
+                        updated.extend(page.entries);
+                        next_before.set(page.next_before);
+                        entries.set(Some(Ok(updated)));
+                    }
+                    Err(e) => {
+                        if let Some(toaster) = &toaster {
+                            toaster.toast(Toast {
+                                title: format!("Failed to load more entries: {e}"),
+                                timeout: Some(std::time::Duration::from_secs(3)),
+                                r#type: AlertType::Danger,
+                                ..Default::default()
+                            });
+                        }
+                    }
+                }
+            });
+        })
+    };
+
+    if !admin {
+        return html! {
+            <MyPage title="Audit log">
+                {"Only admins can view the audit log"}
+            </MyPage>
+        };
+    }
+
+    let onactor = {
+        let draft = draft.clone();
+        Callback::from(move |actor: String| draft.set(Filters { actor, ..(*draft).clone() }))
+    };
+    let onserver = {
+        let draft = draft.clone();
+        Callback::from(move |server: String| draft.set(Filters { server, ..(*draft).clone() }))
+    };
+    let onaction = {
+        let draft = draft.clone();
+        Callback::from(move |e: Event| {
+            let action = e.target_unchecked_into::<HtmlSelectElement>().value();
+            draft.set(Filters { action, ..(*draft).clone() });
+        })
+    };
+
+    let load_more = next_before.is_some().then(|| html_nested! {
+        <FlexItem>
+            <Button variant={ButtonVariant::Secondary} onclick={onmore}>
+                {"Load more"}
+            </Button>
+        </FlexItem>
+    });
+
+    let list = match &*entries {
+        None => html! { <Spinner /> },
+        Some(Err(e)) => html! {
+            <Alert inline=true title="Failed to load the audit log" r#type={AlertType::Danger}>{e.clone()}</Alert>
+        },
+        Some(Ok(entries)) => entries_table(entries),
+    };
+
+    html! {
+        <MyPage title="Audit log">
+            <Flex modifiers={[FlexModifier::Column]}>
+                <FlexItem>
+                    <form onsubmit={onsearch}>
+                        <Flex>
+                            <FlexItem>
+                                <TextInput
+                                    placeholder="User or account ID"
+                                    value={draft.actor.clone()}
+                                    onchange={onactor}
+                                />
+                            </FlexItem>
+                            <FlexItem>
+                                <TextInput
+                                    placeholder="Server ID"
+                                    value={draft.server.clone()}
+                                    onchange={onserver}
+                                />
+                            </FlexItem>
+                            <FlexItem>
+                                <select class="pf-v5-c-form-control" onchange={onaction}>
+                                    <option value="" selected={draft.action.is_empty()}>{"Any action"}</option>
+                                    {for AuditAction::ALL.iter().map(|action| html! {
+                                        <option
+                                            value={action.as_str()}
+                                            selected={draft.action == action.as_str()}
+                                        >
+                                            {action_label(*action)}
+                                        </option>
+                                    })}
+                                </select>
+                            </FlexItem>
+                            <FlexItem>
+                                <Button variant={ButtonVariant::Primary} r#type={ButtonType::Submit}>
+                                    {"Search"}
+                                </Button>
+                            </FlexItem>
+                        </Flex>
+                    </form>
+                </FlexItem>
+                <FlexItem>{list}</FlexItem>
+                {for load_more}
+            </Flex>
+        </MyPage>
+    }
+}
+
+fn entries_table(entries: &[AuditEntry]) -> Html {
+    if entries.is_empty() {
+        return html! { <p>{"Nothing matches these filters"}</p> };
+    }
+
+    html! {
+        <table class="pf-v5-c-table pf-m-compact pf-m-grid-md" role="grid">
+            <thead>
+                <tr>
+                    <th>{"Time"}</th>
+                    <th>{"User"}</th>
+                    <th>{"Action"}</th>
+                    <th>{"Server"}</th>
+                    <th>{"Outcome"}</th>
+                    <th>{"Detail"}</th>
+                </tr>
+            </thead>
+            <tbody>
+                {for entries.iter().map(|entry| {
+                    let outcome = match entry.outcome {
+                        AuditOutcome::Ok => "OK",
+                        AuditOutcome::Denied => "Denied",
+                        AuditOutcome::Error => "Failed",
+                    };
+                    html! {
+                        <tr>
+                            <td>{format_time(entry.at)}</td>
+                            <td title={entry.actor.clone().unwrap_or_default().to_string()}>
+                                {entry.username.as_deref().unwrap_or("System").to_owned()}
+                            </td>
+                            <td>{action_label(entry.action)}</td>
+                            <td>{entry.server.as_deref().unwrap_or_default().to_owned()}</td>
+                            <td>{outcome}</td>
+                            <td><code>{&entry.detail}</code></td>
+                        </tr>
+                    }
+                })}
+            </tbody>
+        </table>
+    }
+}
@@ -0,0 +1,164 @@
+use std::time::Duration;
+use gloo_net::http::Request;
+use patternfly_yew::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::use_selector;
+use common::console::{ConsoleCommand, ConsoleEntry, ConsoleOutcome};
+use crate::app::AppState;
+use crate::pages::MyPage;
+use crate::pages::server::format_time;
+
+#[derive(Properties, PartialEq)]
+pub struct ConsolePageProps {
+    pub id: AttrValue,
+}
+
+#[function_component(ConsolePage)]
+pub fn console_page(props: &ConsolePageProps) -> Html {
+    let logged_in = *use_selector(|s: &AppState| s.user_data.is_some());
+    let entries = use_state_eq(|| None::<Result<Vec<ConsoleEntry>, String>>);
+    let command = use_state_eq(String::new);
+    let pending = use_state_eq(|| false);
+    let toaster = use_toaster();
+
+    {
+        let entries = entries.clone();
+        use_effect_with((props.id.clone(), logged_in), move |(id, logged_in)| {
+            if !logged_in {
+                return;
+            }
+            let url = format!("/api/servers/{id}/rcon");
+            spawn_local(async move {
+                let result = match Request::get(&url).send().await {
+                    Ok(resp) if resp.ok() => resp.json::<Vec<ConsoleEntry>>().await.map_err(|e| e.to_string()),
+                    Ok(resp) => Err(resp.status_text()),
+                    Err(e) => Err(e.to_string()),
+                };
+                if let Err(e) = &result {
+                    log::error!("Failed to fetch console log: {e}");
+                }
+                entries.set(Some(result));
+            });
+        });
+    }
+
+    let onsubmit = {
+        let id = props.id.clone();
+        let entries = entries.clone();
+        let command = command.clone();
+        let pending = pending.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            if command.trim().is_empty() || *pending {
+                return;
+            }
+            let url = format!("/api/servers/{id}/rcon");
+            let request = ConsoleCommand { command: (*command).clone() };
+            let entries = entries.clone();
+            let command = command.clone();
+            let pending = pending.clone();
+            let toaster = toaster.clone();
+            pending.set(true);
+            spawn_local(async move {
+                let resp = match Request::post(&url).json(&request) {
+                    Ok(req) => req.send().await,
+                    Err(e) => Err(e),
+                };
+                // Denied and failed commands still come back as an entry
+                let entry = match resp {
+                    Ok(resp) => resp.json::<ConsoleEntry>().await.map_err(|_| resp.status_text()),
+                    Err(e) => Err(e.to_string()),
+                };
+                match entry {
+                    Ok(entry) => {
+                        let mut updated = match &*entries {
+                            Some(Ok(existing)) => existing.clone(),
+                            _ => Vec::new(),
+                        };
+                        updated.push(entry);
+                        entries.set(Some(Ok(updated)));
+                        command.set(String::new());
+                    }
+                    Err(e) => {
+                        if let Some(toaster) = toaster {
+                            toaster.toast(Toast {
+                                title: format!("Failed to run command: {e}"),
+                                timeout: Some(Duration::from_secs(3)),
+                                r#type: AlertType::Danger,
+                                ..Default::default()
+                            });
+                        }
+                    }
+                }
+                pending.set(false);
+            });
+        })
+    };
+
+    let oncommand = {
+        let command = command.clone();
+        Callback::from(move |value: String| command.set(value))
+    };
+
+    let content = match &*entries {
+        _ if !logged_in => html! { {"Please log in to view this page"} },
+        None => html! { <Spinner /> },
+        Some(Err(e)) => html! {
+            <Alert inline=true title="Failed to load console" r#type={AlertType::Danger}>{e.clone()}</Alert>
+        },
+        Some(Ok(entries)) => html! {
+            <Flex modifiers={[FlexModifier::Column]}>
+                <FlexItem>
+                    <pre class="console-log">
+                        {for entries.iter().map(entry_view)}
+                    </pre>
+                </FlexItem>
+                <FlexItem>
+                    <form {onsubmit}>
+                        <Flex>
+                            <FlexItem modifiers={[FlexModifier::Grow]}>
+                                <TextInput
+                                    placeholder="/players"
+                                    value={(*command).clone()}
+                                    onchange={oncommand}
+                                />
+                            </FlexItem>
+                            <FlexItem>
+                                <Button
+                                    variant={ButtonVariant::Primary}
+                                    r#type={ButtonType::Submit}
+                                    disabled={*pending}
+                                >
+                                    {"Run"}
+                                </Button>
+                            </FlexItem>
+                        </Flex>
+                    </form>
+                </FlexItem>
+            </Flex>
+        },
+    };
+
+    html! {
+        <MyPage title={format!("{} console", props.id)}>
+            {content}
+        </MyPage>
+    }
+}
+
+fn entry_view(entry: &ConsoleEntry) -> Html {
+    let outcome = match entry.outcome {
+        ConsoleOutcome::Ok => "",
+        ConsoleOutcome::Denied => " (denied)",
+        ConsoleOutcome::Error => " (failed)",
+    };
+    html! {
+        <>
+            <strong>{format!("[{}] {}> {}{}\n", format_time(entry.at), entry.username, entry.command, outcome)}</strong>
+            if !entry.response.is_empty() {
+                {format!("{}\n", entry.response.trim_end())}
+            }
+        </>
+    }
+}
@@ -1,9 +1,12 @@
-use gloo_net::http::Request;
+use std::rc::Rc;
+use futures::StreamExt;
+use gloo_net::eventsource::futures::{EventSource, EventSourceSubscription};
+use gloo_utils::errors::JsError;
 use patternfly_yew::prelude::{Flex, FlexItem, Spinner};
 use wasm_bindgen_futures::spawn_local;
-use yew::{function_component, html, html_nested, use_effect_with, use_state_eq, AttrValue, Html, Properties};
+use yew::{function_component, html, html_nested, use_effect_with, use_reducer_eq, AttrValue, Html, Properties, Reducible};
 use yewdux::use_selector;
-use common::status::ServerStatus;
+use common::status::{ServerInfo, StatusEvent};
 use crate::app::AppState;
 use crate::components::ServerStatusCard;
 use crate::pages::MyPage;
@@ -13,56 +16,99 @@ pub struct GamePageProps {
     pub game: AttrValue,
 }
 
+#[derive(Default, PartialEq)]
+struct ServerList {
+    loaded: bool,
+    servers: Vec<ServerInfo>,
+}
+
+impl Reducible for ServerList {
+    type Action = StatusEvent;
+
+    fn reduce(self: Rc<Self>, event: StatusEvent) -> Rc<Self> {
+        let mut servers = self.servers.clone();
+        match event {
+            StatusEvent::Snapshot(snapshot) => servers = snapshot,
+            StatusEvent::Updated(server) => {
+                match servers.iter_mut().find(|s| s.id == server.id) {
+                    Some(existing) => *existing = *server,
+                    None => servers.push(*server),
+                }
+            }
+            StatusEvent::Removed(id) => servers.retain(|s| s.id != id),
+        }
+
+        Self {
+            loaded: true,
+            servers,
+        }
+        .into()
+    }
+}
+
 #[function_component(GamePage)]
 pub fn game_page(props: &GamePageProps) -> Html {
     let logged_in = *use_selector(|s: &AppState| s.user_data.is_some());
-    let loading = use_state_eq(|| false);
-    let servers = use_state_eq(Vec::<ServerStatus>::new);
+    let session_expired = *use_selector(|s: &AppState| s.session_expired);
+    let servers = use_reducer_eq(ServerList::default);
     {
-        let servers = servers.clone();
-        let loading = loading.clone();
+        let dispatcher = servers.dispatcher();
         let game = props.game.clone();
         use_effect_with(logged_in, move |_| {
-            if logged_in && !*loading {
-                loading.set(true);
-                spawn_local(async move {
-                    let resp = Request::get("/api/servers/status")
-                        .query([("game", game)])
-                        .send()
-                        .await;
-                    match resp {
-                        Ok(resp) => {
-                            let servers_resp = resp
-                                .json::<Vec<ServerStatus>>()
-                                .await
-                                .expect("Failed to parse server status response");
-                            servers.set(servers_resp);
-                        }
-                        Err(e) => {
-                            log::error!("Error while getting server status: {e}");
-                        }
+            // Dropping the event source closes the connection
+            let mut event_source = None;
+            if logged_in {
+                match subscribe(&game) {
+                    Ok((source, mut events)) => {
+                        event_source = Some(source);
+                        spawn_local(async move {
+                            while let Some(msg) = events.next().await {
+                                let event = match msg {
+                                    Ok((_, msg)) => msg.data().as_string().and_then(|data| {
+                                        serde_json::from_str::<StatusEvent>(&data)
+                                            .map_err(|e| log::error!("Invalid status event: {e}"))
+                                            .ok()
+                                    }),
+                                    Err(e) => {
+                                        // The browser reconnects on its own and the server starts
+                                        // over with a snapshot, so keep listening
+                                        log::warn!("Server status stream interrupted: {e}");
+                                        continue;
+                                    }
+                                };
+                                if let Some(event) = event {
+                                    dispatcher.dispatch(event);
+                                }
+                            }
+                        });
                     }
-                    loading.set(false);
-                });
+                    Err(e) => log::error!("Error while subscribing to server status: {e}"),
+                }
             }
+
+            move || drop(event_source)
         });
     }
 
-    let content = if !logged_in {
+    let content = if session_expired {
+        html! {
+            {"Your login expired, please log in again to view this page"}
+        }
+    } else if !logged_in {
         // window().location().assign("/auth/discord").unwrap();
         html! {
             {"Please log in to view this page"}
         }
-    } else if *loading {
+    } else if !servers.loaded {
         html! {
             <Spinner />
         }
     } else {
         html! {
             <Flex>
-                {for servers.iter().map(|status| html_nested!{
-                    <FlexItem>
-                        <ServerStatusCard status={status.clone()} />
+                {for servers.servers.iter().map(|server| html_nested!{
+                    <FlexItem key={&*server.id}>
+                        <ServerStatusCard server={server.clone()} />
                     </FlexItem>
                 })}
             </Flex>
@@ -74,4 +120,10 @@ pub fn game_page(props: &GamePageProps) -> Html {
             {content}
         </MyPage>
     }
-}
\ No newline at end of file
+}
+
+fn subscribe(game: &str) -> Result<(EventSource, EventSourceSubscription), JsError> {
+    let mut source = EventSource::new(&format!("/api/servers/status/stream?game={game}"))?;
+    let events = source.subscribe("message")?;
+    Ok((source, events))
+}
@@ -1,4 +1,10 @@
+pub mod audit;
+pub mod console;
 pub mod games;
+pub mod mods;
+pub mod saves;
+pub mod server;
+pub mod tokens;
 
 use patternfly_yew::prelude::*;
 use yew::prelude::*;
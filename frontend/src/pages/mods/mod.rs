@@ -0,0 +1,255 @@
+use gloo_net::http::Request;
+use patternfly_yew::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yewdux::use_selector;
+use common::factorio::{ModInfo, ModOverview, ModState};
+use common::status::{Capability, ServerInfo};
+use crate::app::AppState;
+use crate::components::LifecycleControls;
+use crate::pages::MyPage;
+
+#[derive(Properties, PartialEq)]
+pub struct ModsPageProps {
+    pub id: AttrValue,
+}
+
+#[function_component(ModsPage)]
+pub fn mods_page(props: &ModsPageProps) -> Html {
+    let logged_in = *use_selector(|s: &AppState| s.user_data.is_some());
+    let mods = use_state_eq(|| None::<Result<ModOverview, String>>);
+    // For the restart offer, fetched along with the mods
+    let server = use_state_eq(|| None::<ServerInfo>);
+    // Bumped to reload after a change
+    let generation = use_state_eq(|| 0u32);
+    let pending = use_state_eq(|| false);
+    let toaster = use_toaster();
+
+    {
+        let mods = mods.clone();
+        let server = server.clone();
+        use_effect_with((props.id.clone(), logged_in, *generation), move |(id, logged_in, _)| {
+            if !logged_in {
+                return;
+            }
+            let id = id.clone();
+            spawn_local(async move {
+                let url = format!("/api/servers/{id}/mods");
+                let result = match Request::get(&url).send().await {
+                    Ok(resp) if resp.ok() => resp.json::<ModOverview>().await.map_err(|e| e.to_string()),
+                    Ok(resp) => Err(resp.status_text()),
+                    Err(e) => Err(e.to_string()),
+                };
+                if let Err(e) = &result {
+                    log::error!("Failed to fetch mods: {e}");
+                }
+                mods.set(Some(result));
+
+                let servers = match Request::get("/api/servers/status?game=Factorio").send().await {
+                    Ok(resp) if resp.ok() => resp.json::<Vec<ServerInfo>>().await.ok(),
+                    _ => None,
+                };
+                server.set(servers.and_then(|servers| servers.into_iter().find(|s| s.id == *id)));
+            });
+        });
+    }
+
+    let notify = {
+        let toaster = toaster.clone();
+        move |title: String, r#type: AlertType| {
+            if let Some(toaster) = &toaster {
+                toaster.toast(Toast {
+                    title,
+                    timeout: Some(std::time::Duration::from_secs(3)),
+                    r#type,
+                    ..Default::default()
+                });
+            }
+        }
+    };
+
+    let ontoggle = {
+        let id = props.id.clone();
+        let generation = generation.clone();
+        let pending = pending.clone();
+        let notify = notify.clone();
+        Callback::from(move |(name, enabled): (AttrValue, bool)| {
+            let url = format!("/api/servers/{id}/mods/{name}");
+            let generation = generation.clone();
+            let pending = pending.clone();
+            let notify = notify.clone();
+            pending.set(true);
+            spawn_local(async move {
+                let resp = match Request::put(&url).json(&ModState { enabled }) {
+                    Ok(req) => req.send().await,
+                    Err(e) => Err(e),
+                };
+                match resp {
+                    Ok(resp) if resp.ok() => {}
+                    Ok(resp) => notify(format!("Failed to change {name}: {}", resp.status_text()), AlertType::Danger),
+                    Err(e) => notify(format!("Failed to change {name}: {e}"), AlertType::Danger),
+                }
+                pending.set(false);
+                generation.set(*generation + 1);
+            });
+        })
+    };
+
+    let onupload = {
+        let id = props.id.clone();
+        let generation = generation.clone();
+        let pending = pending.clone();
+        Callback::from(move |e: Event| {
+            let input = e.target_unchecked_into::<HtmlInputElement>();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            // Allows picking the same file again
+            input.set_value("");
+            let url = format!("/api/servers/{id}/mods/files/{}", file.name());
+            let generation = generation.clone();
+            let pending = pending.clone();
+            let notify = notify.clone();
+            pending.set(true);
+            spawn_local(async move {
+                let resp = match Request::put(&url).body(file.clone()) {
+                    Ok(req) => req.send().await,
+                    Err(e) => Err(e),
+                };
+                match resp {
+                    Ok(resp) if resp.ok() => notify(format!("Uploaded {}", file.name()), AlertType::Success),
+                    Ok(resp) => {
+                        let reason = resp.text().await.ok().filter(|text| !text.is_empty());
+                        let reason = reason.unwrap_or_else(|| resp.status_text());
+                        notify(format!("Failed to upload {}: {reason}", file.name()), AlertType::Danger);
+                    }
+                    Err(e) => notify(format!("Failed to upload {}: {e}", file.name()), AlertType::Danger),
+                }
+                pending.set(false);
+                generation.set(*generation + 1);
+            });
+        })
+    };
+
+    let content = match &*mods {
+        _ if !logged_in => html! { {"Please log in to view this page"} },
+        None => html! { <Spinner /> },
+        Some(Err(e)) => html! {
+            <Alert inline=true title="Failed to load mods" r#type={AlertType::Danger}>{e.clone()}</Alert>
+        },
+        Some(Ok(overview)) => html! {
+            <Flex modifiers={[FlexModifier::Column]}>
+                {for overview.mods.iter().any(|m| m.mismatch).then(|| html_nested! {
+                    <FlexItem>
+                        {restart_alert(&server)}
+                    </FlexItem>
+                })}
+                {for (!overview.running_known).then(|| html_nested! {
+                    <FlexItem>
+                        <Alert inline=true title="The server isn't reporting its mods" r#type={AlertType::Info}>
+                            {"Changes apply the next time it starts."}
+                        </Alert>
+                    </FlexItem>
+                })}
+                <FlexItem>
+                    <label class="pf-v5-c-button pf-m-secondary">
+                        {"Upload mod"}
+                        <input type="file" accept=".zip" hidden=true disabled={*pending} onchange={onupload} />
+                    </label>
+                </FlexItem>
+                <FlexItem>
+                    {mods_table(&overview.mods, *pending, ontoggle)}
+                </FlexItem>
+            </Flex>
+        },
+    };
+
+    html! {
+        <MyPage title={format!("{} mods", props.id)}>
+            {content}
+        </MyPage>
+    }
+}
+
+fn restart_alert(server: &Option<ServerInfo>) -> Html {
+    let controls = server.as_ref().filter(|server| server.can(Capability::Control));
+    html! {
+        <Alert inline=true title="Restart the server to apply mod changes" r#type={AlertType::Warning}>
+            {match controls {
+                Some(server) => html! {
+                    <LifecycleControls
+                        id={AttrValue::from(server.id.to_string())}
+                        health={server.status.health()}
+                        can_control=true
+                    />
+                },
+                None => html! { {"Ask someone who can restart it."} },
+            }}
+        </Alert>
+    }
+}
+
+fn mods_table(mods: &[ModInfo], pending: bool, ontoggle: Callback<(AttrValue, bool)>) -> Html {
+    if mods.is_empty() {
+        return html! { <p>{"No mods installed"}</p> };
+    }
+
+    html! {
+        <table class="pf-v5-c-table pf-m-compact pf-m-grid-md" role="grid">
+            <thead>
+                <tr>
+                    <th>{"Name"}</th>
+                    <th>{"Enabled"}</th>
+                    <th>{"Installed"}</th>
+                    <th>{"Running"}</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+                {for mods.iter().map(|info| {
+                    let name = AttrValue::from(info.name.to_string());
+                    let onchange = ontoggle.reform(move |enabled| (name.clone(), enabled));
+                    html! {
+                        <tr key={&*info.name}>
+                            <td>{info.name.to_string()}</td>
+                            <td>
+                                <Switch
+                                    checked={info.enabled}
+                                    disabled={pending}
+                                    {onchange}
+                                />
+                            </td>
+                            <td>{installed_text(info)}</td>
+                            <td>{info.running.as_deref().unwrap_or("-")}</td>
+                            <td>
+                                if info.mismatch {
+                                    <Label color={Color::Orange} label="Restart needed" />
+                                }
+                            </td>
+                        </tr>
+                    }
+                })}
+            </tbody>
+        </table>
+    }
+}
+
+/// The installed versions, with the one the server loads in bold if there are several
+fn installed_text(info: &ModInfo) -> Html {
+    if info.installed.is_empty() {
+        return html! { {"Built in"} };
+    }
+    html! {
+        {for info.installed.iter().enumerate().map(|(i, version)| html! {
+            <>
+                if i > 0 { {", "} }
+                if info.installed.len() > 1 && info.expected.as_ref() == Some(version) {
+                    <strong>{version.to_string()}</strong>
+                } else {
+                    {version.to_string()}
+                }
+            </>
+        })}
+    }
+}
@@ -0,0 +1,160 @@
+use gloo_net::http::Request;
+use patternfly_yew::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::use_selector;
+use common::factorio::{SaveFile, SaveList};
+use crate::app::AppState;
+use crate::pages::MyPage;
+use crate::pages::server::format_time;
+
+#[derive(Properties, PartialEq)]
+pub struct SavesPageProps {
+    pub id: AttrValue,
+}
+
+#[function_component(SavesPage)]
+pub fn saves_page(props: &SavesPageProps) -> Html {
+    let logged_in = *use_selector(|s: &AppState| s.user_data.is_some());
+    let saves = use_state_eq(|| None::<Result<SaveList, String>>);
+    // Bumped to reload the list after saving
+    let generation = use_state_eq(|| 0u32);
+    let pending = use_state_eq(|| false);
+    let toaster = use_toaster();
+
+    {
+        let saves = saves.clone();
+        use_effect_with((props.id.clone(), logged_in, *generation), move |(id, logged_in, _)| {
+            if !logged_in {
+                return;
+            }
+            let url = format!("/api/servers/{id}/saves");
+            spawn_local(async move {
+                let result = match Request::get(&url).send().await {
+                    Ok(resp) if resp.ok() => resp.json::<SaveList>().await.map_err(|e| e.to_string()),
+                    Ok(resp) => Err(resp.status_text()),
+                    Err(e) => Err(e.to_string()),
+                };
+                if let Err(e) = &result {
+                    log::error!("Failed to fetch saves: {e}");
+                }
+                saves.set(Some(result));
+            });
+        });
+    }
+
+    let onsave = {
+        let id = props.id.clone();
+        let generation = generation.clone();
+        let pending = pending.clone();
+        Callback::from(move |_| {
+            let url = format!("/api/servers/{id}/saves");
+            let generation = generation.clone();
+            let pending = pending.clone();
+            let toaster = toaster.clone();
+            pending.set(true);
+            spawn_local(async move {
+                let (title, r#type) = match Request::post(&url).send().await {
+                    Ok(resp) if resp.ok() => ("Saving, refresh in a moment".to_owned(), AlertType::Success),
+                    Ok(resp) => (format!("Failed to save: {}", resp.status_text()), AlertType::Danger),
+                    Err(e) => (format!("Failed to save: {e}"), AlertType::Danger),
+                };
+                if let Some(toaster) = &toaster {
+                    toaster.toast(Toast {
+                        title,
+                        timeout: Some(std::time::Duration::from_secs(3)),
+                        r#type,
+                        ..Default::default()
+                    });
+                }
+                pending.set(false);
+                generation.set(*generation + 1);
+            });
+        })
+    };
+
+    let onrefresh = {
+        let generation = generation.clone();
+        Callback::from(move |_| generation.set(*generation + 1))
+    };
+
+    let content = match &*saves {
+        _ if !logged_in => html! { {"Please log in to view this page"} },
+        None => html! { <Spinner /> },
+        Some(Err(e)) => html! {
+            <Alert inline=true title="Failed to load saves" r#type={AlertType::Danger}>{e.clone()}</Alert>
+        },
+        Some(Ok(list)) => html! {
+            <Flex modifiers={[FlexModifier::Column]}>
+                <Flex>
+                    <FlexItem>
+                        <Button variant={ButtonVariant::Primary} disabled={*pending} onclick={onsave}>
+                            {"Save now"}
+                        </Button>
+                    </FlexItem>
+                    <FlexItem>
+                        <Button variant={ButtonVariant::Secondary} onclick={onrefresh}>
+                            {"Refresh"}
+                        </Button>
+                    </FlexItem>
+                </Flex>
+                <FlexItem>
+                    <Title level={Level::H2}>{"Saves"}</Title>
+                    {saves_table(&props.id, "saves", &list.saves)}
+                </FlexItem>
+                {for (!list.backups.is_empty()).then(|| html_nested! {
+                    <FlexItem>
+                        <Title level={Level::H2}>{"Backups"}</Title>
+                        {saves_table(&props.id, "saves/backups", &list.backups)}
+                    </FlexItem>
+                })}
+            </Flex>
+        },
+    };
+
+    html! {
+        <MyPage title={format!("{} saves", props.id)}>
+            {content}
+        </MyPage>
+    }
+}
+
+fn saves_table(id: &str, path: &str, saves: &[SaveFile]) -> Html {
+    if saves.is_empty() {
+        return html! { <p>{"No saves yet"}</p> };
+    }
+
+    html! {
+        <table class="pf-v5-c-table pf-m-compact pf-m-grid-md" role="grid">
+            <thead>
+                <tr>
+                    <th>{"Name"}</th>
+                    <th>{"Size"}</th>
+                    <th>{"Modified"}</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+                {for saves.iter().map(|save| html! {
+                    <tr key={&*save.name}>
+                        <td>{save.name.to_string()}</td>
+                        <td>{format_size(save.size)}</td>
+                        <td>{format_time(save.modified)}</td>
+                        <td>
+                            <a href={format!("/api/servers/{id}/{path}/{}", save.name)} download="">
+                                {"Download"}
+                            </a>
+                        </td>
+                    </tr>
+                })}
+            </tbody>
+        </table>
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1_000_000 => format!("{:.0} kB", bytes as f64 / 1e3),
+        _ => format!("{:.1} MB", bytes as f64 / 1e6),
+    }
+}
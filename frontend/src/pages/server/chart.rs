@@ -0,0 +1,73 @@
+use common::history::StatusSample;
+use yew::prelude::*;
+
+const WIDTH: f64 = 1000.0;
+const HEIGHT: f64 = 200.0;
+
+#[derive(Properties, PartialEq)]
+pub struct PlayerChartProps {
+    /// Start of the charted range in Unix seconds
+    pub from: i64,
+    /// End of the charted range in Unix seconds
+    pub to: i64,
+    pub samples: Vec<StatusSample>,
+}
+
+/// Step chart of the player count over time. Gaps mark samples without a player count,
+/// e.g. while the server was offline.
+#[function_component(PlayerChart)]
+pub fn player_chart(props: &PlayerChartProps) -> Html {
+    let max = props
+        .samples
+        .iter()
+        .filter_map(|s| s.players_online)
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    let span = (props.to - props.from).max(1) as f64;
+    let x = |at: i64| (at - props.from) as f64 / span * WIDTH;
+    let y = |players: u32| HEIGHT - players as f64 / max as f64 * HEIGHT;
+
+    let mut lines = Vec::new();
+    let mut points = String::new();
+    for sample in &props.samples {
+        match sample.players_online {
+            Some(players) => {
+                if !points.is_empty() {
+                    // Hold the previous count until this sample, so changes show as steps
+                    let last_y = points.rsplit(',').next().unwrap_or_default().to_owned();
+                    points.push_str(&format!(" {:.1},{last_y}", x(sample.at)));
+                }
+                points.push_str(&format!(" {:.1},{:.1}", x(sample.at), y(players)));
+            }
+            None if !points.is_empty() => lines.push(std::mem::take(&mut points)),
+            None => {}
+        }
+    }
+    if !points.is_empty() {
+        lines.push(points);
+    }
+
+    html! {
+        <svg
+            viewBox={format!("0 0 {WIDTH} {HEIGHT}")}
+            preserveAspectRatio="none"
+            width="100%"
+            height="200"
+            role="img"
+            aria-label={format!("Player count, peaking at {max}")}
+        >
+            <line x1="0" y1={HEIGHT.to_string()} x2={WIDTH.to_string()} y2={HEIGHT.to_string()} stroke="gray" />
+            <text x="4" y="14" font-size="12" fill="gray">{max.to_string()}</text>
+            {for lines.into_iter().map(|points| html! {
+                <polyline
+                    points={points}
+                    fill="none"
+                    stroke="var(--pf-v5-global--primary-color--100)"
+                    stroke-width="2"
+                    vector-effect="non-scaling-stroke"
+                />
+            })}
+        </svg>
+    }
+}
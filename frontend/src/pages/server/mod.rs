@@ -0,0 +1,210 @@
+mod chart;
+
+use std::collections::HashMap;
+use gloo_net::http::Request;
+use patternfly_yew::prelude::*;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::use_selector;
+use common::history::{PlayerEventKind, ServerHistory};
+use common::status::HealthStatus;
+use crate::app::AppState;
+use crate::pages::MyPage;
+use crate::pages::server::chart::PlayerChart;
+
+/// How many sessions to list below the chart
+const MAX_SESSIONS: usize = 50;
+
+const RANGES: [(&str, i64); 3] = [
+    ("24 hours", 24 * 60 * 60),
+    ("7 days", 7 * 24 * 60 * 60),
+    ("30 days", 30 * 24 * 60 * 60),
+];
+
+#[derive(Properties, PartialEq)]
+pub struct ServerPageProps {
+    pub id: AttrValue,
+}
+
+#[function_component(ServerPage)]
+pub fn server_page(props: &ServerPageProps) -> Html {
+    let logged_in = *use_selector(|s: &AppState| s.user_data.is_some());
+    let session_expired = *use_selector(|s: &AppState| s.session_expired);
+    let range = use_state_eq(|| RANGES[1].1);
+    let history = use_state_eq(|| None::<Result<(i64, i64, ServerHistory), String>>);
+    {
+        let history = history.clone();
+        use_effect_with((props.id.clone(), *range, logged_in), move |(id, range, logged_in)| {
+            if !logged_in {
+                return;
+            }
+            history.set(None);
+            let to = (js_sys::Date::now() / 1000.0) as i64;
+            let from = to - range;
+            let url = format!("/api/servers/{id}/history?from={from}&to={to}");
+            spawn_local(async move {
+                let result = match Request::get(&url).send().await {
+                    Ok(resp) if resp.ok() => resp.json::<ServerHistory>().await.map_err(|e| e.to_string()),
+                    Ok(resp) => Err(resp.status_text()),
+                    Err(e) => Err(e.to_string()),
+                };
+                if let Err(e) = &result {
+                    log::error!("Failed to fetch server history: {e}");
+                }
+                history.set(Some(result.map(|h| (from, to, h))));
+            });
+        });
+    }
+
+    let range_buttons = RANGES.iter().map(|&(label, secs)| {
+        let range = range.clone();
+        let variant = if *range == secs { ButtonVariant::Primary } else { ButtonVariant::Secondary };
+        html_nested! {
+            <FlexItem>
+                <Button {variant} onclick={Callback::from(move |_| range.set(secs))}>{label}</Button>
+            </FlexItem>
+        }
+    });
+
+    let content = match &*history {
+        _ if session_expired => html! { {"Your login expired, please log in again to view this page"} },
+        _ if !logged_in => html! { {"Please log in to view this page"} },
+        None => html! { <Spinner /> },
+        Some(Err(e)) => html! {
+            <Alert inline=true title="Failed to load history" r#type={AlertType::Danger}>{e.clone()}</Alert>
+        },
+        Some(Ok((from, to, history))) => history_view(*from, *to, history),
+    };
+
+    html! {
+        <MyPage title={props.id.clone()}>
+            <Flex>{for range_buttons}</Flex>
+            {content}
+        </MyPage>
+    }
+}
+
+fn history_view(from: i64, to: i64, history: &ServerHistory) -> Html {
+    let uptime = if history.samples.is_empty() {
+        "No data".to_owned()
+    } else {
+        let running = history
+            .samples
+            .iter()
+            .filter(|s| s.health == HealthStatus::Running)
+            .count();
+        format!("{:.1}%", running as f64 * 100.0 / history.samples.len() as f64)
+    };
+    let peak_players = history
+        .samples
+        .iter()
+        .filter_map(|s| s.players_online)
+        .max()
+        .unwrap_or_default();
+
+    html! {
+        <Flex modifiers={[FlexModifier::Column]}>
+            <FlexItem>
+                <DescriptionList mode={[DescriptionListMode::Horizontal]}>
+                    <DescriptionGroup term="Uptime">{uptime}</DescriptionGroup>
+                    <DescriptionGroup term="Peak players">{peak_players.to_string()}</DescriptionGroup>
+                    <DescriptionGroup term="Last online">
+                        {history.last_online.map(format_time).unwrap_or_else(|| "Never".to_owned())}
+                    </DescriptionGroup>
+                </DescriptionList>
+            </FlexItem>
+            <FlexItem>
+                <Title level={Level::H2}>{"Players online"}</Title>
+                <PlayerChart {from} {to} samples={history.samples.clone()} />
+            </FlexItem>
+            <FlexItem>
+                <Title level={Level::H2}>{"Recent sessions"}</Title>
+                {sessions_table(history)}
+            </FlexItem>
+        </Flex>
+    }
+}
+
+struct Session<'a> {
+    player: &'a str,
+    /// `None` if the player joined before the start of the range
+    start: Option<i64>,
+    /// `None` if the player is still online
+    end: Option<i64>,
+}
+
+/// Pairs up join and leave events into play sessions, most recent first
+fn sessions(history: &ServerHistory) -> Vec<Session<'_>> {
+    let mut sessions = Vec::new();
+    let mut open = HashMap::new();
+    for event in &history.player_events {
+        match event.kind {
+            PlayerEventKind::Join => {
+                open.insert(&*event.player, event.at);
+            }
+            PlayerEventKind::Leave => sessions.push(Session {
+                player: &event.player,
+                start: open.remove(&*event.player),
+                end: Some(event.at),
+            }),
+        }
+    }
+    sessions.extend(open.into_iter().map(|(player, start)| Session {
+        player,
+        start: Some(start),
+        end: None,
+    }));
+
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.end.unwrap_or(i64::MAX)));
+    sessions.truncate(MAX_SESSIONS);
+    sessions
+}
+
+fn sessions_table(history: &ServerHistory) -> Html {
+    let sessions = sessions(history);
+    if sessions.is_empty() {
+        return html! { <p>{"Nobody played in this period"}</p> };
+    }
+
+    html! {
+        <table class="pf-v5-c-table pf-m-compact pf-m-grid-md" role="grid">
+            <thead>
+                <tr>
+                    <th>{"Player"}</th>
+                    <th>{"Joined"}</th>
+                    <th>{"Left"}</th>
+                    <th>{"Duration"}</th>
+                </tr>
+            </thead>
+            <tbody>
+                {for sessions.iter().map(|s| html! {
+                    <tr>
+                        <td>{s.player}</td>
+                        <td>{s.start.map(format_time).unwrap_or_else(|| "Before this period".to_owned())}</td>
+                        <td>{s.end.map(format_time).unwrap_or_else(|| "Still online".to_owned())}</td>
+                        <td>{match (s.start, s.end) {
+                            (Some(start), Some(end)) => format_span(end - start),
+                            _ => String::new(),
+                        }}</td>
+                    </tr>
+                })}
+            </tbody>
+        </table>
+    }
+}
+
+/// Formats Unix seconds in the browser's locale and timezone
+pub(crate) fn format_time(at: i64) -> String {
+    js_sys::Date::new(&JsValue::from_f64(at as f64 * 1000.0))
+        .to_locale_string("default", &JsValue::UNDEFINED)
+        .into()
+}
+
+fn format_span(secs: i64) -> String {
+    let (hours, mins) = (secs / 3600, secs % 3600 / 60);
+    match hours {
+        0 => format!("{mins}m"),
+        _ => format!("{hours}h {mins}m"),
+    }
+}
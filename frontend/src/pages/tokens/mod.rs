@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+use gloo_net::http::Request;
+use patternfly_yew::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::use_selector;
+use common::status::Capability;
+use common::user::{ApiToken, CreatedApiToken, NewApiToken};
+use crate::app::AppState;
+use crate::components::copy_to_clipboard;
+use crate::pages::MyPage;
+use crate::pages::server::format_time;
+
+/// Capabilities a token can be scoped to. `EditConfig` isn't offered, nothing checks it yet.
+const CAPABILITIES: [(Capability, &str); 7] = [
+    (Capability::View, "View"),
+    (Capability::ViewSecrets, "View passwords"),
+    (Capability::Wake, "Wake"),
+    (Capability::Control, "Start, stop and restart"),
+    (Capability::Console, "Console"),
+    (Capability::Saves, "Saves"),
+    (Capability::Mods, "Mods"),
+];
+
+fn capability_label(capability: Capability) -> &'static str {
+    CAPABILITIES
+        .iter()
+        .find(|(c, _)| *c == capability)
+        .map(|(_, label)| *label)
+        .unwrap_or_default()
+}
+
+#[function_component(TokensPage)]
+pub fn tokens_page() -> Html {
+    let logged_in = *use_selector(|s: &AppState| s.user_data.is_some());
+    let tokens = use_state_eq(|| None::<Result<Vec<ApiToken>, String>>);
+    // Bumped to reload the list after creating or revoking a token
+    let generation = use_state_eq(|| 0u32);
+    let created = use_state_eq(|| None::<CreatedApiToken>);
+    let name = use_state_eq(String::new);
+    let capabilities = use_state_eq(|| HashSet::from([Capability::View]));
+
+    {
+        let tokens = tokens.clone();
+        use_effect_with((logged_in, *generation), move |(logged_in, _)| {
+            if !logged_in {
+                return;
+            }
+            spawn_local(async move {
+                let result = match Request::get("/api/tokens").send().await {
+                    Ok(resp) if resp.ok() => resp.json::<Vec<ApiToken>>().await.map_err(|e| e.to_string()),
+                    Ok(resp) => Err(resp.status_text()),
+                    Err(e) => Err(e.to_string()),
+                };
+                if let Err(e) = &result {
+                    log::error!("Failed to fetch API tokens: {e}");
+                }
+                tokens.set(Some(result));
+            });
+        });
+    }
+
+    let toaster = use_toaster();
+    let toast_error = {
+        let toaster = toaster.clone();
+        Callback::from(move |title: String| {
+            if let Some(toaster) = &toaster {
+                toaster.toast(Toast {
+                    title,
+                    timeout: Some(std::time::Duration::from_secs(3)),
+                    r#type: AlertType::Danger,
+                    ..Default::default()
+                });
+            }
+        })
+    };
+
+    let oncreate = {
+        let name = name.clone();
+        let capabilities = capabilities.clone();
+        let created = created.clone();
+        let generation = generation.clone();
+        let toast_error = toast_error.clone();
+        Callback::from(move |_| {
+            let request = NewApiToken {
+                name: name.trim().into(),
+                capabilities: (*capabilities).clone(),
+            };
+            let name = name.clone();
+            let created = created.clone();
+            let generation = generation.clone();
+            let toast_error = toast_error.clone();
+            spawn_local(async move {
+                let resp = match Request::post("/api/tokens").json(&request) {
+                    Ok(req) => req.send().await,
+                    Err(e) => Err(e),
+                };
+                match resp {
+                    Ok(resp) if resp.ok() => match resp.json::<CreatedApiToken>().await {
+                        Ok(token) => {
+                            created.set(Some(token));
+                            name.set(String::new());
+                            generation.set(*generation + 1);
+                        }
+                        Err(e) => toast_error.emit(format!("Failed to create token: {e}")),
+                    },
+                    Ok(resp) => toast_error.emit(format!("Failed to create token: {}", resp.status_text())),
+                    Err(e) => toast_error.emit(format!("Failed to create token: {e}")),
+                }
+            });
+        })
+    };
+
+    let onrevoke = {
+        let generation = generation.clone();
+        Callback::from(move |id: i64| {
+            let generation = generation.clone();
+            let toast_error = toast_error.clone();
+            spawn_local(async move {
+                match Request::delete(&format!("/api/tokens/{id}")).send().await {
+                    Ok(resp) if resp.ok() => generation.set(*generation + 1),
+                    Ok(resp) => toast_error.emit(format!("Failed to revoke token: {}", resp.status_text())),
+                    Err(e) => toast_error.emit(format!("Failed to revoke token: {e}")),
+                }
+            });
+        })
+    };
+
+    if !logged_in {
+        return html! {
+            <MyPage title="API tokens">
+                {"Please log in to view this page"}
+            </MyPage>
+        };
+    }
+
+    let checkboxes = CAPABILITIES.iter().map(|&(capability, label)| {
+        let capabilities = capabilities.clone();
+        let checked = capabilities.contains(&capability);
+        let onchange = Callback::from(move |state: CheckboxState| {
+            let mut updated = (*capabilities).clone();
+            match state {
+                CheckboxState::Checked => updated.insert(capability),
+                _ => updated.remove(&capability),
+            };
+            capabilities.set(updated);
+        });
+        html_nested! {
+            <FlexItem>
+                <Checkbox label={label} checked={CheckboxState::from(checked)} {onchange} />
+            </FlexItem>
+        }
+    });
+
+    let created_alert = created.as_ref().map(|token| html_nested! {
+        <FlexItem>
+            <Alert
+                inline=true
+                title={format!("Created token {}. Copy it now, it won't be shown again.", token.token.name)}
+                r#type={AlertType::Success}
+            >
+                <Flex>
+                    <FlexItem>
+                        <TextInput readonly=true value={token.secret.clone()} />
+                    </FlexItem>
+                    <FlexItem>
+                        <Button
+                            onclick={toaster.clone().map(|toaster| copy_to_clipboard("Token", &token.secret, toaster)).unwrap_or_default()}
+                            variant={ButtonVariant::Plain}
+                            icon={Icon::Copy}
+                            aria_label="Copy token" />
+                    </FlexItem>
+                </Flex>
+            </Alert>
+        </FlexItem>
+    });
+
+    let onname = {
+        let name = name.clone();
+        Callback::from(move |value: String| name.set(value))
+    };
+
+    let list = match &*tokens {
+        None => html! { <Spinner /> },
+        Some(Err(e)) => html! {
+            <Alert inline=true title="Failed to load API tokens" r#type={AlertType::Danger}>{e.clone()}</Alert>
+        },
+        Some(Ok(tokens)) => tokens_table(tokens, onrevoke),
+    };
+
+    html! {
+        <MyPage title="API tokens">
+            <Flex modifiers={[FlexModifier::Column]}>
+                <FlexItem>
+                    <p>{"Tokens let scripts use the API with "}<code>{"Authorization: Bearer <token>"}</code>
+                    {". A token can never do more than you can, and stops working after 90 days."}</p>
+                </FlexItem>
+                <FlexItem>
+                    <TextInput
+                        placeholder="Token name"
+                        value={(*name).clone()}
+                        onchange={onname}
+                    />
+                </FlexItem>
+                <Flex>{for checkboxes}</Flex>
+                <FlexItem>
+                    <Button
+                        variant={ButtonVariant::Primary}
+                        disabled={name.trim().is_empty() || capabilities.is_empty()}
+                        onclick={oncreate}
+                    >
+                        {"Create token"}
+                    </Button>
+                </FlexItem>
+                {for created_alert}
+                <FlexItem>{list}</FlexItem>
+            </Flex>
+        </MyPage>
+    }
+}
+
+fn tokens_table(tokens: &[ApiToken], onrevoke: Callback<i64>) -> Html {
+    if tokens.is_empty() {
+        return html! { <p>{"You don't have any API tokens yet"}</p> };
+    }
+
+    html! {
+        <table class="pf-v5-c-table pf-m-compact pf-m-grid-md" role="grid">
+            <thead>
+                <tr>
+                    <th>{"Name"}</th>
+                    <th>{"Allows"}</th>
+                    <th>{"Created"}</th>
+                    <th>{"Last used"}</th>
+                    <th>{"Expires"}</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+                {for tokens.iter().map(|token| {
+                    let id = token.id;
+                    let mut allows = token.capabilities.iter()
+                        .map(|&c| capability_label(c))
+                        .filter(|label| !label.is_empty())
+                        .collect::<Vec<_>>();
+                    allows.sort_unstable();
+                    html! {
+                        <tr>
+                            <td>{token.name.to_string()}</td>
+                            <td>{allows.join(", ")}</td>
+                            <td>{format_time(token.created_at)}</td>
+                            <td>{token.last_used_at.map(format_time).unwrap_or_else(|| "Never".to_owned())}</td>
+                            <td>{format_time(token.expires_at)}</td>
+                            <td>
+                                <Button variant={ButtonVariant::Danger} onclick={onrevoke.reform(move |_| id)}>
+                                    {"Revoke"}
+                                </Button>
+                            </td>
+                        </tr>
+                    }
+                })}
+            </tbody>
+        </table>
+    }
+}
@@ -4,6 +4,8 @@
     { config, lib, pkgs, ... }:
     let
       cfg = config.services.homelab-server-manager;
+      # The server picks the format by extension, so the credential has to keep it
+      configName = "config.${lib.last (lib.splitString "." (baseNameOf cfg.configFile))}";
     in with lib; {
       options.services.homelab-server-manager = {
         enable = mkEnableOption "homelab-server-manager Game server";
@@ -26,14 +28,41 @@
         configFile = mkOption {
           type = types.nullOr types.str;
           default = null;
-          description = "Path to the config file. DO NOT USE /nix/store PATHS.";
+          description = ''
+            Path to the config file, in JSON, TOML or YAML by extension. DO NOT USE /nix/store PATHS.
+          '';
+        };
+        serverDirectory = mkOption {
+          type = types.nullOr types.str;
+          default = null;
+          example = "/etc/homelab-server-manager/servers";
+          description = ''
+            Directory of extra config files in JSON, TOML or YAML, each with one server or a `servers` list.
+            Must be readable by the service's user. Keep secrets out of it, see `credentials`.
+          '';
+        };
+        credentials = mkOption {
+          type = types.attrsOf types.str;
+          default = { };
+          example = { rcon-password = "/run/secrets/factorio-rcon"; };
+          description = ''
+            Files to pass to the service as systemd credentials, e.g. from agenix or sops-nix.
+            Refer to them in secret config values as
+            `{ "file": "/run/credentials/homelab-server-manager.service/<name>" }`.
+            Other strings are used as they are; `{ "env": "NAME" }` reads an environment variable.
+          '';
         };
         envFile = mkOption {
           type = types.str;
           description = ''
             Path an environment file. DO NOT USE /nix/store PATHS.
 
-            Must contain DISCORD_CLIENT_ID and DISCORD_CLIENT_SECRET
+            Must contain DISCORD_CLIENT_ID and DISCORD_CLIENT_SECRET.
+            Set DISCORD_BOT_TOKEN as well to enable the slash command bot.
+            Set OIDC_ISSUER_URL, OIDC_CLIENT_ID and OIDC_CLIENT_SECRET to allow logging in
+            through an OpenID Connect provider. OIDC_SCOPES (default "profile"),
+            OIDC_GROUPS_CLAIM (default "groups", dots for nested claims) and
+            OIDC_DISPLAY_NAME are optional.
           '';
         };
         publicUrl = mkOption {
@@ -44,6 +73,41 @@
             Public URL. Mainly used for OAuth redirection.
           '';
         };
+        controlledUnits = mkOption {
+          type = types.listOf types.str;
+          default = [ ];
+          example = [ "factorio.service" ];
+          description = ''
+            systemd units the service may start, stop and restart.
+            Must match the units used by servers with systemd control configured.
+          '';
+        };
+        dockerAccess = mkOption {
+          type = types.bool;
+          default = false;
+          description = ''
+            Whether to add the service to the docker group, for servers with docker control configured.
+            Access to the docker socket is equivalent to root access.
+          '';
+        };
+        savesDirectories = mkOption {
+          type = types.listOf types.str;
+          default = [ ];
+          example = [ "/var/lib/factorio/saves" ];
+          description = ''
+            Factorio saves directories, and backup directories outside of them, the service may write to.
+            Must match the saves_dir and backups of servers with save management configured.
+            The files must also be readable by the service's user, e.g. through a shared group.
+          '';
+        };
+        modsDirectories = mkOption {
+          type = types.listOf types.str;
+          default = [ ];
+          example = [ "/var/lib/factorio/mods" ];
+          description = ''
+            Factorio mods directories the service may write to. Must match the mods_dir of servers with mod management configured.
+          '';
+        };
         openFirewall = mkOption {
           type = types.bool;
           default = false;
@@ -58,7 +122,8 @@
             "--addr=${cfg.address}"
             "--port=${toString cfg.port}"
             "--public-url='${cfg.publicUrl}'"
-            ] ++ (lib.optional (cfg.configFile != null) "--config-file=%d/config.json");
+            ] ++ (lib.optional (cfg.configFile != null) "--config-file=%d/${configName}")
+              ++ (lib.optional (cfg.serverDirectory != null) "--server-dir=${cfg.serverDirectory}");
           argString = builtins.concatStringsSep " \\\n" args;
         in {
           description = "Homelab Server Manager";
@@ -77,7 +142,11 @@
 
             EnvironmentFile = cfg.envFile;
 
-            LoadCredential = lib.optional (cfg.configFile != null) "config.json:${cfg.configFile}";
+            SupplementaryGroups = lib.optional cfg.dockerAccess "docker";
+            ReadWritePaths = cfg.savesDirectories ++ cfg.modsDirectories;
+
+            LoadCredential = (lib.optional (cfg.configFile != null) "${configName}:${cfg.configFile}")
+              ++ (lib.mapAttrsToList (name: path: "${name}:${path}") cfg.credentials);
           };
 
           environment = {
@@ -85,6 +154,16 @@
           };
         };
 
+        security.polkit.extraConfig = mkIf (cfg.controlledUnits != [ ]) ''
+          polkit.addRule(function(action, subject) {
+            if (action.id == "org.freedesktop.systemd1.manage-units" &&
+                subject.user == "homelab-server-manager" &&
+                ${builtins.toJSON cfg.controlledUnits}.indexOf(action.lookup("unit")) >= 0) {
+              return polkit.Result.YES;
+            }
+          });
+        '';
+
         networking.firewall = mkIf cfg.openFirewall {
           allowedTCPPorts = [ cfg.port ];
           allowedUDPPorts = [ cfg.port ];
@@ -27,6 +27,7 @@
         filter = path: type:
           (hasSuffix "\.html" path)  ||
           (hasSuffix "\.scss" path)  ||
+          (hasSuffix "\.sql" path)   ||
           (hasInfix "/assets/" path) ||
           (craneLib.filterCargoSources path type);
       };
@@ -0,0 +1,19 @@
+CREATE TABLE status_samples
+(
+    server_id      TEXT    NOT NULL,
+    sampled_at     INTEGER NOT NULL,
+    health         TEXT    NOT NULL,
+    players_online INTEGER
+);
+
+CREATE INDEX status_samples_server_time ON status_samples (server_id, sampled_at);
+
+CREATE TABLE player_events
+(
+    server_id   TEXT    NOT NULL,
+    occurred_at INTEGER NOT NULL,
+    player      TEXT    NOT NULL,
+    kind        TEXT    NOT NULL CHECK (kind IN ('join', 'leave'))
+);
+
+CREATE INDEX player_events_server_time ON player_events (server_id, occurred_at);
@@ -0,0 +1,18 @@
+CREATE TABLE api_tokens
+(
+    id           INTEGER PRIMARY KEY,
+    owner        TEXT    NOT NULL,
+    owner_name   TEXT    NOT NULL,
+    name         TEXT    NOT NULL,
+    token_hash   TEXT    NOT NULL UNIQUE,
+    -- JSON array of the capabilities the token is limited to
+    capabilities TEXT    NOT NULL,
+    -- JSON snapshot of the owner's principal, refreshed whenever their roles are fetched
+    principal    TEXT    NOT NULL,
+    created_at   INTEGER NOT NULL,
+    -- Tokens only carry a snapshot of their owner's roles, so they mustn't work forever
+    expires_at   INTEGER NOT NULL,
+    last_used_at INTEGER
+);
+
+CREATE INDEX api_tokens_owner ON api_tokens (owner);
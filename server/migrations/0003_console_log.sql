@@ -0,0 +1,13 @@
+CREATE TABLE console_log
+(
+    server_id   TEXT    NOT NULL,
+    occurred_at INTEGER NOT NULL,
+    -- Account that ran the command, e.g. `discord:<user id>`
+    actor       TEXT    NOT NULL,
+    username    TEXT    NOT NULL,
+    command     TEXT    NOT NULL,
+    outcome     TEXT    NOT NULL CHECK (outcome IN ('ok', 'denied', 'error')),
+    response    TEXT    NOT NULL
+);
+
+CREATE INDEX console_log_server_time ON console_log (server_id, occurred_at);
@@ -0,0 +1,16 @@
+CREATE TABLE audit_log
+(
+    id          INTEGER PRIMARY KEY,
+    occurred_at INTEGER NOT NULL,
+    -- Account that acted, e.g. `discord:<user id>`. NULL for the server itself.
+    actor       TEXT,
+    username    TEXT,
+    action      TEXT    NOT NULL,
+    server_id   TEXT,
+    outcome     TEXT    NOT NULL CHECK (outcome IN ('ok', 'denied', 'error')),
+    detail      TEXT    NOT NULL
+);
+
+CREATE INDEX audit_log_time ON audit_log (occurred_at);
+CREATE INDEX audit_log_actor ON audit_log (actor, id);
+CREATE INDEX audit_log_server ON audit_log (server_id, id);
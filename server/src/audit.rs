@@ -0,0 +1,201 @@
+use crate::servers::unix_now;
+use crate::{AppState, UserData};
+use axum::extract::FromRef;
+use common::audit::{AuditAction, AuditEntry, AuditOutcome, AuditPage};
+use common::status::ServerInfo;
+use moka::future::{Cache, CacheBuilder};
+use serde::Deserialize;
+use smol_str::SmolStr;
+use sqlx::SqlitePool;
+use std::time::Duration;
+
+/// Page size when the client doesn't ask for one
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+/// Passwords are sent with every status update, so a reveal is only recorded once per user and
+/// server in this window
+const SECRET_REVEAL_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Who performed an audited action
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub id: String,
+    pub username: String,
+}
+
+impl From<&UserData> for Actor {
+    fn from(user: &UserData) -> Self {
+        Self {
+            id: user.owner_id(),
+            username: user.username().to_owned(),
+        }
+    }
+}
+
+/// Filters of `/api/audit`. Every filter is optional.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    /// Exact account ID, or part of a username
+    pub actor: Option<String>,
+    pub server: Option<String>,
+    pub action: Option<String>,
+    /// Unix seconds, inclusive
+    pub from: Option<i64>,
+    /// Unix seconds, inclusive
+    pub to: Option<i64>,
+    /// Only entries older than this entry ID, see [AuditPage::next_before]
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Persistent record of privileged actions, kept in SQLite. Recording never fails the action
+/// itself, errors are only logged.
+#[derive(Clone)]
+pub struct AuditLog {
+    pool: SqlitePool,
+    revealed: Cache<(String, SmolStr), ()>,
+}
+
+impl FromRef<AppState> for AuditLog {
+    fn from_ref(input: &AppState) -> Self {
+        input.audit.clone()
+    }
+}
+
+type AuditRow = (
+    i64,
+    i64,
+    Option<String>,
+    Option<String>,
+    String,
+    Option<String>,
+    String,
+    String,
+);
+
+impl AuditLog {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            revealed: CacheBuilder::new(10_000)
+                .time_to_live(SECRET_REVEAL_WINDOW)
+                .build(),
+        }
+    }
+
+    /// Records an action. `actor` is `None` for actions the server takes on its own.
+    pub async fn record(
+        &self,
+        actor: Option<&Actor>,
+        action: AuditAction,
+        server_id: Option<&str>,
+        outcome: AuditOutcome,
+        detail: &str,
+    ) {
+        let result = sqlx::query(
+            "INSERT INTO audit_log
+             (occurred_at, actor, username, action, server_id, outcome, detail)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(unix_now())
+        .bind(actor.map(|actor| actor.id.as_str()))
+        .bind(actor.map(|actor| actor.username.as_str()))
+        .bind(action.as_str())
+        .bind(server_id)
+        .bind(outcome.as_str())
+        .bind(detail)
+        .execute(&self.pool)
+        .await;
+
+        if let Err(err) = result {
+            tracing::error!("Failed to record {} in audit log: {:#}", action.as_str(), err);
+        }
+    }
+
+    /// Records that the servers' passwords were sent to `actor`, at most once per window
+    pub async fn secrets_revealed(&self, actor: &Actor, servers: &[ServerInfo]) {
+        for server in servers.iter().filter(|s| s.status.has_secrets()) {
+            let first = self
+                .revealed
+                .entry((actor.id.clone(), server.id.clone()))
+                .or_insert(())
+                .await
+                .is_fresh();
+            if first {
+                self.record(
+                    Some(actor),
+                    AuditAction::SecretReveal,
+                    Some(&server.id),
+                    AuditOutcome::Ok,
+                    "Server password",
+                )
+                .await;
+            }
+        }
+    }
+
+    pub async fn query(&self, query: &AuditQuery) -> anyhow::Result<AuditPage> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+
+        // One extra row tells us whether there's another page
+        let mut entries = sqlx::query_as::<_, AuditRow>(
+            "SELECT id, occurred_at, actor, username, action, server_id, outcome, detail
+             FROM audit_log
+             WHERE (?1 IS NULL OR actor = ?1 OR username LIKE '%' || ?1 || '%')
+               AND (?2 IS NULL OR server_id = ?2)
+               AND (?3 IS NULL OR action = ?3)
+               AND (?4 IS NULL OR occurred_at >= ?4)
+               AND (?5 IS NULL OR occurred_at <= ?5)
+               AND (?6 IS NULL OR id < ?6)
+             ORDER BY id DESC
+             LIMIT ?7",
+        )
+        .bind(query.actor.as_deref().filter(|s| !s.is_empty()))
+        .bind(query.server.as_deref().filter(|s| !s.is_empty()))
+        .bind(query.action.as_deref().filter(|s| !s.is_empty()))
+        .bind(query.from)
+        .bind(query.to)
+        .bind(query.before)
+        .bind(limit + 1)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .filter_map(to_entry)
+        .collect::<Vec<_>>();
+
+        let next_before = if entries.len() as i64 > limit {
+            entries.truncate(limit as usize);
+            entries.last().map(|entry| entry.id)
+        } else {
+            None
+        };
+
+        Ok(AuditPage {
+            entries,
+            next_before,
+        })
+    }
+}
+
+/// Skips rows with actions this version doesn't know about
+fn to_entry(
+    (id, at, actor, username, action, server, outcome, detail): AuditRow,
+) -> Option<AuditEntry> {
+    Some(AuditEntry {
+        id,
+        at,
+        actor: actor.map(Into::into),
+        username: username.map(Into::into),
+        action: AuditAction::from_name(&action)?,
+        server: server.map(Into::into),
+        outcome: match outcome.as_str() {
+            "ok" => AuditOutcome::Ok,
+            "denied" => AuditOutcome::Denied,
+            _ => AuditOutcome::Error,
+        },
+        detail,
+    })
+}
@@ -0,0 +1,231 @@
+use crate::auth::{LoginProvider, PendingLogin};
+use crate::metrics::METRICS;
+use crate::servers::unix_now;
+use crate::{AppError, AppState, Server, UserData};
+use anyhow::{anyhow, Context};
+use common::discord::{RoleId, UserId};
+use moka::future::{Cache, CacheBuilder};
+use oauth2::basic::{
+    BasicClient, BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
+    BasicTokenResponse,
+};
+use oauth2::{
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, EndpointNotSet, EndpointSet,
+    RedirectUrl, RevocationUrl, Scope, StandardRevocableToken, TokenResponse, TokenUrl,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+pub type OAuthClient = oauth2::Client<
+    BasicErrorResponse,
+    BasicTokenResponse,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointSet,
+    EndpointSet,
+>;
+
+/// Tokens along with when the access token expires, as a unix timestamp
+type RefreshedTokens = (BasicTokenResponse, Option<i64>);
+
+#[derive(Clone)]
+pub struct DiscordProvider {
+    client: OAuthClient,
+    http_client: reqwest::Client,
+    /// Results of recent refreshes by the refresh token that was used. Discord rotates refresh
+    /// tokens, so concurrent requests of the same session have to share a single refresh.
+    refreshed: Cache<String, RefreshedTokens>,
+}
+
+/// Discord didn't accept the access token, the user has to log in again
+#[derive(Debug)]
+pub struct TokenRejected;
+
+impl Display for TokenRejected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Discord rejected the access token")
+    }
+}
+
+impl Error for TokenRejected {}
+
+impl DiscordProvider {
+    pub fn from_env(server: &Server) -> anyhow::Result<Self, AppError> {
+        let client_id = env::var("DISCORD_CLIENT_ID").context("Missing CLIENT_ID!")?;
+        let client_secret = env::var("DISCORD_CLIENT_SECRET").context("Missing CLIENT_SECRET!")?;
+        let redirect_url = server
+            .public_url
+            .join("/auth/discord/authorize")?
+            .to_string();
+
+        let auth_url = env::var("AUTH_URL")
+            .unwrap_or_else(|_| "https://discord.com/api/oauth2/authorize".to_string());
+
+        let token_url = env::var("TOKEN_URL")
+            .unwrap_or_else(|_| "https://discord.com/api/oauth2/token".to_string());
+
+        let revocation_url = env::var("REVOCATION_URL")
+            .unwrap_or_else(|_| "https://discord.com/api/oauth2/token/revoke".to_string());
+
+        let client = BasicClient::new(ClientId::new(client_id))
+            .set_client_secret(ClientSecret::new(client_secret))
+            .set_auth_uri(
+                AuthUrl::new(auth_url).context("failed to create new authorization server URL")?,
+            )
+            .set_token_uri(
+                TokenUrl::new(token_url).context("failed to create new token endpoint URL")?,
+            )
+            .set_revocation_url(
+                RevocationUrl::new(revocation_url)
+                    .context("failed to create new revocation endpoint URL")?,
+            )
+            .set_redirect_uri(
+                RedirectUrl::new(redirect_url).context("failed to create new redirection URL")?,
+            );
+
+        Ok(Self {
+            client,
+            http_client: reqwest::Client::new(),
+            refreshed: CacheBuilder::new(100)
+                .time_to_live(Duration::from_secs(5 * 60))
+                .build(),
+        })
+    }
+
+    /// Gets new tokens using the refresh token in `tokens`
+    pub async fn refresh(&self, tokens: &BasicTokenResponse) -> anyhow::Result<RefreshedTokens> {
+        let refresh_token = tokens.refresh_token().context("No refresh token")?;
+
+        self.refreshed
+            .try_get_with(refresh_token.secret().clone(), async {
+                METRICS.discord_api_call("oauth_refresh");
+                let mut tokens = self
+                    .client
+                    .exchange_refresh_token(refresh_token)
+                    .request_async(&self.http_client)
+                    .await
+                    .context("failed to refresh token")?;
+                if tokens.refresh_token().is_none() {
+                    tokens.set_refresh_token(Some(refresh_token.clone()));
+                }
+                let expires_at = expires_at(&tokens);
+                anyhow::Ok((tokens, expires_at))
+            })
+            .await
+            .map_err(|err| anyhow!("{:#}", err))
+    }
+
+    /// Revokes the tokens so they can't be used anymore, even if they leaked
+    pub async fn revoke(&self, tokens: &BasicTokenResponse) -> anyhow::Result<()> {
+        // Revoking the refresh token revokes the access token with it
+        let token = match tokens.refresh_token() {
+            Some(refresh_token) => StandardRevocableToken::from(refresh_token),
+            None => StandardRevocableToken::from(tokens.access_token()),
+        };
+
+        METRICS.discord_api_call("oauth_revoke");
+        self.client
+            .revoke_token(token)?
+            .request_async(&self.http_client)
+            .await
+            .context("failed to revoke token")?;
+
+        Ok(())
+    }
+}
+
+fn expires_at(tokens: &BasicTokenResponse) -> Option<i64> {
+    tokens
+        .expires_in()
+        .map(|expires_in| unix_now() + expires_in.as_secs() as i64)
+}
+
+impl LoginProvider for DiscordProvider {
+    const ID: &'static str = "discord";
+
+    fn from_state(state: &AppState) -> Option<&Self> {
+        Some(&state.discord)
+    }
+
+    fn display_name(&self) -> &str {
+        "Discord"
+    }
+
+    fn begin_login(&self) -> (Url, PendingLogin) {
+        let (auth_url, csrf_token) = self
+            .client
+            .authorize_url(CsrfToken::new_random)
+            .add_scope(Scope::new("identify".to_string()))
+            .add_scope(Scope::new("guilds.members.read".to_string()))
+            .url();
+
+        let pending = PendingLogin {
+            csrf_token,
+            pkce_verifier: None,
+            nonce: None,
+        };
+        (auth_url, pending)
+    }
+
+    async fn complete_login(
+        &self,
+        code: AuthorizationCode,
+        _pending: PendingLogin,
+    ) -> anyhow::Result<UserData> {
+        // Get an auth token
+        METRICS.discord_api_call("oauth_token");
+        let tokens = self
+            .client
+            .exchange_code(code)
+            .request_async(&self.http_client)
+            .await
+            .context("failed in sending request request to authorization server")?;
+
+        // Fetch user data from discord
+        METRICS.discord_api_call("current_user");
+        let user = self
+            .http_client
+            // https://discord.com/developers/docs/resources/user#get-current-user
+            .get("https://discordapp.com/api/users/@me")
+            .bearer_auth(tokens.access_token().secret())
+            .send()
+            .await
+            .context("failed in sending request to target Url")?
+            .json::<DiscordUserData>()
+            .await
+            .context("failed to deserialize response as JSON")?;
+
+        Ok(UserData::Discord {
+            user,
+            expires_at: expires_at(&tokens),
+            tokens,
+        })
+    }
+}
+
+// The user data we'll get back from Discord.
+// https://discord.com/developers/docs/resources/user#user-object-user-structure
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiscordUserData {
+    pub id: UserId,
+    pub avatar: Option<String>,
+    pub username: String,
+    pub discriminator: String,
+}
+
+/// The guild member data we'll get back from Discord.
+/// https://discord.com/developers/docs/resources/guild#guild-member-object
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuildMember {
+    pub user: DiscordUserData,
+    pub nick: Option<String>,
+    pub roles: Vec<RoleId>,
+}
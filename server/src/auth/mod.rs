@@ -1,63 +1,49 @@
-use crate::{AppError, AppState, Server};
-use anyhow::Context;
-use axum::extract::FromRef;
+mod discord;
+mod oidc;
+mod tokens;
+
+use crate::{AppState, UserData};
 use axum::response::{IntoResponse, Redirect, Response};
-use common::discord::{RoleId, UserId};
-use oauth2::basic::{
-    BasicClient, BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
-    BasicTokenResponse,
-};
-use oauth2::{
-    AuthUrl, ClientId, ClientSecret, EndpointNotSet, EndpointSet, RedirectUrl,
-    StandardRevocableToken, TokenUrl,
-};
+use oauth2::{AuthorizationCode, CsrfToken, PkceCodeVerifier};
+use openidconnect::Nonce;
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use std::env;
 
-pub static CSRF_TOKEN: &str = "csrf_token";
+pub use discord::{DiscordProvider, DiscordUserData, GuildMember, TokenRejected};
+pub use oidc::{OidcProvider, OidcUserData};
+pub use tokens::{ApiTokenUser, ApiTokens};
 
-pub type OAuthClient = oauth2::Client<
-    BasicErrorResponse,
-    BasicTokenResponse,
-    BasicTokenIntrospectionResponse,
-    StandardRevocableToken,
-    BasicRevocationErrorResponse,
-    EndpointSet,
-    EndpointNotSet,
-    EndpointNotSet,
-    EndpointNotSet,
-    EndpointSet,
->;
+pub static PENDING_LOGIN: &str = "pending_login";
 
-impl FromRef<AppState> for OAuthClient {
-    fn from_ref(state: &AppState) -> Self {
-        state.oauth_client.clone()
-    }
-}
+/// A way for users to log in. Every provider is served under `/auth/{ID}`, with the callback at
+/// `/auth/{ID}/authorize`.
+pub trait LoginProvider: Send + Sync + 'static {
+    const ID: &'static str;
 
-pub fn oauth_client(server: &Server) -> anyhow::Result<OAuthClient, AppError> {
-    let client_id = env::var("DISCORD_CLIENT_ID").context("Missing CLIENT_ID!")?;
-    let client_secret = env::var("DISCORD_CLIENT_SECRET").context("Missing CLIENT_SECRET!")?;
-    let redirect_url = server
-        .public_url
-        .join("/auth/discord/authorize")?
-        .to_string();
+    /// Returns the provider if it's configured
+    fn from_state(state: &AppState) -> Option<&Self>;
 
-    let auth_url = env::var("AUTH_URL")
-        .unwrap_or_else(|_| "https://discord.com/api/oauth2/authorize".to_string());
+    /// Name shown on the login button
+    fn display_name(&self) -> &str;
 
-    let token_url = env::var("TOKEN_URL")
-        .unwrap_or_else(|_| "https://discord.com/api/oauth2/token".to_string());
+    /// Builds the URL to send the user to, along with what we need to remember until they
+    /// come back
+    fn begin_login(&self) -> (Url, PendingLogin);
 
-    Ok(BasicClient::new(ClientId::new(client_id))
-        .set_client_secret(ClientSecret::new(client_secret))
-        .set_auth_uri(
-            AuthUrl::new(auth_url).context("failed to create new authorization server URL")?,
-        )
-        .set_token_uri(TokenUrl::new(token_url).context("failed to create new token endpoint URL")?)
-        .set_redirect_uri(
-            RedirectUrl::new(redirect_url).context("failed to create new redirection URL")?,
-        ))
+    /// Exchanges the authorization code for the user's data
+    fn complete_login(
+        &self,
+        code: AuthorizationCode,
+        pending: PendingLogin,
+    ) -> impl Future<Output = anyhow::Result<UserData>> + Send;
+}
+
+/// State of a login that was started but hasn't come back from the provider yet
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingLogin {
+    pub csrf_token: CsrfToken,
+    pub pkce_verifier: Option<PkceCodeVerifier>,
+    pub nonce: Option<Nonce>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -74,22 +60,3 @@ impl IntoResponse for AuthRedirect {
         Redirect::temporary("/auth/discord").into_response()
     }
 }
-
-// The user data we'll get back from Discord.
-// https://discord.com/developers/docs/resources/user#user-object-user-structure
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DiscordUserData {
-    pub id: UserId,
-    pub avatar: Option<String>,
-    pub username: String,
-    pub discriminator: String,
-}
-
-/// The guild member data we'll get back from Discord.
-/// https://discord.com/developers/docs/resources/guild#guild-member-object
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GuildMember {
-    pub user: DiscordUserData,
-    pub nick: Option<String>,
-    pub roles: Vec<RoleId>,
-}
@@ -0,0 +1,202 @@
+use crate::servers::{unix_now, Principal};
+use crate::AppState;
+use anyhow::Context;
+use axum::extract::FromRef;
+use common::status::Capability;
+use common::user::{ApiToken, CreatedApiToken, NewApiToken};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+
+/// Prefix of every token secret, so they're easy to recognize in scripts and secret scanners
+const TOKEN_PREFIX: &str = "hsm_";
+/// `last_used_at` is only updated if it's older than this, so polling scripts don't cause a
+/// write on every request
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+/// How long a token works. Its roles are only refreshed when the owner uses the web UI, so
+/// this bounds how long a stale snapshot can grant access.
+const TOKEN_LIFETIME_SECS: i64 = 90 * 24 * 60 * 60;
+
+/// A request authenticated with an API token instead of a session
+#[derive(Debug, Clone)]
+pub struct ApiTokenUser {
+    pub id: i64,
+    pub owner: String,
+    pub owner_name: String,
+    pub name: String,
+    /// The owner's principal, limited to the token's capabilities
+    pub principal: Principal,
+}
+
+/// Personal API tokens, stored hashed in SQLite
+#[derive(Clone)]
+pub struct ApiTokens {
+    pool: SqlitePool,
+}
+
+impl FromRef<AppState> for ApiTokens {
+    fn from_ref(input: &AppState) -> Self {
+        input.api_tokens.clone()
+    }
+}
+
+type TokenRow = (i64, String, String, i64, Option<i64>, i64);
+
+impl ApiTokens {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self, owner: &str) -> anyhow::Result<Vec<ApiToken>> {
+        sqlx::query_as::<_, TokenRow>(
+            "SELECT id, name, capabilities, created_at, last_used_at, expires_at FROM api_tokens
+             WHERE owner = ? AND expires_at > ?
+             ORDER BY created_at",
+        )
+        .bind(owner)
+        .bind(unix_now())
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(to_api_token)
+        .collect()
+    }
+
+    pub async fn create(
+        &self,
+        owner: &str,
+        owner_name: &str,
+        principal: &Principal,
+        request: NewApiToken,
+    ) -> anyhow::Result<CreatedApiToken> {
+        let secret = format!(
+            "{TOKEN_PREFIX}{}",
+            hex::encode(rand::thread_rng().r#gen::<[u8; 32]>())
+        );
+        let created_at = unix_now();
+        let expires_at = created_at + TOKEN_LIFETIME_SECS;
+
+        let id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO api_tokens
+             (owner, owner_name, name, token_hash, capabilities, principal, created_at, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+             RETURNING id",
+        )
+        .bind(owner)
+        .bind(owner_name)
+        .bind(request.name.as_str())
+        .bind(hash(&secret))
+        .bind(serde_json::to_string(&request.capabilities)?)
+        .bind(serde_json::to_string(principal)?)
+        .bind(created_at)
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(CreatedApiToken {
+            token: ApiToken {
+                id,
+                name: request.name,
+                capabilities: request.capabilities,
+                created_at,
+                last_used_at: None,
+                expires_at,
+            },
+            secret,
+        })
+    }
+
+    /// Returns whether the token existed
+    pub async fn revoke(&self, owner: &str, id: i64) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM api_tokens WHERE owner = ? AND id = ?")
+            .bind(owner)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes every token of the owner, returns how many there were
+    pub async fn revoke_all(&self, owner: &str) -> anyhow::Result<u64> {
+        let result = sqlx::query("DELETE FROM api_tokens WHERE owner = ?")
+            .bind(owner)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Looks up the token with the given secret, unless it expired
+    pub async fn authenticate(&self, secret: &str) -> anyhow::Result<Option<ApiTokenUser>> {
+        let now = unix_now();
+        let row = sqlx::query_as::<_, (i64, String, String, String, String, String)>(
+            "SELECT id, owner, owner_name, name, capabilities, principal FROM api_tokens
+             WHERE token_hash = ? AND expires_at > ?",
+        )
+        .bind(hash(secret))
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some((id, owner, owner_name, name, capabilities, principal)) = row else {
+            return Ok(None);
+        };
+
+        sqlx::query(
+            "UPDATE api_tokens SET last_used_at = ?
+             WHERE id = ? AND (last_used_at IS NULL OR last_used_at < ?)",
+        )
+        .bind(now)
+        .bind(id)
+        .bind(now - LAST_USED_RESOLUTION_SECS)
+        .execute(&self.pool)
+        .await?;
+
+        let mut principal = serde_json::from_str::<Principal>(&principal)
+            .context("failed to deserialize token principal")?;
+        principal.scope = Some(
+            serde_json::from_str::<HashSet<Capability>>(&capabilities)
+                .context("failed to deserialize token capabilities")?,
+        );
+
+        Ok(Some(ApiTokenUser {
+            id,
+            owner,
+            owner_name,
+            name,
+            principal,
+        }))
+    }
+
+    /// Keeps the owner's tokens in line with their current roles and groups, so losing a role
+    /// also takes it away from their tokens
+    pub async fn update_principal(&self, owner: &str, principal: &Principal) -> anyhow::Result<()> {
+        sqlx::query("UPDATE api_tokens SET principal = ? WHERE owner = ?")
+            .bind(serde_json::to_string(principal)?)
+            .bind(owner)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn to_api_token(
+    (id, name, capabilities, created_at, last_used_at, expires_at): TokenRow,
+) -> anyhow::Result<ApiToken> {
+    Ok(ApiToken {
+        id,
+        name: name.into(),
+        capabilities: serde_json::from_str(&capabilities)
+            .context("failed to deserialize token capabilities")?,
+        created_at,
+        last_used_at,
+        expires_at,
+    })
+}
+
+/// Secrets are random enough that a plain SHA-256 is fine, no need for a password hash
+fn hash(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
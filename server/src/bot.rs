@@ -0,0 +1,248 @@
+use crate::audit::Actor;
+use crate::control::{Lifecycle, LifecycleAction};
+use crate::servers::{Principal, ServerConfig, ServerManager};
+use common::audit::{AuditAction, AuditOutcome};
+use common::discord::{RoleId, UserId};
+use common::status::{Capability, HealthStatus, ServerInfo, ServerStatus};
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    EditInteractionResponse, EventHandler, GatewayIntents, GuildId, Interaction, Member, Ready,
+};
+use serenity::async_trait;
+use serenity::http::StatusCode;
+use std::collections::HashSet;
+
+/// Connects to the Discord gateway and answers slash commands until the connection fails
+/// for good. Members get the same view of the servers as in the web UI, based on their roles.
+pub(crate) async fn run_bot(token: String, server_manager: ServerManager) -> anyhow::Result<()> {
+    let mut client = serenity::Client::builder(token, GatewayIntents::empty())
+        .event_handler(Handler { server_manager })
+        .await?;
+
+    client.start().await?;
+    Ok(())
+}
+
+struct Handler {
+    server_manager: ServerManager,
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        tracing::info!("Discord bot connected as {}", ready.user.name);
+
+        let server_option = || {
+            CreateCommandOption::new(CommandOptionType::String, "name", "Server name or ID")
+                .required(true)
+        };
+        let commands = vec![
+            CreateCommand::new("servers").description("List the game servers you can see"),
+            CreateCommand::new("status")
+                .description("Show the status of a game server")
+                .add_option(server_option()),
+            CreateCommand::new("start")
+                .description("Start a game server")
+                .add_option(server_option()),
+        ];
+
+        // Guild commands update immediately, unlike global ones
+        for guild in self.server_manager.guilds().await {
+            if let Err(e) = GuildId::new(guild.get())
+                .set_commands(&ctx.http, commands.clone())
+                .await
+            {
+                tracing::error!("Failed to register slash commands in guild {}: {}", guild.get(), e);
+            }
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::Command(command) = interaction else {
+            return;
+        };
+
+        // Starting a server can take longer than the 3 seconds Discord waits for a response
+        if let Err(e) = command.defer_ephemeral(&ctx.http).await {
+            tracing::warn!("Failed to acknowledge /{}: {}", command.data.name, e);
+            return;
+        }
+
+        let content = self.handle(&ctx, &command).await;
+        let response = EditInteractionResponse::new().content(content);
+        if let Err(e) = command.edit_response(&ctx.http, response).await {
+            tracing::warn!("Failed to respond to /{}: {}", command.data.name, e);
+        }
+    }
+}
+
+impl Handler {
+    async fn handle(&self, ctx: &Context, command: &CommandInteraction) -> String {
+        let Some(member) = &command.member else {
+            return "Use this command in the server, not in DMs".to_owned();
+        };
+        let roles = self.member_roles(ctx, command.guild_id, member).await;
+        let principal = Principal::discord(UserId::from(member.user.id.get()), roles);
+        let name = command
+            .data
+            .options
+            .iter()
+            .find(|o| o.name == "name")
+            .and_then(|o| o.value.as_str());
+
+        match (command.data.name.as_str(), name) {
+            ("servers", _) => self.list_servers(&principal).await,
+            ("status", Some(name)) => self.server_status(name, &principal).await,
+            ("start", Some(name)) => {
+                let actor = Actor {
+                    id: format!("discord:{}", member.user.id.get()),
+                    username: member.user.name.clone(),
+                };
+                self.start_server(name, &principal, &actor).await
+            }
+            _ => "Unknown command".to_owned(),
+        }
+    }
+
+    /// Roles of the member across every configured guild, the same as the web UI gathers them.
+    /// Guilds the bot can't look the member up in are skipped.
+    async fn member_roles(
+        &self,
+        ctx: &Context,
+        invoked_in: Option<GuildId>,
+        member: &Member,
+    ) -> HashSet<RoleId> {
+        let mut roles = HashSet::new();
+        for guild in self.server_manager.guilds().await {
+            let guild = GuildId::new(guild.get());
+            let guild_roles = if Some(guild) == invoked_in {
+                member.roles.clone()
+            } else {
+                match guild.member(&ctx.http, member.user.id).await {
+                    Ok(other) => other.roles,
+                    // Not a member there
+                    Err(serenity::Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => {
+                        continue;
+                    }
+                    Err(e) => {
+                        let user = &member.user.name;
+                        tracing::warn!("Failed to fetch roles of {} in guild {}: {}", user, guild, e);
+                        continue;
+                    }
+                }
+            };
+            // Same as the web UI, the guild itself stands for its @everyone role
+            roles.insert(RoleId::from(guild.get()));
+            roles.extend(guild_roles.iter().map(|role| RoleId::from(role.get())));
+        }
+        roles
+    }
+
+    async fn list_servers(&self, principal: &Principal) -> String {
+        let servers = self.server_manager.get_servers(principal).await;
+        if servers.is_empty() {
+            return "You can't see any servers".to_owned();
+        }
+
+        servers
+            .iter()
+            .map(|server| {
+                format!(
+                    "**{}** (`{}`): {}",
+                    server.status.name(),
+                    server.id,
+                    server.status.health()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    async fn server_status(&self, name: &str, principal: &Principal) -> String {
+        let Some(config) = self.server_manager.find_server_config(name, principal).await else {
+            return format!("No server called `{name}`");
+        };
+        let servers = self.server_manager.get_servers(principal).await;
+        match servers.iter().find(|s| s.id == *config.id()) {
+            Some(server) => describe(server),
+            None => format!("No status for `{name}` yet"),
+        }
+    }
+
+    async fn start_server(&self, name: &str, principal: &Principal, actor: &Actor) -> String {
+        let Some(config) = self.server_manager.find_server_config(name, principal).await else {
+            return format!("No server called `{name}`");
+        };
+        let Some(control) = &config.control else {
+            return format!("**{}** can't be started from here", config.name);
+        };
+        let capabilities = config.capabilities(principal);
+        let action = if capabilities.contains(&Capability::Control) {
+            LifecycleAction::Start
+        } else if capabilities.contains(&Capability::Wake) {
+            LifecycleAction::Wake
+        } else {
+            self.audit(actor, &config, AuditOutcome::Denied, "start from Discord").await;
+            return format!("You aren't allowed to start **{}**", config.name);
+        };
+
+        tracing::info!("{} requested {} of {} from Discord", actor.username, action, config.name);
+        match control.backend.run(action).await {
+            Ok(()) => {
+                let detail = format!("{action} from Discord");
+                self.audit(actor, &config, AuditOutcome::Ok, &detail).await;
+                format!("Starting **{}**", config.name)
+            }
+            Err(e) => {
+                tracing::error!("Failed to {} {}: {:#}", action, config.name, e);
+                let detail = format!("{action} from Discord: {e:#}");
+                self.audit(actor, &config, AuditOutcome::Error, &detail).await;
+                format!("Failed to start **{}**", config.name)
+            }
+        }
+    }
+
+    async fn audit(&self, actor: &Actor, config: &ServerConfig, outcome: AuditOutcome, detail: &str) {
+        self.server_manager
+            .audit()
+            .record(Some(actor), AuditAction::Lifecycle, Some(config.id()), outcome, detail)
+            .await;
+    }
+}
+
+/// Summarises a status for chat. Passwords are left out since the channel may be public.
+fn describe(server: &ServerInfo) -> String {
+    let status = &server.status;
+    let mut lines = vec![format!("**{}**: {}", status.name(), status.health())];
+    if !status.url().is_empty() {
+        lines.push(format!("Address: `{}`", status.url()));
+    }
+    if let Some(version) = status.version() {
+        lines.push(format!("Version: {version}"));
+    }
+    if status.health() == HealthStatus::Running {
+        match (status.players_online(), status.player_names()) {
+            (Some(0), _) => lines.push("Nobody is online".to_owned()),
+            (_, Some(names)) if !names.is_empty() => {
+                lines.push(format!("Online: {}", names.join(", ")));
+            }
+            (Some(count), _) => lines.push(format!("{count} players online")),
+            (None, _) => {}
+        }
+    }
+    if let ServerStatus::Generic(generic) = status
+        && let Some(latency) = generic.latency_ms
+    {
+        lines.push(format!("Latency: {latency} ms"));
+    }
+    if let ServerStatus::Factorio(factorio) = status
+        && let Some(research) = &factorio.research
+    {
+        lines.push(format!(
+            "Researching {} ({:.0}%)",
+            research.name,
+            research.progress * 100.0
+        ));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,96 @@
+use crate::control::docker::DockerConfig;
+use crate::control::systemd::SystemdConfig;
+use common::discord::RoleId;
+use common::status::HealthStatus;
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+
+mod docker;
+mod systemd;
+
+/// How a game server's process is managed, and who may manage it
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct ControlConfig {
+    #[serde(flatten)]
+    pub backend: BackendConfig,
+    /// Deprecated, grant `control` in the server's `permissions` instead
+    #[serde(default)]
+    pub role: Option<RoleId>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+#[serde(tag = "type")]
+pub enum BackendConfig {
+    Docker(DockerConfig),
+    Systemd(SystemdConfig),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LifecycleAction {
+    Start,
+    Stop,
+    Restart,
+    /// Start a server that was stopped for being idle
+    Wake,
+}
+
+impl Display for LifecycleAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LifecycleAction::Start => write!(f, "start"),
+            LifecycleAction::Stop => write!(f, "stop"),
+            LifecycleAction::Restart => write!(f, "restart"),
+            LifecycleAction::Wake => write!(f, "wake"),
+        }
+    }
+}
+
+pub trait Lifecycle {
+    async fn start(&self) -> anyhow::Result<()>;
+
+    async fn stop(&self) -> anyhow::Result<()>;
+
+    async fn restart(&self) -> anyhow::Result<()>;
+
+    /// Current state of the server process, independent of whether the game answers queries
+    async fn state(&self) -> anyhow::Result<HealthStatus>;
+
+    async fn run(&self, action: LifecycleAction) -> anyhow::Result<()> {
+        match action {
+            LifecycleAction::Start | LifecycleAction::Wake => self.start().await,
+            LifecycleAction::Stop => self.stop().await,
+            LifecycleAction::Restart => self.restart().await,
+        }
+    }
+}
+
+impl Lifecycle for BackendConfig {
+    async fn start(&self) -> anyhow::Result<()> {
+        match self {
+            BackendConfig::Docker(config) => config.start().await,
+            BackendConfig::Systemd(config) => config.start().await,
+        }
+    }
+
+    async fn stop(&self) -> anyhow::Result<()> {
+        match self {
+            BackendConfig::Docker(config) => config.stop().await,
+            BackendConfig::Systemd(config) => config.stop().await,
+        }
+    }
+
+    async fn restart(&self) -> anyhow::Result<()> {
+        match self {
+            BackendConfig::Docker(config) => config.restart().await,
+            BackendConfig::Systemd(config) => config.restart().await,
+        }
+    }
+
+    async fn state(&self) -> anyhow::Result<HealthStatus> {
+        match self {
+            BackendConfig::Docker(config) => config.state().await,
+            BackendConfig::Systemd(config) => config.state().await,
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use crate::control::Lifecycle;
+use anyhow::{Context, bail};
+use common::status::HealthStatus;
+use http::{Method, Request, StatusCode};
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use smol_str::SmolStr;
+use std::path::PathBuf;
+use tokio::net::UnixStream;
+
+/// Seconds the engine waits for a container to exit before killing it
+const STOP_TIMEOUT_SECS: u32 = 30;
+
+/// Docker or Podman container managed through the Engine API
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct DockerConfig {
+    /// Container name or id
+    pub container: SmolStr,
+    /// Engine API socket. Podman serves a compatible API at `/run/podman/podman.sock`.
+    #[serde(default = "default_socket")]
+    pub socket: PathBuf,
+}
+
+fn default_socket() -> PathBuf {
+    PathBuf::from("/var/run/docker.sock")
+}
+
+// https://docs.docker.com/reference/api/engine/latest/#tag/Container/operation/ContainerInspect
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerInspect {
+    state: ContainerState,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerState {
+    status: SmolStr,
+    health: Option<ContainerHealth>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerHealth {
+    status: SmolStr,
+}
+
+#[derive(Debug, Deserialize)]
+struct EngineError {
+    message: String,
+}
+
+impl DockerConfig {
+    /// Sends a bodiless request over a fresh connection, returning the status and response body
+    async fn request(&self, method: Method, path: &str) -> anyhow::Result<(StatusCode, Bytes)> {
+        let stream = UnixStream::connect(&self.socket)
+            .await
+            .with_context(|| format!("failed to connect to {}", self.socket.display()))?;
+        let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+        tokio::spawn(async move {
+            if let Err(e) = conn.await {
+                tracing::debug!("Docker connection closed with error: {e}");
+            }
+        });
+
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(http::header::HOST, "docker")
+            .body(Empty::<Bytes>::new())?;
+        let response = sender.send_request(request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        Ok((status, body))
+    }
+
+    async fn container_action(&self, action: &str) -> anyhow::Result<()> {
+        let path = match action {
+            "start" => format!("/containers/{}/start", self.container),
+            _ => format!("/containers/{}/{action}?t={STOP_TIMEOUT_SECS}", self.container),
+        };
+        let (status, body) = self.request(Method::POST, &path).await?;
+        // 304 means the container was already in the requested state
+        if status.is_success() || status == StatusCode::NOT_MODIFIED {
+            return Ok(());
+        }
+        bail!("failed to {action} container {}: {}", self.container, engine_error(status, &body))
+    }
+}
+
+fn engine_error(status: StatusCode, body: &[u8]) -> String {
+    match serde_json::from_slice::<EngineError>(body) {
+        Ok(error) => format!("{status}: {}", error.message),
+        Err(_) => status.to_string(),
+    }
+}
+
+impl Lifecycle for DockerConfig {
+    async fn start(&self) -> anyhow::Result<()> {
+        self.container_action("start").await
+    }
+
+    async fn stop(&self) -> anyhow::Result<()> {
+        self.container_action("stop").await
+    }
+
+    async fn restart(&self) -> anyhow::Result<()> {
+        self.container_action("restart").await
+    }
+
+    async fn state(&self) -> anyhow::Result<HealthStatus> {
+        let path = format!("/containers/{}/json", self.container);
+        let (status, body) = self.request(Method::GET, &path).await?;
+        if !status.is_success() {
+            bail!("failed to inspect container {}: {}", self.container, engine_error(status, &body));
+        }
+        container_health(&body)
+    }
+}
+
+/// Maps the container inspect response to the server's health. A failing health check counts
+/// as offline, since the game isn't reachable.
+fn container_health(inspect: &[u8]) -> anyhow::Result<HealthStatus> {
+    let inspect: ContainerInspect = serde_json::from_slice(inspect)?;
+    let state = match (inspect.state.status.as_str(), inspect.state.health) {
+        ("running", Some(health)) => match health.status.as_str() {
+            "starting" => HealthStatus::Starting,
+            "unhealthy" => HealthStatus::Offline,
+            _ => HealthStatus::Running,
+        },
+        ("running", None) => HealthStatus::Running,
+        ("restarting", _) => HealthStatus::Starting,
+        ("created" | "paused" | "exited" | "dead" | "removing", _) => HealthStatus::Offline,
+        _ => HealthStatus::Unknown,
+    };
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::Uri;
+    use axum::response::{IntoResponse, Response};
+    use axum::{Json, Router};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tokio::net::UnixListener;
+
+    #[test]
+    fn maps_container_states() {
+        let cases = [
+            (r#"{"Status": "running"}"#, HealthStatus::Running),
+            (r#"{"Status": "running", "Health": {"Status": "healthy"}}"#, HealthStatus::Running),
+            (r#"{"Status": "running", "Health": {"Status": "none"}}"#, HealthStatus::Running),
+            (r#"{"Status": "running", "Health": {"Status": "starting"}}"#, HealthStatus::Starting),
+            (r#"{"Status": "running", "Health": {"Status": "unhealthy"}}"#, HealthStatus::Offline),
+            (r#"{"Status": "restarting"}"#, HealthStatus::Starting),
+            (r#"{"Status": "created"}"#, HealthStatus::Offline),
+            (r#"{"Status": "paused"}"#, HealthStatus::Offline),
+            (r#"{"Status": "exited", "Health": {"Status": "unhealthy"}}"#, HealthStatus::Offline),
+            (r#"{"Status": "dead"}"#, HealthStatus::Offline),
+            (r#"{"Status": "removing"}"#, HealthStatus::Offline),
+            (r#"{"Status": "something new"}"#, HealthStatus::Unknown),
+        ];
+        for (state, expected) in cases {
+            let inspect = format!(r#"{{"Id": "abc", "Name": "/factorio", "State": {state}}}"#);
+            assert_eq!(container_health(inspect.as_bytes()).unwrap(), expected, "{state}");
+        }
+    }
+
+    #[test]
+    fn parses_real_inspect_response() {
+        // Trimmed `docker container inspect` output
+        let inspect = br#"{
+            "Id": "4fa6e0f0c678",
+            "Created": "2025-01-12T18:02:41.227Z",
+            "State": {
+                "Status": "running",
+                "Running": true,
+                "Paused": false,
+                "Restarting": false,
+                "OOMKilled": false,
+                "Dead": false,
+                "Pid": 4182,
+                "ExitCode": 0,
+                "Error": "",
+                "StartedAt": "2025-01-12T18:02:42.003Z",
+                "FinishedAt": "0001-01-01T00:00:00Z",
+                "Health": {"Status": "healthy", "FailingStreak": 0, "Log": []}
+            },
+            "Name": "/factorio"
+        }"#;
+        assert_eq!(container_health(inspect).unwrap(), HealthStatus::Running);
+    }
+
+    #[test]
+    fn rejects_malformed_responses() {
+        let cases: &[&[u8]] = &[b"", b"not json", br#"{"Id": "abc"}"#, br#"{"State": {}}"#];
+        for &case in cases {
+            assert!(container_health(case).is_err(), "accepted {}", String::from_utf8_lossy(case));
+        }
+    }
+
+    /// Requests a [MockEngine] received, as `METHOD /path?query`
+    type Requests = Arc<Mutex<Vec<String>>>;
+
+    /// Engine API stand-in on a unix socket. Knows the `factorio` container, which is already
+    /// stopped, and nothing else.
+    struct MockEngine {
+        config: DockerConfig,
+        requests: Requests,
+    }
+
+    impl MockEngine {
+        async fn start(container: &str) -> Self {
+            async fn handle(
+                State(requests): State<Requests>,
+                method: Method,
+                uri: Uri,
+            ) -> Response {
+                let path = uri.path_and_query().map_or("", |path| path.as_str());
+                requests.lock().unwrap().push(format!("{method} {path}"));
+                let not_found = || {
+                    let message = json!({ "message": "No such container: missing" });
+                    (StatusCode::NOT_FOUND, Json(message)).into_response()
+                };
+                match path {
+                    "/containers/factorio/start" | "/containers/factorio/restart?t=30" => {
+                        StatusCode::NO_CONTENT.into_response()
+                    }
+                    "/containers/factorio/stop?t=30" => StatusCode::NOT_MODIFIED.into_response(),
+                    "/containers/factorio/json" => {
+                        let inspect = json!({ "Id": "4fa6e0f0", "State": { "Status": "exited" } });
+                        Json(inspect).into_response()
+                    }
+                    "/containers/broken/start" => {
+                        (StatusCode::INTERNAL_SERVER_ERROR, "not json").into_response()
+                    }
+                    _ => not_found(),
+                }
+            }
+
+            static COUNTER: AtomicU32 = AtomicU32::new(0);
+            let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let name = format!("docker-mock-{}-{id}.sock", std::process::id());
+            let socket = std::env::temp_dir().join(name);
+            let _ = std::fs::remove_file(&socket);
+            let listener = UnixListener::bind(&socket).unwrap();
+
+            let requests = Requests::default();
+            let app = Router::new().fallback(handle).with_state(requests.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+
+            Self {
+                config: DockerConfig {
+                    container: container.into(),
+                    socket,
+                },
+                requests,
+            }
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl Drop for MockEngine {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.config.socket);
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_lifecycle_actions() {
+        let engine = MockEngine::start("factorio").await;
+        engine.config.start().await.unwrap();
+        // 304 means it was already stopped
+        engine.config.stop().await.unwrap();
+        engine.config.restart().await.unwrap();
+        assert_eq!(
+            engine.requests(),
+            [
+                "POST /containers/factorio/start",
+                "POST /containers/factorio/stop?t=30",
+                "POST /containers/factorio/restart?t=30",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn inspects_the_container() {
+        let engine = MockEngine::start("factorio").await;
+        assert_eq!(engine.config.state().await.unwrap(), HealthStatus::Offline);
+        assert_eq!(engine.requests(), ["GET /containers/factorio/json"]);
+    }
+
+    #[tokio::test]
+    async fn reports_engine_errors() {
+        let engine = MockEngine::start("missing").await;
+        let error = engine.config.start().await.unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "failed to start container missing: 404 Not Found: No such container: missing"
+        );
+        let error = engine.config.state().await.unwrap_err().to_string();
+        assert!(error.contains("No such container: missing"), "{error}");
+
+        // Bodies that aren't Engine API errors fall back to the status
+        let engine = MockEngine::start("broken").await;
+        let error = engine.config.start().await.unwrap_err().to_string();
+        assert_eq!(error, "failed to start container broken: 500 Internal Server Error");
+    }
+
+    #[tokio::test]
+    async fn fails_without_a_socket() {
+        let config = DockerConfig {
+            container: "factorio".into(),
+            socket: std::env::temp_dir().join("no-such-docker.sock"),
+        };
+        let error = format!("{:#}", config.start().await.unwrap_err());
+        assert!(error.starts_with("failed to connect to"), "{error}");
+    }
+}
@@ -0,0 +1,119 @@
+use crate::control::Lifecycle;
+use anyhow::Context;
+use common::status::HealthStatus;
+use serde::Deserialize;
+use smol_str::SmolStr;
+use tokio::sync::OnceCell;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::Connection;
+
+static SYSTEM_BUS: OnceCell<Connection> = OnceCell::const_new();
+
+/// Mode passed to systemd for every job. Replaces any queued job for the unit.
+const JOB_MODE: &str = "replace";
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct SystemdConfig {
+    /// Full unit name, e.g. `factorio.service`
+    pub unit: SmolStr,
+}
+
+// https://www.freedesktop.org/software/systemd/man/latest/org.freedesktop.systemd1.html
+#[zbus::proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1"
+)]
+trait Manager {
+    fn start_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+
+    fn stop_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+
+    fn restart_unit(&self, name: &str, mode: &str) -> zbus::Result<OwnedObjectPath>;
+
+    /// Unlike `GetUnit` this also works for units that aren't currently loaded
+    fn load_unit(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.systemd1.Unit",
+    default_service = "org.freedesktop.systemd1"
+)]
+trait Unit {
+    #[zbus(property)]
+    fn active_state(&self) -> zbus::Result<String>;
+}
+
+impl SystemdConfig {
+    async fn manager(&self) -> anyhow::Result<ManagerProxy<'static>> {
+        let conn = SYSTEM_BUS
+            .get_or_try_init(Connection::system)
+            .await
+            .context("failed to connect to the system bus")?;
+
+        Ok(ManagerProxy::new(conn).await?)
+    }
+}
+
+impl Lifecycle for SystemdConfig {
+    async fn start(&self) -> anyhow::Result<()> {
+        self.manager().await?.start_unit(&self.unit, JOB_MODE).await?;
+        Ok(())
+    }
+
+    async fn stop(&self) -> anyhow::Result<()> {
+        self.manager().await?.stop_unit(&self.unit, JOB_MODE).await?;
+        Ok(())
+    }
+
+    async fn restart(&self) -> anyhow::Result<()> {
+        self.manager().await?.restart_unit(&self.unit, JOB_MODE).await?;
+        Ok(())
+    }
+
+    async fn state(&self) -> anyhow::Result<HealthStatus> {
+        let manager = self.manager().await?;
+        let path = manager.load_unit(&self.unit).await?;
+        let unit = UnitProxy::builder(manager.inner().connection())
+            .path(path)?
+            .build()
+            .await?;
+
+        Ok(unit_health(&unit.active_state().await?))
+    }
+}
+
+/// Maps a unit's `ActiveState` to the server's health
+fn unit_health(active_state: &str) -> HealthStatus {
+    match active_state {
+        "active" | "reloading" | "refreshing" => HealthStatus::Running,
+        "activating" => HealthStatus::Starting,
+        "inactive" | "failed" | "deactivating" | "maintenance" => HealthStatus::Offline,
+        _ => HealthStatus::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_active_states() {
+        let cases = [
+            ("active", HealthStatus::Running),
+            ("reloading", HealthStatus::Running),
+            ("refreshing", HealthStatus::Running),
+            ("activating", HealthStatus::Starting),
+            ("inactive", HealthStatus::Offline),
+            ("failed", HealthStatus::Offline),
+            ("deactivating", HealthStatus::Offline),
+            ("maintenance", HealthStatus::Offline),
+            ("", HealthStatus::Unknown),
+            ("Active", HealthStatus::Unknown),
+            ("some-future-state", HealthStatus::Unknown),
+        ];
+        for (active_state, expected) in cases {
+            assert_eq!(unit_health(active_state), expected, "{active_state:?}");
+        }
+    }
+}
@@ -1,20 +1,30 @@
+mod audit;
 mod auth;
+mod bot;
+mod control;
+mod metrics;
 mod routes;
+mod secret;
 mod servers;
+#[cfg(test)]
+mod test_util;
 
-use crate::auth::OAuthClient;
+use crate::audit::AuditLog;
+use crate::auth::{ApiTokenUser, ApiTokens, DiscordProvider, OidcProvider, OidcUserData};
 use crate::routes::make_router;
-use crate::servers::ServerManager;
+use crate::servers::{unix_now, ServerManager};
 use anyhow::Context;
 use auth::DiscordUserData;
 use axum::extract::{FromRequestParts, OptionalFromRequestParts};
 use axum::response::{IntoResponse, Response};
+use http::header::AUTHORIZATION;
 use http::request::Parts;
 use http::StatusCode;
 use oauth2::basic::BasicTokenResponse;
-use reqwest::Url;
+use reqwest::{Client, Url};
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
+use std::env;
 use std::fmt::{Display, Formatter};
 use std::net::SocketAddr;
 use std::ops::Deref;
@@ -29,6 +39,7 @@ use tower_sessions_sqlx_store::SqliteStore;
 pub struct Server {
     pub bind: SocketAddr,
     pub config_path: PathBuf,
+    pub server_dir: Option<PathBuf>,
     pub public_url: Url,
 }
 
@@ -37,7 +48,11 @@ impl Server {
         tracing::debug!("Starting server: {:?}", &self);
         // let session_store = MemoryStore::default();
         let pool = SqlitePool::connect("sqlite:sessions.db?mode=rwc").await?;
-        let session_store = SqliteStore::new(pool);
+        sqlx::migrate!()
+            .run(&pool)
+            .await
+            .context("Failed to migrate database")?;
+        let session_store = SqliteStore::new(pool.clone());
         session_store
             .migrate()
             .await
@@ -49,7 +64,29 @@ impl Server {
                 .continuously_delete_expired(tokio::time::Duration::from_secs(10)),
         );
 
-        let app = make_router(&self, session_store).await?;
+        let api_tokens = ApiTokens::new(pool.clone());
+        let audit = AuditLog::new(pool.clone());
+        let server_manager = ServerManager::new(
+            Client::new(),
+            self.config_path.clone(),
+            self.server_dir.clone(),
+            pool,
+            api_tokens.clone(),
+            audit.clone(),
+        )?;
+
+        // The bot is optional, only start it if a token was provided
+        let bot_task = env::var("DISCORD_BOT_TOKEN").ok().map(|token| {
+            let server_manager = server_manager.clone();
+            tokio::spawn(async move {
+                if let Err(e) = bot::run_bot(token, server_manager).await {
+                    tracing::error!("Discord bot stopped: {:#}", e);
+                }
+            })
+            .abort_handle()
+        });
+
+        let app = make_router(&self, session_store, server_manager, api_tokens, audit).await?;
 
         let listener = tokio::net::TcpListener::bind(self.bind)
             .await
@@ -66,6 +103,10 @@ impl Server {
             .with_graceful_shutdown(shutdown_signal(deletion_task.abort_handle()))
             .await?;
 
+        if let Some(bot_task) = bot_task {
+            bot_task.abort();
+        }
+
         match deletion_task.await {
             Ok(res) => res?,
             // task being cancelled is expected, don't count as a real error
@@ -138,14 +179,47 @@ where
 
 #[derive(Clone)]
 struct AppState {
-    oauth_client: OAuthClient,
+    discord: DiscordProvider,
+    oidc: Option<OidcProvider>,
+    api_tokens: ApiTokens,
+    audit: AuditLog,
     server_manager: ServerManager,
 }
 
+/// The logged in user, depending on which provider they used
 #[derive(Debug, Serialize, Deserialize)]
-pub struct UserData {
-    pub discord_user: DiscordUserData,
-    pub tokens: BasicTokenResponse,
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum UserData {
+    Discord {
+        user: DiscordUserData,
+        tokens: BasicTokenResponse,
+        /// When the access token expires, as a unix timestamp
+        #[serde(default)]
+        expires_at: Option<i64>,
+    },
+    Oidc(OidcUserData),
+    /// Authenticated with an `Authorization: Bearer` API token, never stored in a session
+    #[serde(skip)]
+    ApiToken(ApiTokenUser),
+}
+
+impl UserData {
+    /// Stable identifier of the account, across logins
+    pub fn owner_id(&self) -> String {
+        match self {
+            UserData::Discord { user, .. } => format!("discord:{}", user.id.get()),
+            UserData::Oidc(user) => format!("oidc:{}", user.subject),
+            UserData::ApiToken(token) => token.owner.clone(),
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        match self {
+            UserData::Discord { user, .. } => &user.username,
+            UserData::Oidc(user) => &user.username,
+            UserData::ApiToken(token) => &token.owner_name,
+        }
+    }
 }
 
 pub struct User {
@@ -155,10 +229,9 @@ pub struct User {
 
 impl User {
     const USER_DATA_KEY: &'static str = "user";
-
-    pub fn username(&self) -> &str {
-        &self.user_data.discord_user.username
-    }
+    const SESSION_EXPIRED_KEY: &'static str = "session_expired";
+    /// Refresh tokens this long before they expire
+    const REFRESH_MARGIN_SECS: i64 = 5 * 60;
 
     async fn update_session(session: &Session, data: &UserData) -> Result<(), AppError> {
         session
@@ -167,6 +240,32 @@ impl User {
             .context("failed to insert user data")
             .map_err(From::from)
     }
+
+    /// Logs the user out because their provider doesn't accept their tokens anymore. Unlike
+    /// logging out, this is remembered so they can be asked to log in again.
+    pub async fn expire_session(&self) {
+        Self::expire(&self.session).await;
+    }
+
+    async fn expire(session: &Session) {
+        let result = async {
+            session.remove_value(Self::USER_DATA_KEY).await?;
+            session.insert(Self::SESSION_EXPIRED_KEY, true).await
+        };
+        if let Err(err) = result.await {
+            tracing::error!("Failed to expire session: {}", err);
+        }
+    }
+
+    /// Whether the session's login expired, see [`User::expire_session`]
+    pub async fn is_expired(session: &Session) -> bool {
+        session
+            .get::<bool>(Self::SESSION_EXPIRED_KEY)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or(false)
+    }
 }
 
 impl Deref for User {
@@ -177,23 +276,62 @@ impl Deref for User {
     }
 }
 
-impl<S> FromRequestParts<S> for User
-where
-    S: Send + Sync,
-{
+impl FromRequestParts<AppState> for User {
     type Rejection = Response;
 
-    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
         let session = Session::from_request_parts(parts, state)
             .await
             .map_err(IntoResponse::into_response)?;
 
-        let user_data = session
+        if let Some(secret) = bearer_token(parts) {
+            let token = state
+                .api_tokens
+                .authenticate(secret)
+                .await
+                .map_err(|err| AppError(err).into_response())?
+                .ok_or_else(|| StatusCode::UNAUTHORIZED.into_response())?;
+            return Ok(Self {
+                session,
+                user_data: UserData::ApiToken(token),
+            });
+        }
+
+        // Sessions from before a change to `UserData` don't decode anymore, treat them as logged out
+        let mut user_data = session
             .get::<UserData>(Self::USER_DATA_KEY)
             .await
-            .expect("Failed to read session")
+            .unwrap_or_else(|err| {
+                tracing::debug!("Failed to read user from session: {}", err);
+                None
+            })
             .ok_or_else(|| StatusCode::UNAUTHORIZED.into_response())?;
 
+        if let UserData::Discord {
+            tokens, expires_at, ..
+        } = &mut user_data
+            && expires_at.is_some_and(|at| at - Self::REFRESH_MARGIN_SECS <= unix_now())
+        {
+            match state.discord.refresh(tokens).await {
+                Ok((new_tokens, new_expires_at)) => {
+                    tracing::debug!("Refreshed Discord token");
+                    *tokens = new_tokens;
+                    *expires_at = new_expires_at;
+                    Self::update_session(&session, &user_data)
+                        .await
+                        .map_err(IntoResponse::into_response)?;
+                }
+                Err(err) => {
+                    tracing::info!("Failed to refresh Discord token, expiring session: {:#}", err);
+                    Self::expire(&session).await;
+                    return Err(StatusCode::UNAUTHORIZED.into_response());
+                }
+            }
+        }
+
         // Uncomment if we add something like a last seen time
         // Self::update_session(&session, &user_data)
         //     .await
@@ -203,19 +341,27 @@ where
     }
 }
 
-impl<S> OptionalFromRequestParts<S> for User
-where
-    S: Send + Sync,
-{
+impl OptionalFromRequestParts<AppState> for User {
     type Rejection = Infallible;
 
     async fn from_request_parts(
         parts: &mut Parts,
-        state: &S,
+        state: &AppState,
     ) -> anyhow::Result<Option<Self>, Self::Rejection> {
-        match <User as FromRequestParts<S>>::from_request_parts(parts, state).await {
+        match <User as FromRequestParts<AppState>>::from_request_parts(parts, state).await {
             Ok(res) => Ok(Some(res)),
             Err(_) => Ok(None),
         }
     }
 }
+
+/// The token from an `Authorization: Bearer` header, if there is one
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
@@ -19,6 +19,10 @@ struct Args {
     #[arg(short, long, default_value = "./config.json")]
     config_file: PathBuf,
 
+    /// Directory of extra config files, each with one or more servers
+    #[arg(long)]
+    server_dir: Option<PathBuf>,
+
     /// Public URL. Mainly used for oauth2 redirects
     #[arg(long, default_value = "http://localhost:9000")]
     public_url: Url,
@@ -43,6 +47,7 @@ async fn main() -> Result<(), AppError> {
     let server = server::Server {
         bind: (args.addr, args.port).into(),
         config_path: args.config_file,
+        server_dir: args.server_dir,
         public_url: args.public_url,
     };
 
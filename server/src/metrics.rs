@@ -0,0 +1,191 @@
+use common::status::{HealthStatus, ServerStatus};
+use once_cell::sync::Lazy;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::registry::{Registry, Unit};
+use std::sync::atomic::AtomicU64;
+
+pub(crate) static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+const HEALTH_STATES: [HealthStatus; 4] = [
+    HealthStatus::Running,
+    HealthStatus::Starting,
+    HealthStatus::Offline,
+    HealthStatus::Unknown,
+];
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub(crate) struct ServerLabels {
+    server: String,
+    game: String,
+}
+
+impl ServerLabels {
+    pub fn new(server: &str, game: &str) -> Self {
+        Self {
+            server: server.to_owned(),
+            game: game.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct HealthLabels {
+    server: String,
+    game: String,
+    state: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct GameLabels {
+    game: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct EndpointLabels {
+    endpoint: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ProviderLabels {
+    provider: String,
+}
+
+/// Process-wide Prometheus metrics, served in OpenMetrics text format at `/metrics`
+pub(crate) struct Metrics {
+    registry: Registry,
+    /// 1 for the server's current health state, 0 for the others
+    server_health: Family<HealthLabels, Gauge>,
+    players_online: Family<ServerLabels, Gauge>,
+    probe_latency: Family<ServerLabels, Gauge<f64, AtomicU64>>,
+    last_success: Family<ServerLabels, Gauge>,
+    rcon_errors: Family<GameLabels, Counter>,
+    discord_api_calls: Family<EndpointLabels, Counter>,
+    logins: Family<ProviderLabels, Counter>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let mut registry = Registry::with_prefix("homelab");
+        let metrics = Self {
+            registry: Registry::default(),
+            server_health: Family::default(),
+            players_online: Family::default(),
+            probe_latency: Family::default(),
+            last_success: Family::default(),
+            rcon_errors: Family::default(),
+            discord_api_calls: Family::default(),
+            logins: Family::default(),
+        };
+
+        registry.register(
+            "server_health",
+            "Current health state of each game server",
+            metrics.server_health.clone(),
+        );
+        registry.register(
+            "server_players_online",
+            "Players connected to each game server",
+            metrics.players_online.clone(),
+        );
+        registry.register_with_unit(
+            "server_probe_latency",
+            "Round-trip time of the last successful health probe",
+            Unit::Seconds,
+            metrics.probe_latency.clone(),
+        );
+        registry.register_with_unit(
+            "server_last_success_timestamp",
+            "Unix time of the last fetch that found the server running",
+            Unit::Seconds,
+            metrics.last_success.clone(),
+        );
+        registry.register(
+            "rcon_errors",
+            "Failed RCON connections and commands",
+            metrics.rcon_errors.clone(),
+        );
+        registry.register(
+            "discord_api_calls",
+            "Requests made to the Discord API",
+            metrics.discord_api_calls.clone(),
+        );
+        registry.register("logins", "Successful logins", metrics.logins.clone());
+
+        Self { registry, ..metrics }
+    }
+
+    pub fn record_status(&self, labels: &ServerLabels, status: &ServerStatus, now: i64) {
+        let health = status.health();
+        for state in HEALTH_STATES {
+            self.server_health
+                .get_or_create(&health_labels(labels, state))
+                .set((state == health).into());
+        }
+
+        match status.players_online() {
+            Some(players) => {
+                self.players_online.get_or_create(labels).set(players.into());
+            }
+            None => {
+                self.players_online.remove(labels);
+            }
+        }
+
+        if let ServerStatus::Generic(status) = status
+            && let Some(latency) = status.latency_ms
+        {
+            self.probe_latency
+                .get_or_create(labels)
+                .set(f64::from(latency) / 1000.0);
+        }
+
+        if health == HealthStatus::Running {
+            self.last_success.get_or_create(labels).set(now);
+        }
+    }
+
+    /// Drops every series of a server that is no longer configured
+    pub fn remove_server(&self, labels: &ServerLabels) {
+        for state in HEALTH_STATES {
+            self.server_health.remove(&health_labels(labels, state));
+        }
+        self.players_online.remove(labels);
+        self.probe_latency.remove(labels);
+        self.last_success.remove(labels);
+    }
+
+    pub fn rcon_error(&self, game: &str) {
+        self.rcon_errors
+            .get_or_create(&GameLabels { game: game.to_owned() })
+            .inc();
+    }
+
+    pub fn discord_api_call(&self, endpoint: &str) {
+        self.discord_api_calls
+            .get_or_create(&EndpointLabels { endpoint: endpoint.to_owned() })
+            .inc();
+    }
+
+    pub fn login(&self, provider: &str) {
+        self.logins
+            .get_or_create(&ProviderLabels { provider: provider.to_owned() })
+            .inc();
+    }
+
+    pub fn encode(&self) -> Result<String, std::fmt::Error> {
+        let mut buffer = String::new();
+        prometheus_client::encoding::text::encode(&mut buffer, &self.registry)?;
+        Ok(buffer)
+    }
+}
+
+fn health_labels(labels: &ServerLabels, state: HealthStatus) -> HealthLabels {
+    HealthLabels {
+        server: labels.server.clone(),
+        game: labels.game.clone(),
+        state: state.to_string(),
+    }
+}
@@ -0,0 +1,92 @@
+use crate::audit::{Actor, AuditLog};
+use crate::servers::{Principal, ServerConfig, ServerManager};
+use crate::{AppState, User};
+use axum::extract::{FromRef, FromRequestParts, RawPathParams};
+use axum::response::{IntoResponse, Response};
+use common::audit::{AuditAction, AuditOutcome};
+use common::status::Capability;
+use http::request::Parts;
+use http::StatusCode;
+use std::marker::PhantomData;
+
+/// Capability a [ServerAccess] extractor requires, as a type so routes can declare it
+pub(super) trait Requirement {
+    const CAPABILITY: Capability;
+    /// Recorded in the audit log when a user is denied the capability
+    const AUDITED: Option<AuditAction>;
+}
+
+macro_rules! requirement {
+    ($name: ident, $audited: expr) => {
+        pub(in crate::routes::api) struct $name;
+
+        impl Requirement for $name {
+            const CAPABILITY: Capability = Capability::$name;
+            const AUDITED: Option<AuditAction> = $audited;
+        }
+    };
+}
+
+pub(super) mod require {
+    use super::*;
+
+    requirement!(View, None);
+    requirement!(Console, Some(AuditAction::ConsoleCommand));
+    requirement!(Control, Some(AuditAction::Lifecycle));
+    requirement!(Wake, Some(AuditAction::Lifecycle));
+    requirement!(Saves, Some(AuditAction::SaveDownload));
+    requirement!(Mods, Some(AuditAction::ModChange));
+}
+
+/// The server named by the `{id}` path segment, extracted only if the logged-in user has the
+/// capability `R`. Rejects with 404 if the user can't see the server at all, so its existence
+/// isn't leaked, and 403 if they can see it but lack `R`.
+pub(super) struct ServerAccess<R: Requirement = require::View> {
+    pub user: User,
+    pub principal: Principal,
+    pub config: ServerConfig,
+    _requirement: PhantomData<R>,
+}
+
+impl<R: Requirement> FromRequestParts<AppState> for ServerAccess<R> {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let user = User::from_request_parts(parts, state).await?;
+        let params = RawPathParams::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Some((_, id)) = params.iter().find(|(key, _)| *key == "id") else {
+            return Err(StatusCode::NOT_FOUND.into_response());
+        };
+
+        let server_manager = ServerManager::from_ref(state);
+        let principal = server_manager.get_principal(&user).await;
+        let Some(config) = server_manager.get_server_config(id, &principal).await else {
+            return Err(StatusCode::NOT_FOUND.into_response());
+        };
+
+        if !config.capabilities(&principal).contains(&R::CAPABILITY) {
+            if let Some(action) = R::AUDITED {
+                let detail = format!("{} {}", parts.method, parts.uri.path());
+                AuditLog::from_ref(state)
+                    .record(
+                        Some(&Actor::from(&*user)),
+                        action,
+                        Some(config.id()),
+                        AuditOutcome::Denied,
+                        &detail,
+                    )
+                    .await;
+            }
+            return Err(StatusCode::FORBIDDEN.into_response());
+        }
+
+        Ok(Self {
+            user,
+            principal,
+            config,
+            _requirement: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,23 @@
+use crate::audit::{AuditLog, AuditQuery};
+use crate::servers::ServerManager;
+use crate::{AppError, User};
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use http::StatusCode;
+
+/// Searches the audit log, newest first. Only for admins.
+pub(super) async fn get_audit_log(
+    user: User,
+    State(server_manager): State<ServerManager>,
+    State(audit): State<AuditLog>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Response, AppError> {
+    let principal = server_manager.get_principal(&user).await;
+    if !server_manager.is_admin(&principal).await {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let page = audit.query(&query).await?;
+    Ok(Json(page).into_response())
+}
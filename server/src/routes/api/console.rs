@@ -0,0 +1,42 @@
+use crate::routes::api::access::{require, ServerAccess};
+use crate::servers::ServerManager;
+use crate::AppError;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use common::console::{ConsoleCommand, ConsoleOutcome};
+use http::StatusCode;
+
+pub(super) async fn run_console_command(
+    access: ServerAccess<require::Console>,
+    State(server_manager): State<ServerManager>,
+    Json(request): Json<ConsoleCommand>,
+) -> Result<Response, AppError> {
+    if request.command.trim().is_empty() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    let entry = server_manager
+        .run_console_command(&access.config, &access.principal, &access.user, &request.command)
+        .await?;
+    let Some(entry) = entry else {
+        return Ok((StatusCode::CONFLICT, "Server has no console").into_response());
+    };
+
+    let status = match entry.outcome {
+        ConsoleOutcome::Ok => StatusCode::OK,
+        ConsoleOutcome::Denied => StatusCode::FORBIDDEN,
+        ConsoleOutcome::Error => StatusCode::BAD_GATEWAY,
+    };
+    Ok((status, Json(entry)).into_response())
+}
+
+/// Recent commands anyone ran on the server, so everyone with console access sees what
+/// happened
+pub(super) async fn get_console_log(
+    access: ServerAccess<require::Console>,
+    State(server_manager): State<ServerManager>,
+) -> Result<Response, AppError> {
+    let entries = server_manager.get_console_log(access.config.id()).await?;
+    Ok(Json(entries).into_response())
+}
@@ -1,26 +1,84 @@
+mod access;
+mod audit;
+mod console;
+mod mods;
+mod saves;
 mod servers;
+mod tokens;
 
-use crate::routes::api::servers::get_servers;
+use crate::auth::{DiscordProvider, LoginProvider, OidcProvider};
+use crate::routes::api::audit::get_audit_log;
+use crate::routes::api::console::{get_console_log, run_console_command};
+use crate::routes::api::mods::{list_mods, set_mod_state, upload_mod};
+use crate::routes::api::saves::{download_backup, download_save, list_saves, save_game};
+use crate::routes::api::servers::{
+    control_server, get_server_history, get_servers, stream_servers, wake_server,
+};
+use crate::routes::api::tokens::{create_token, list_tokens, revoke_token};
+use crate::servers::ServerManager;
 use crate::{AppError, AppState, User};
+use axum::extract::{DefaultBodyLimit, State};
 use axum::response::IntoResponse;
-use axum::routing::get;
+use axum::routing::{delete, get, post, put};
 use axum::{Json, Router};
-use common::user::UserData;
+use common::user::{self, LoginState, UserData};
+use tower_sessions::Session;
 
 pub(super) fn make_api_router() -> Router<AppState> {
     Router::new()
         .route("/me", get(get_user_data))
+        .route("/auth/providers", get(get_login_providers))
         .route("/servers/status", get(get_servers))
+        .route("/servers/status/stream", get(stream_servers))
+        .route("/servers/{id}/history", get(get_server_history))
+        .route("/servers/{id}/wake", post(wake_server))
+        .route("/servers/{id}/rcon", get(get_console_log).post(run_console_command))
+        .route("/servers/{id}/saves", get(list_saves).post(save_game))
+        .route("/servers/{id}/saves/{name}", get(download_save))
+        .route("/servers/{id}/saves/backups/{name}", get(download_backup))
+        .route("/servers/{id}/mods", get(list_mods))
+        .route("/servers/{id}/mods/{name}", put(set_mod_state))
+        .route(
+            "/servers/{id}/mods/files/{file_name}",
+            // Mod zips easily exceed the default 2 MB body limit, the upload enforces its own
+            put(upload_mod).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/servers/{id}/{action}", post(control_server))
+        .route("/tokens", get(list_tokens).post(create_token))
+        .route("/tokens/{id}", delete(revoke_token))
+        .route("/audit", get(get_audit_log))
 }
 
-async fn get_user_data(user: Option<User>) -> anyhow::Result<impl IntoResponse, AppError> {
+async fn get_user_data(
+    user: Option<User>,
+    State(server_manager): State<ServerManager>,
+    session: Session,
+) -> anyhow::Result<impl IntoResponse, AppError> {
     let Some(user) = user else {
-        return Ok(Json(None));
+        if User::is_expired(&session).await {
+            return Ok(Json(LoginState::Expired));
+        }
+        return Ok(Json(LoginState::LoggedOut));
     };
 
+    let principal = server_manager.get_principal(&user).await;
     let data = UserData {
-        name: user.user_data.discord_user.username.into(),
+        name: user.username().into(),
+        admin: server_manager.is_admin(&principal).await,
     };
 
-    Ok(Json(Some(data)))
+    Ok(Json(LoginState::LoggedIn(data)))
+}
+
+/// Lists the login providers that are configured, for the login button
+async fn get_login_providers(State(state): State<AppState>) -> Json<Vec<user::LoginProvider>> {
+    fn entry<P: LoginProvider>(state: &AppState) -> Option<user::LoginProvider> {
+        P::from_state(state).map(|provider| user::LoginProvider {
+            id: P::ID.into(),
+            name: provider.display_name().into(),
+        })
+    }
+
+    let providers = [entry::<DiscordProvider>(&state), entry::<OidcProvider>(&state)];
+    Json(providers.into_iter().flatten().collect())
 }
@@ -0,0 +1,93 @@
+use crate::audit::{Actor, AuditLog};
+use crate::routes::api::access::{require, ServerAccess};
+use crate::servers::{ServerManager, Upload};
+use crate::AppError;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use common::audit::{AuditAction, AuditOutcome};
+use common::factorio::ModState;
+use http::StatusCode;
+use smol_str::SmolStr;
+
+pub(super) async fn list_mods(
+    access: ServerAccess<require::Mods>,
+    State(server_manager): State<ServerManager>,
+) -> Result<Response, AppError> {
+    match server_manager.list_mods(&access.config).await? {
+        Some(mods) => Ok(Json(mods).into_response()),
+        None => Ok((StatusCode::CONFLICT, "Server has no mods directory").into_response()),
+    }
+}
+
+/// Enables or disables a mod in `mod-list.json`. The server only picks it up when restarted.
+pub(super) async fn set_mod_state(
+    access: ServerAccess<require::Mods>,
+    State(server_manager): State<ServerManager>,
+    State(audit): State<AuditLog>,
+    Path((_, name)): Path<(SmolStr, SmolStr)>,
+    Json(state): Json<ModState>,
+) -> Result<Response, AppError> {
+    let config = &access.config;
+    let verb = if state.enabled { "enable" } else { "disable" };
+    tracing::info!("{} requested to {} {} on {}", access.user.username(), verb, name, config.name);
+    let result = server_manager.set_mod_enabled(config, &name, state.enabled).await;
+
+    let (outcome, detail) = match &result {
+        Ok(true) => (AuditOutcome::Ok, format!("{verb} {name}")),
+        Ok(false) => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(err) => (AuditOutcome::Error, format!("{verb} {name}: {err}")),
+    };
+    audit
+        .record(
+            Some(&Actor::from(&*access.user)),
+            AuditAction::ModChange,
+            Some(config.id()),
+            outcome,
+            &detail,
+        )
+        .await;
+
+    result?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Stores the request body as `{name}_{version}.zip` in the mods directory
+pub(super) async fn upload_mod(
+    access: ServerAccess<require::Mods>,
+    State(server_manager): State<ServerManager>,
+    State(audit): State<AuditLog>,
+    Path((_, file_name)): Path<(SmolStr, SmolStr)>,
+    body: Body,
+) -> Result<Response, AppError> {
+    let config = &access.config;
+    tracing::info!("{} is uploading mod {} to {}", access.user.username(), file_name, config.name);
+    let result = server_manager
+        .upload_mod(config, &file_name, body.into_data_stream())
+        .await;
+
+    let (outcome, detail) = match &result {
+        Ok(Some(Upload::Stored)) => (AuditOutcome::Ok, format!("upload {file_name}")),
+        Ok(Some(Upload::NotZip)) => {
+            return Ok((StatusCode::UNPROCESSABLE_ENTITY, "Not a zip file").into_response());
+        }
+        Ok(Some(Upload::TooLarge)) => return Ok(StatusCode::PAYLOAD_TOO_LARGE.into_response()),
+        Ok(None) => {
+            return Ok((StatusCode::BAD_REQUEST, "Mods must be named {name}_{version}.zip").into_response());
+        }
+        Err(err) => (AuditOutcome::Error, format!("upload {file_name}: {err}")),
+    };
+    audit
+        .record(
+            Some(&Actor::from(&*access.user)),
+            AuditAction::ModChange,
+            Some(config.id()),
+            outcome,
+            &detail,
+        )
+        .await;
+
+    result?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
@@ -0,0 +1,118 @@
+use crate::audit::{Actor, AuditLog};
+use crate::routes::api::access::{require, ServerAccess};
+use crate::servers::ServerManager;
+use crate::AppError;
+use axum::body::Body;
+use axum::extract::{Path, Request, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use common::audit::{AuditAction, AuditOutcome};
+use http::header::CONTENT_DISPOSITION;
+use http::{HeaderValue, StatusCode};
+use smol_str::SmolStr;
+use tower_http::services::ServeFile;
+
+pub(super) async fn list_saves(
+    access: ServerAccess<require::Saves>,
+    State(server_manager): State<ServerManager>,
+) -> Result<Response, AppError> {
+    match server_manager.list_saves(&access.config).await? {
+        Some(saves) => Ok(Json(saves).into_response()),
+        None => Ok((StatusCode::CONFLICT, "Server has no saves directory").into_response()),
+    }
+}
+
+/// Runs `/server-save`. Factorio answers before the save is written, so it may take a moment
+/// to show up in the list.
+pub(super) async fn save_game(
+    access: ServerAccess<require::Saves>,
+    State(server_manager): State<ServerManager>,
+    State(audit): State<AuditLog>,
+) -> Result<Response, AppError> {
+    let config = &access.config;
+    tracing::info!("{} requested a save of {}", access.user.username(), config.name);
+    let result = server_manager.save_game(config).await;
+
+    let (outcome, detail) = match &result {
+        Ok(Some(response)) => (AuditOutcome::Ok, response.trim().to_owned()),
+        Ok(None) => return Ok((StatusCode::CONFLICT, "Server can't be saved").into_response()),
+        Err(err) => (AuditOutcome::Error, err.to_string()),
+    };
+    audit
+        .record(
+            Some(&Actor::from(&*access.user)),
+            AuditAction::ServerSave,
+            Some(config.id()),
+            outcome,
+            &detail,
+        )
+        .await;
+
+    match result {
+        Ok(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(_) => Ok((StatusCode::BAD_GATEWAY, detail).into_response()),
+    }
+}
+
+pub(super) async fn download_save(
+    access: ServerAccess<require::Saves>,
+    State(server_manager): State<ServerManager>,
+    State(audit): State<AuditLog>,
+    Path((_, name)): Path<(SmolStr, SmolStr)>,
+    request: Request,
+) -> Result<Response, AppError> {
+    send_save(access, server_manager, audit, &name, false, request).await
+}
+
+pub(super) async fn download_backup(
+    access: ServerAccess<require::Saves>,
+    State(server_manager): State<ServerManager>,
+    State(audit): State<AuditLog>,
+    Path((_, name)): Path<(SmolStr, SmolStr)>,
+    request: Request,
+) -> Result<Response, AppError> {
+    send_save(access, server_manager, audit, &name, true, request).await
+}
+
+/// Streams the file, with support for range requests so big saves can resume
+async fn send_save(
+    access: ServerAccess<require::Saves>,
+    server_manager: ServerManager,
+    audit: AuditLog,
+    name: &str,
+    backup: bool,
+    request: Request,
+) -> Result<Response, AppError> {
+    let Some(path) = server_manager.save_path(&access.config, name, backup) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let mut response = ServeFile::new(path)
+        .try_call(request)
+        .await?
+        .map(Body::new);
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+
+    audit
+        .record(
+            Some(&Actor::from(&*access.user)),
+            AuditAction::SaveDownload,
+            Some(access.config.id()),
+            AuditOutcome::Ok,
+            name,
+        )
+        .await;
+    response.headers_mut().insert(
+        CONTENT_DISPOSITION,
+        HeaderValue::from_str(&attachment(name))?,
+    );
+    Ok(response)
+}
+
+/// `Content-Disposition` value that makes browsers download the save under its own name
+fn attachment(name: &str) -> String {
+    // A quote would end the file name early
+    format!("attachment; filename=\"{}\"", name.replace('"', ""))
+}
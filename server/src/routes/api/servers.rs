@@ -1,32 +1,185 @@
-use crate::servers::ServerManager;
+use crate::audit::{Actor, AuditLog};
+use crate::control::{Lifecycle, LifecycleAction};
+use crate::routes::api::access::{require, Requirement, ServerAccess};
+use crate::servers::{unix_now, ServerManager};
 use crate::{AppError, User};
-use axum::extract::{Query, State};
-use axum::response::IntoResponse;
+use axum::extract::{Path, Query, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
+use futures::{stream, Stream, StreamExt};
+use http::StatusCode;
 use smol_str::SmolStr;
-use common::status::ServerStatus;
+use common::audit::{AuditAction, AuditOutcome};
+use common::status::{ServerInfo, ServerStatus, StatusEvent};
 
 #[derive(serde::Deserialize)]
 pub(super) struct Filters {
     game: Option<SmolStr>,
 }
 
+impl Filters {
+    fn matches(&self, server: &ServerInfo) -> bool {
+        let Some(ref filter) = self.game else {
+            return true;
+        };
+
+        match server.status {
+            ServerStatus::Factorio(_) => filter == "Factorio",
+            ServerStatus::Generic(_) => filter == "Generic",
+            ServerStatus::Minecraft(_) => filter == "Minecraft",
+            ServerStatus::SourceQuery(_) => filter == "SourceQuery",
+        }
+    }
+}
+
 pub(super) async fn get_servers(
     user: User,
     State(server_manager): State<ServerManager>,
+    State(audit): State<AuditLog>,
     filters: Query<Filters>,
 ) -> Result<impl IntoResponse, AppError> {
     let servers = server_manager.get_servers_for_user(&user).await?;
-    let filtered = if let Some(ref filter) = filters.game {
-        servers.into_iter().filter(|s| {
-            match s {
-                ServerStatus::Factorio(_) => filter == "Factorio",
-                ServerStatus::Generic(_) => filter == "Generic",
-            }
-        }).collect()
-    } else {
-        servers
-    };
+    let filtered = servers
+        .into_iter()
+        .filter(|s| filters.matches(s))
+        .collect::<Vec<_>>();
+    audit.secrets_revealed(&Actor::from(&*user), &filtered).await;
 
     Ok(Json(filtered))
 }
+
+/// Streams [StatusEvent]s as Server-Sent Events, starting with a snapshot of every visible
+/// server followed by a delta whenever one of them changes.
+///
+/// Permissions are resolved once when the stream is opened, so permission changes only apply after
+/// the client reconnects.
+pub(super) async fn stream_servers(
+    user: User,
+    State(server_manager): State<ServerManager>,
+    Query(filters): Query<Filters>,
+) -> Result<impl IntoResponse, AppError> {
+    let principal = server_manager.get_principal(&user).await;
+    let actor = Actor::from(&*user);
+    // Subscribe before taking the snapshot so no change can slip in between
+    let updates = server_manager.subscribe();
+
+    let state = (server_manager, (principal, actor), filters, updates, None::<Vec<ServerInfo>>);
+    let events = stream::unfold(state, |(manager, who, filters, mut updates, sent)| async move {
+        if sent.is_some() && updates.changed().await.is_err() {
+            return None;
+        }
+
+        let (principal, actor) = &who;
+        let servers = manager
+            .get_servers(principal)
+            .await
+            .into_iter()
+            .filter(|s| filters.matches(s))
+            .collect::<Vec<_>>();
+        manager.audit().secrets_revealed(actor, &servers).await;
+
+        let events = match &sent {
+            None => vec![StatusEvent::Snapshot(servers.clone())],
+            Some(sent) => diff_statuses(sent, &servers),
+        };
+
+        Some((events, (manager, who, filters, updates, Some(servers))))
+    });
+
+    Ok(Sse::new(into_sse_events(events)).keep_alive(KeepAlive::default()))
+}
+
+fn into_sse_events(
+    events: impl Stream<Item = Vec<StatusEvent>>,
+) -> impl Stream<Item = Result<Event, axum::Error>> {
+    events
+        .flat_map(stream::iter)
+        .map(|event| Event::default().json_data(event))
+}
+
+fn diff_statuses(old: &[ServerInfo], new: &[ServerInfo]) -> Vec<StatusEvent> {
+    let updated = new
+        .iter()
+        .filter(|server| !old.contains(server))
+        .map(|server| StatusEvent::Updated(server.clone().into()));
+
+    let removed = old
+        .iter()
+        .filter(|server| !new.iter().any(|s| s.id == server.id))
+        .map(|server| StatusEvent::Removed(server.id.clone()));
+
+    updated.chain(removed).collect()
+}
+
+pub(super) async fn control_server(
+    access: ServerAccess<require::Control>,
+    State(audit): State<AuditLog>,
+    Path((_, action)): Path<(SmolStr, LifecycleAction)>,
+) -> Result<Response, AppError> {
+    run_lifecycle_action(access, audit, action).await
+}
+
+pub(super) async fn wake_server(
+    access: ServerAccess<require::Wake>,
+    State(audit): State<AuditLog>,
+) -> Result<Response, AppError> {
+    run_lifecycle_action(access, audit, LifecycleAction::Wake).await
+}
+
+async fn run_lifecycle_action<R: Requirement>(
+    access: ServerAccess<R>,
+    audit: AuditLog,
+    action: LifecycleAction,
+) -> Result<Response, AppError> {
+    let config = &access.config;
+    let Some(control) = &config.control else {
+        return Ok((StatusCode::CONFLICT, "Server has no lifecycle control configured").into_response());
+    };
+
+    tracing::info!("{} requested {} of {}", access.user.username(), action, config.name);
+    let result = control.backend.run(action).await;
+
+    let (outcome, detail) = match &result {
+        Ok(()) => (AuditOutcome::Ok, action.to_string()),
+        Err(err) => (AuditOutcome::Error, format!("{action}: {err:#}")),
+    };
+    audit
+        .record(
+            Some(&Actor::from(&*access.user)),
+            AuditAction::Lifecycle,
+            Some(config.id()),
+            outcome,
+            &detail,
+        )
+        .await;
+    result?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Range used when the client doesn't ask for one
+const DEFAULT_HISTORY_SECS: i64 = 7 * 24 * 60 * 60;
+
+#[derive(serde::Deserialize)]
+pub(super) struct HistoryRange {
+    /// Unix seconds, defaults to a week before `to`
+    from: Option<i64>,
+    /// Unix seconds, defaults to now
+    to: Option<i64>,
+}
+
+pub(super) async fn get_server_history(
+    access: ServerAccess,
+    State(server_manager): State<ServerManager>,
+    Query(range): Query<HistoryRange>,
+) -> Result<Response, AppError> {
+    let to = range.to.unwrap_or_else(unix_now);
+    let from = range.from.unwrap_or(to - DEFAULT_HISTORY_SECS);
+    if from > to {
+        return Ok((StatusCode::BAD_REQUEST, "`from` must not be after `to`").into_response());
+    }
+
+    let history = server_manager.get_history(access.config.id(), from, to).await?;
+    Ok(Json(history).into_response())
+}
@@ -0,0 +1,90 @@
+use crate::audit::{Actor, AuditLog};
+use crate::auth::ApiTokens;
+use crate::servers::ServerManager;
+use crate::{AppError, User, UserData};
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use common::audit::{AuditAction, AuditOutcome};
+use common::user::NewApiToken;
+use http::StatusCode;
+
+const MAX_NAME_LEN: usize = 64;
+
+/// Tokens can't be used to manage tokens, that needs a real login
+fn is_api_token(user: &User) -> bool {
+    matches!(&**user, UserData::ApiToken(_))
+}
+
+pub(super) async fn list_tokens(
+    user: User,
+    State(api_tokens): State<ApiTokens>,
+) -> Result<Response, AppError> {
+    if is_api_token(&user) {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let tokens = api_tokens.list(&user.owner_id()).await?;
+    Ok(Json(tokens).into_response())
+}
+
+pub(super) async fn create_token(
+    user: User,
+    State(api_tokens): State<ApiTokens>,
+    State(server_manager): State<ServerManager>,
+    State(audit): State<AuditLog>,
+    Json(mut request): Json<NewApiToken>,
+) -> Result<Response, AppError> {
+    if is_api_token(&user) {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+    request.name = request.name.trim().into();
+    let name_len = request.name.chars().count();
+    if name_len == 0 || name_len > MAX_NAME_LEN || request.capabilities.is_empty() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    let principal = server_manager.get_principal(&user).await;
+    let created = api_tokens
+        .create(&user.owner_id(), user.username(), &principal, request)
+        .await?;
+    tracing::info!("{} created API token {}", user.username(), created.token.name);
+    audit
+        .record(
+            Some(&Actor::from(&*user)),
+            AuditAction::TokenCreate,
+            None,
+            AuditOutcome::Ok,
+            &created.token.name,
+        )
+        .await;
+
+    Ok((StatusCode::CREATED, Json(created)).into_response())
+}
+
+pub(super) async fn revoke_token(
+    user: User,
+    State(api_tokens): State<ApiTokens>,
+    State(audit): State<AuditLog>,
+    Path(id): Path<i64>,
+) -> Result<Response, AppError> {
+    if is_api_token(&user) {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    if !api_tokens.revoke(&user.owner_id(), id).await? {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    tracing::info!("{} revoked API token {}", user.username(), id);
+    audit
+        .record(
+            Some(&Actor::from(&*user)),
+            AuditAction::TokenRevoke,
+            None,
+            AuditOutcome::Ok,
+            &id.to_string(),
+        )
+        .await;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
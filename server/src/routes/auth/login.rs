@@ -0,0 +1,115 @@
+use crate::audit::Actor;
+use crate::auth::{AuthRequest, LoginProvider, PendingLogin, PENDING_LOGIN};
+use crate::metrics::METRICS;
+use crate::servers::Principal;
+use crate::{AppError, AppState, User, UserData};
+use anyhow::{anyhow, Context};
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Redirect, Response};
+use common::audit::{AuditAction, AuditOutcome};
+use http::StatusCode;
+use oauth2::AuthorizationCode;
+use tower_sessions::Session;
+
+pub async fn begin_login<P: LoginProvider>(
+    State(state): State<AppState>,
+    session: Session,
+) -> anyhow::Result<Response, AppError> {
+    let Some(provider) = P::from_state(&state) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    tracing::debug!("Logging in via {}", P::ID);
+    let (auth_url, pending) = provider.begin_login();
+
+    // Clear old session
+    if session.id().is_some() {
+        session
+            .delete()
+            .await
+            .context("failed to delete old session")?;
+    }
+
+    // Save csrf_token and whatever else the provider needs on the way back
+    session
+        .insert(PENDING_LOGIN, &pending)
+        .await
+        .context("failed in inserting pending login into session")?;
+
+    Ok(Redirect::to(auth_url.as_ref()).into_response())
+}
+
+async fn csrf_token_validation_workflow(
+    auth_request: &AuthRequest,
+    session: &Session,
+) -> anyhow::Result<PendingLogin, AppError> {
+    let pending = session
+        .get::<PendingLogin>(PENDING_LOGIN)
+        .await
+        .context("failed to read pending login from session")?
+        .context("Pending login missing")?;
+
+    // Cleanup the CSRF token session
+    session
+        .delete()
+        .await
+        .context("Failed to destroy old session")?;
+
+    // Validate CSRF token is the same as the one in the auth request
+    if *pending.csrf_token.secret() != auth_request.state {
+        return Err(anyhow!("CSRF token mismatch").into());
+    }
+
+    Ok(pending)
+}
+
+pub async fn login_authorized<P: LoginProvider>(
+    Query(query): Query<AuthRequest>,
+    State(state): State<AppState>,
+    session: Session,
+) -> anyhow::Result<Response, AppError> {
+    let Some(provider) = P::from_state(&state) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let pending = csrf_token_validation_workflow(&query, &session).await?;
+
+    let user_data = match provider
+        .complete_login(AuthorizationCode::new(query.code), pending)
+        .await
+    {
+        Ok(user_data) => user_data,
+        Err(err) => {
+            let detail = format!("{}: {:#}", P::ID, err);
+            state
+                .audit
+                .record(None, AuditAction::Login, None, AuditOutcome::Error, &detail)
+                .await;
+            return Err(err.into());
+        }
+    };
+
+    // OIDC groups only change on login, so pass them on to the user's API tokens now
+    if let UserData::Oidc(oidc_user) = &user_data
+        && let Err(err) = state
+            .api_tokens
+            .update_principal(&user_data.owner_id(), &Principal::oidc(oidc_user))
+            .await
+    {
+        tracing::error!("Failed to update API tokens of {}: {:#}", oidc_user.username, err);
+    }
+
+    // Insert user data into session
+    User::update_session(&session, &user_data).await?;
+    METRICS.login(P::ID);
+    state
+        .audit
+        .record(
+            Some(&Actor::from(&user_data)),
+            AuditAction::Login,
+            None,
+            AuditOutcome::Ok,
+            P::ID,
+        )
+        .await;
+
+    Ok(Redirect::to("/").into_response())
+}
@@ -1,6 +1,7 @@
-mod discord;
+mod login;
 
 use crate::AppState;
+use crate::auth::{DiscordProvider, LoginProvider, OidcProvider};
 use axum::response::IntoResponse;
 use axum::routing::get;
 use axum::Router;
@@ -8,11 +9,20 @@ use http::StatusCode;
 
 pub(super) fn make_auth_router() -> Router<AppState> {
     Router::new()
-        .route("/discord", get(discord::discord_auth))
-        .route("/discord/authorize", get(discord::login_authorized))
+        .merge(provider_routes::<DiscordProvider>())
+        .merge(provider_routes::<OidcProvider>())
         .fallback(fallback)
 }
 
+fn provider_routes<P: LoginProvider>() -> Router<AppState> {
+    Router::new()
+        .route(&format!("/{}", P::ID), get(login::begin_login::<P>))
+        .route(
+            &format!("/{}/authorize", P::ID),
+            get(login::login_authorized::<P>),
+        )
+}
+
 async fn fallback() -> impl IntoResponse {
     (StatusCode::NOT_FOUND, "Not Found")
 }
@@ -1,12 +1,17 @@
+use crate::audit::{Actor, AuditLog};
+use crate::auth::{ApiTokens, DiscordProvider, OidcProvider};
+use crate::metrics::METRICS;
 use crate::routes::api::make_api_router;
 use crate::routes::auth::make_auth_router;
 use crate::servers::ServerManager;
-use crate::{AppError, AppResult, AppState, Server, User};
+use crate::{AppError, AppResult, AppState, Server, User, UserData};
 use anyhow::Context;
+use axum::extract::State;
 use axum::response::{IntoResponse, Redirect};
 use axum::routing::get;
 use axum::Router;
-use reqwest::Client;
+use common::audit::{AuditAction, AuditOutcome};
+use http::header::CONTENT_TYPE;
 use std::path::Path;
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::trace::TraceLayer;
@@ -20,12 +25,21 @@ mod auth;
 pub async fn make_router<Store: SessionStore + Clone>(
     server: &Server,
     session_store: Store,
+    server_manager: ServerManager,
+    api_tokens: ApiTokens,
+    audit: AuditLog,
 ) -> AppResult<Router> {
-    // `MemoryStore` is just used as an example. Don't use this in production.
-    let oauth_client = crate::auth::oauth_client(server)?;
+    let discord = DiscordProvider::from_env(server)?;
+    let oidc = OidcProvider::from_env(server).await?;
+    if oidc.is_some() {
+        tracing::info!("OpenID Connect login enabled");
+    }
     let app_state = AppState {
-        oauth_client,
-        server_manager: ServerManager::new(Client::new(), server.config_path.clone())?,
+        discord,
+        oidc,
+        api_tokens,
+        audit,
+        server_manager,
     };
 
     let session_layer = SessionManagerLayer::new(session_store)
@@ -42,6 +56,7 @@ pub async fn make_router<Store: SessionStore + Clone>(
         // .route("/", get(index))
         .route("/protected", get(protected))
         .route("/logout", get(logout))
+        .route("/metrics", get(metrics))
         .nest("/auth", make_auth_router())
         .nest("/api", make_api_router())
         .nest_service("/assets", ServeDir::new(static_dir))
@@ -58,7 +73,29 @@ async fn protected(user: User) -> impl IntoResponse {
     )
 }
 
-pub async fn logout(session: Session) -> anyhow::Result<impl IntoResponse, AppError> {
+pub async fn logout(
+    State(state): State<AppState>,
+    user: Option<User>,
+    session: Session,
+) -> anyhow::Result<impl IntoResponse, AppError> {
+    if let Some(user) = user {
+        if let UserData::Discord { tokens, .. } = &*user
+            && let Err(err) = state.discord.revoke(tokens).await
+        {
+            tracing::warn!("Failed to revoke Discord token: {:#}", err);
+        }
+        state
+            .audit
+            .record(
+                Some(&Actor::from(&*user)),
+                AuditAction::Logout,
+                None,
+                AuditOutcome::Ok,
+                "",
+            )
+            .await;
+    }
+
     session
         .delete()
         .await
@@ -66,3 +103,13 @@ pub async fn logout(session: Session) -> anyhow::Result<impl IntoResponse, AppEr
 
     Ok(Redirect::to("/"))
 }
+
+/// Prometheus scrape endpoint. Unauthenticated, so keep it off the public internet if server
+/// names are sensitive.
+async fn metrics() -> Result<impl IntoResponse, AppError> {
+    let body = METRICS.encode()?;
+    Ok((
+        [(CONTENT_TYPE, "application/openmetrics-text; version=1.0.0; charset=utf-8")],
+        body,
+    ))
+}
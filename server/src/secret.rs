@@ -0,0 +1,125 @@
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use smol_str::SmolStr;
+use std::fmt::{Debug, Formatter};
+use std::path::PathBuf;
+
+/// A password or similar that shouldn't be logged. In config files it's either the value itself
+/// or where to read it from: `{ "env": "NAME" }` for an environment variable, or
+/// `{ "file": "/path" }` for the contents of a file, e.g. a systemd credential.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Secret(SmolStr);
+
+impl Secret {
+    #[cfg(test)]
+    pub fn new(value: SmolStr) -> Secret {
+        Self(value)
+    }
+
+    pub fn secret(&self) -> &SmolStr {
+        &self.0
+    }
+}
+
+/// How a secret is written in a config file
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "a string, { \"env\": \"NAME\" } or { \"file\": \"/path\" }")]
+enum SecretSource {
+    Value(SmolStr),
+    Reference(Reference),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Reference {
+    Env(String),
+    File(PathBuf),
+}
+
+impl SecretSource {
+    /// Reads the secret from wherever it's stored. Errors never contain the value.
+    fn resolve(self) -> Result<SmolStr, String> {
+        match self {
+            Self::Value(value) => Ok(value),
+            Self::Reference(Reference::Env(name)) => std::env::var(&name)
+                .map(SmolStr::from)
+                .map_err(|_| format!("environment variable {name} for secret isn't set")),
+            Self::Reference(Reference::File(path)) => {
+                let contents = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("failed to read secret file {}: {e}", path.display()))?;
+                // Files written by editors and `echo` end with a newline that isn't part of the
+                // secret
+                Ok(contents.trim_end_matches(['\r', '\n']).into())
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = SecretSource::deserialize(deserializer)?;
+        source.resolve().map(Self).map_err(D::Error::custom)
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use serde_json::json;
+
+    fn parse(value: serde_json::Value) -> Result<SmolStr, String> {
+        serde_json::from_value::<Secret>(value)
+            .map(|secret| secret.0)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn keeps_plain_values() {
+        // Values that looked like references before they had their own syntax stay as they are
+        for value in ["hunter2", "${HOME}", "pa$$word", "file:/etc/passwd", ""] {
+            assert_eq!(parse(json!(value)).as_deref(), Ok(value));
+        }
+    }
+
+    #[test]
+    fn reads_environment_variables() {
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(parse(json!({ "env": "PATH" })).as_deref(), Ok(&*path));
+
+        let err = parse(json!({ "env": "SERVER_TEST_UNSET_SECRET" })).unwrap_err();
+        assert_eq!(err, "environment variable SERVER_TEST_UNSET_SECRET for secret isn't set");
+    }
+
+    #[test]
+    fn reads_files() {
+        let dir = TempDir::new();
+        let path = dir.write("rcon-password", "hunter2\r\n");
+        assert_eq!(parse(json!({ "file": path })).as_deref(), Ok("hunter2"));
+        let path = dir.write("keeps-spaces", " hunter2 ");
+        assert_eq!(parse(json!({ "file": path })).as_deref(), Ok(" hunter2 "));
+
+        let err = parse(json!({ "file": dir.path().join("missing") })).unwrap_err();
+        assert!(err.contains("failed to read secret file"), "{err}");
+    }
+
+    #[test]
+    fn rejects_unknown_references() {
+        for value in [json!({ "vault": "rcon" }), json!({ "env": "A", "file": "b" }), json!(1)] {
+            let err = parse(value.clone()).unwrap_err();
+            assert!(err.contains("a string, { \"env\""), "{value}: {err}");
+        }
+    }
+
+    #[test]
+    fn never_prints_the_value() {
+        let secret = Secret::new("hunter2".into());
+        assert_eq!(format!("{secret:?}"), "Secret(<redacted>)");
+    }
+}
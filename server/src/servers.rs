@@ -1,116 +1,435 @@
-use crate::auth::GuildMember;
-use crate::servers::config::ConfigStore;
-use crate::{AppError, AppResult, AppState, User};
+use crate::audit::{Actor, AuditLog};
+use crate::auth::{ApiTokens, DiscordUserData, GuildMember, TokenRejected};
+use crate::metrics::METRICS;
+use crate::servers::config::{ConfigStore, GameConfig};
+use crate::servers::console::ConsoleLog;
+use crate::servers::history::HistoryRecorder;
+use crate::servers::poller::{StatusPoller, StatusSnapshot};
+use crate::{AppError, AppResult, AppState, User, UserData};
 use anyhow::Context;
 use axum::extract::FromRef;
-use common::discord::{RoleId, UserId};
-use common::status::ServerStatus;
-use config::{GameConfig, ServerConfig};
+use futures::{future, Stream};
+use http::StatusCode;
+use common::audit::{AuditAction, AuditOutcome};
+use common::console::{ConsoleEntry, ConsoleOutcome};
+use common::discord::{GuildId, RoleId, UserId};
+use common::factorio::{ModOverview, SaveList};
+use common::history::ServerHistory;
+use common::status::{Capability, HealthStatus, ServerInfo, ServerStatus};
 use moka::future::{Cache, CacheBuilder};
+use oauth2::basic::BasicTokenResponse;
 use oauth2::TokenResponse;
 use reqwest::Client;
+use smol_str::SmolStr;
+use sqlx::SqlitePool;
 use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::watch;
 
 mod config;
+mod console;
 mod factorio;
 mod generic;
+mod history;
+mod minecraft;
+mod notifications;
+mod permissions;
+mod poller;
+mod probe;
+mod source_query;
 
-const GUILD_ID: u64 = 808535850030727198;
+pub(crate) use history::unix_now;
+pub(crate) use config::ServerConfig;
+pub(crate) use factorio::Upload;
+pub(crate) use permissions::Principal;
+
+const UNKNOWN_TEXT: SmolStr = SmolStr::new_static("unknown");
 
 #[derive(Clone)]
 pub struct ServerManager {
     client: Client,
     config_store: Arc<ConfigStore>,
-    statuses: Cache<GameConfig, ServerStatus>,
+    poller: Arc<StatusPoller>,
+    history: Arc<HistoryRecorder>,
     user_roles: Cache<UserId, HashSet<RoleId>>,
+    api_tokens: ApiTokens,
+    console_log: Arc<ConsoleLog>,
+    audit: AuditLog,
 }
 
 impl ServerManager {
-    pub fn new(client: Client, config_path: PathBuf) -> AppResult<Self> {
+    pub fn new(
+        client: Client,
+        config_path: PathBuf,
+        server_dir: Option<PathBuf>,
+        pool: SqlitePool,
+        api_tokens: ApiTokens,
+        audit: AuditLog,
+    ) -> AppResult<Self> {
+        let config_store = Arc::new(ConfigStore::new(config_path, server_dir, audit.clone())?);
+        let poller = StatusPoller::new(config_store.clone(), audit.clone());
+        let history = HistoryRecorder::new(pool.clone(), poller.subscribe()).into();
+
         Ok(Self {
             client,
-            config_store: ConfigStore::new(config_path)?.into(),
-            statuses: CacheBuilder::new(10)
-                .time_to_live(Duration::from_secs(5))
-                .build(),
+            config_store,
+            poller: poller.into(),
+            history,
             user_roles: CacheBuilder::new(20)
                 .time_to_live(Duration::from_secs(10))
                 .build(),
+            api_tokens,
+            console_log: ConsoleLog::new(pool).into(),
+            audit,
         })
     }
 
-    pub async fn get_servers_for_user(&self, user: &User) -> Result<Vec<ServerStatus>, AppError> {
-        let roles = self
+    pub async fn get_servers_for_user(&self, user: &User) -> Result<Vec<ServerInfo>, AppError> {
+        let principal = self.get_principal(user).await;
+
+        Ok(self.get_servers(&principal).await)
+    }
+
+    pub async fn get_principal(&self, user: &User) -> Principal {
+        let (discord_user, tokens) = match &**user {
+            UserData::Discord { user, tokens, .. } => (user, tokens),
+            UserData::Oidc(user) => return Principal::oidc(user),
+            UserData::ApiToken(token) => return token.principal.clone(),
+        };
+
+        // Failures aren't cached, so the next request tries again
+        let fetch_roles = async {
+            let roles = self.fetch_user_roles(discord_user, tokens).await?;
+            if roles.is_empty() && !self.config_store.guilds().await.is_empty() {
+                // They left or were removed from every guild, so their tokens mustn't outlive that
+                self.revoke_tokens(user, &discord_user.username).await;
+            } else {
+                let principal = Principal::discord(discord_user.id, roles.clone());
+                if let Err(err) = self
+                    .api_tokens
+                    .update_principal(&user.owner_id(), &principal)
+                    .await
+                {
+                    tracing::error!("Failed to update API tokens of {}: {:#}", discord_user.username, err);
+                }
+            }
+            anyhow::Ok(roles)
+        };
+        let roles = match self
             .user_roles
-            .get_with(user.discord_user.id, async move {
-                self.fetch_user_roles(user).await.unwrap_or_else(|err| {
-                    tracing::error!("Failed to fetch user roles {}", err);
-                    HashSet::with_capacity(0)
+            .try_get_with(discord_user.id, fetch_roles)
+            .await
+        {
+            Ok(roles) => roles,
+            Err(err) if err.is::<TokenRejected>() => {
+                tracing::info!(
+                    "Discord rejected the token of {}, expiring session",
+                    discord_user.username
+                );
+                user.expire_session().await;
+                HashSet::with_capacity(0)
+            }
+            Err(err) => {
+                tracing::error!("Failed to fetch user roles {}", err);
+                HashSet::with_capacity(0)
+            }
+        };
+
+        Principal::discord(discord_user.id, roles)
+    }
+
+    async fn revoke_tokens(&self, user: &User, username: &str) {
+        match self.api_tokens.revoke_all(&user.owner_id()).await {
+            Ok(0) => {}
+            Ok(count) => {
+                tracing::info!("Revoked {} API tokens of {}, who is in none of the guilds", count, username);
+                let detail = format!("revoked {count} tokens after leaving every guild");
+                self.audit
+                    .record(Some(&Actor::from(&**user)), AuditAction::TokenRevoke, None, AuditOutcome::Ok, &detail)
+                    .await;
+            }
+            Err(err) => tracing::error!("Failed to revoke API tokens of {}: {:#}", username, err),
+        }
+    }
+
+    pub async fn get_servers(&self, principal: &Principal) -> Vec<ServerInfo> {
+        let configs = self.config_store.configs().await;
+        let statuses = self.poller.statuses();
+
+        let servers = configs
+            .iter()
+            .filter_map(|c| {
+                let capabilities = c.capabilities(principal);
+                if !capabilities.contains(&Capability::View) {
+                    return None;
+                }
+                let mut status = statuses.get(c.id())?.clone();
+                if !capabilities.contains(&Capability::ViewSecrets) {
+                    status.redact_secrets();
+                }
+                Some(ServerInfo {
+                    id: c.id().clone(),
+                    status,
+                    capabilities,
                 })
             })
+            .collect::<Vec<_>>();
+
+        tracing::trace!("Current server statuses: {:?}", servers);
+
+        servers
+    }
+
+    /// Finds the config of the server with the given ID, if it's visible to `principal`
+    pub async fn get_server_config(&self, id: &str, principal: &Principal) -> Option<ServerConfig> {
+        self.config_store
+            .configs()
+            .await
+            .iter()
+            .find(|c| c.id() == id && c.can_view(principal))
+            .cloned()
+    }
+
+    pub async fn get_history(&self, id: &str, from: i64, to: i64) -> AppResult<ServerHistory> {
+        Ok(self.history.history(id, from, to).await?)
+    }
+
+    /// Runs a console command if the server has a console and `principal`'s allowlist permits
+    /// it. Every attempt is recorded, including denied ones. Returns `None` if the server has no
+    /// console.
+    pub async fn run_console_command(
+        &self,
+        config: &ServerConfig,
+        principal: &Principal,
+        user: &User,
+        command: &str,
+    ) -> AppResult<Option<ConsoleEntry>> {
+        let GameConfig::Factorio(factorio) = &config.game else {
+            return Ok(None);
+        };
+        // Checked and sent as the same string, so whitespace can't slip past a rule
+        let command = command.trim();
+
+        let (outcome, response) = if !console::is_allowed(&config.console, principal, command) {
+            (ConsoleOutcome::Denied, "Command not allowed".to_owned())
+        } else {
+            match factorio.run_command(command).await {
+                Ok(response) => (ConsoleOutcome::Ok, response),
+                Err(err) => {
+                    METRICS.rcon_error("factorio");
+                    (ConsoleOutcome::Error, format!("{:#}", err))
+                }
+            }
+        };
+        tracing::info!(
+            "{} ran console command {:?} on {}: {:?}",
+            user.username(),
+            command,
+            config.name,
+            outcome
+        );
+
+        self.audit
+            .record(
+                Some(&Actor::from(&**user)),
+                AuditAction::ConsoleCommand,
+                Some(config.id()),
+                outcome.into(),
+                command,
+            )
             .await;
 
-        Ok(self.get_servers(roles).await)
+        let entry = self
+            .console_log
+            .record(
+                config.id(),
+                &user.owner_id(),
+                user.username(),
+                command,
+                outcome,
+                &response,
+            )
+            .await?;
+        Ok(Some(entry))
     }
 
-    async fn get_servers(&self, roles: HashSet<RoleId>) -> Vec<ServerStatus> {
-        let configs = self.config_store.configs().await;
+    pub async fn get_console_log(&self, id: &str) -> AppResult<Vec<ConsoleEntry>> {
+        Ok(self.console_log.recent(id).await?)
+    }
 
-        let futures = configs.iter().filter_map(|c| {
-            c.required_role.filter(|r| roles.contains(r)).map(|_| {
-                self.statuses
-                    .get_with_by_ref(&c.game, self.fetch_server_status(c))
+    /// Returns `None` if the server doesn't keep saves we can see
+    pub async fn list_saves(&self, config: &ServerConfig) -> AppResult<Option<SaveList>> {
+        match &config.game {
+            GameConfig::Factorio(factorio) => factorio.list_saves().await,
+            _ => Ok(None),
+        }
+    }
+
+    /// Path of a save or backup, see [FactorioConfig::save_path](factorio::FactorioConfig::save_path)
+    pub fn save_path(&self, config: &ServerConfig, name: &str, backup: bool) -> Option<PathBuf> {
+        match &config.game {
+            GameConfig::Factorio(factorio) => factorio.save_path(name, backup),
+            _ => None,
+        }
+    }
+
+    /// Makes the server save now. Returns `None` if it can't be told to.
+    pub async fn save_game(&self, config: &ServerConfig) -> AppResult<Option<String>> {
+        match &config.game {
+            GameConfig::Factorio(factorio) => Ok(Some(factorio.save().await?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns `None` if the server has no mods we can manage
+    pub async fn list_mods(&self, config: &ServerConfig) -> AppResult<Option<ModOverview>> {
+        let GameConfig::Factorio(factorio) = &config.game else {
+            return Ok(None);
+        };
+        // Offline servers and those without detailed status don't report their mods
+        let running = match self.poller.statuses().get(config.id()) {
+            Some(ServerStatus::Factorio(status))
+                if status.health == HealthStatus::Running && !status.mods.is_empty() =>
+            {
+                Some(status.mods.clone())
+            }
+            _ => None,
+        };
+        factorio.list_mods(running.as_deref()).await
+    }
+
+    /// Returns `false` if the server has no such mod
+    pub async fn set_mod_enabled(&self, config: &ServerConfig, name: &str, enabled: bool) -> AppResult<bool> {
+        match &config.game {
+            GameConfig::Factorio(factorio) => factorio.set_mod_enabled(name, enabled).await,
+            _ => Ok(false),
+        }
+    }
+
+    /// Returns `None` if the server doesn't take mods or the file name isn't a mod's
+    pub async fn upload_mod<B, E>(
+        &self,
+        config: &ServerConfig,
+        file_name: &str,
+        body: impl Stream<Item = Result<B, E>> + Unpin,
+    ) -> AppResult<Option<Upload>>
+    where
+        B: AsRef<[u8]>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        match &config.game {
+            GameConfig::Factorio(factorio) => factorio.upload_mod(file_name, body).await,
+            _ => Ok(None),
+        }
+    }
+
+    /// Looks a visible server up by ID or name, ignoring case
+    pub async fn find_server_config(
+        &self,
+        query: &str,
+        principal: &Principal,
+    ) -> Option<ServerConfig> {
+        self.config_store
+            .configs()
+            .await
+            .iter()
+            .find(|c| {
+                (c.id().eq_ignore_ascii_case(query) || c.name.eq_ignore_ascii_case(query))
+                    && c.can_view(principal)
             })
-        });
+            .cloned()
+    }
 
-        let servers = futures::future::join_all(futures).await;
+    pub async fn guilds(&self) -> Vec<GuildId> {
+        self.config_store.guilds().await
+    }
 
-        tracing::debug!("Fetched server statuses: {:?}", servers);
+    /// Whether `principal` is listed in `admins`. API tokens are never admins.
+    pub async fn is_admin(&self, principal: &Principal) -> bool {
+        principal.scope.is_none() && self.config_store.is_admin(principal).await
+    }
 
-        servers
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
     }
 
-    async fn fetch_server_status(&self, config: &ServerConfig) -> ServerStatus {
-        tracing::debug!("Updating server status: {:?}", config);
-        let mut status = config.game.fetch_server_status().await;
-        match &mut status {
-            ServerStatus::Factorio(status) => {
-                status.name = config.name.clone();
-                status.url = config.public_dns.clone();
-            }
-            ServerStatus::Generic(status) => {
-                status.name = config.name.clone();
-                status.url = config.public_dns.clone();
+    /// Returns a receiver that is notified whenever any server status changes
+    pub fn subscribe(&self) -> watch::Receiver<StatusSnapshot> {
+        self.poller.subscribe()
+    }
+
+    /// Gathers the user's roles across every configured guild, including each guild's
+    /// @everyone role. Guilds that fail to answer are skipped, unless Discord rejected the token
+    /// or no guild answered with roles.
+    async fn fetch_user_roles(
+        &self,
+        user: &DiscordUserData,
+        tokens: &BasicTokenResponse,
+    ) -> anyhow::Result<HashSet<RoleId>> {
+        tracing::debug!("Updating user roles for {}", user.username);
+        let guilds = self.config_store.guilds().await;
+        let memberships =
+            future::join_all(guilds.iter().map(|&guild| self.fetch_guild_roles(tokens, guild)))
+                .await;
+
+        let mut roles = HashSet::new();
+        let mut failed = false;
+        for (guild, membership) in guilds.into_iter().zip(memberships) {
+            match membership {
+                Ok(Some(guild_roles)) => {
+                    roles.insert(RoleId::from(guild.get()));
+                    roles.extend(guild_roles);
+                }
+                Ok(None) => {}
+                Err(err) if err.is::<TokenRejected>() => return Err(err),
+                Err(err) => {
+                    tracing::error!("Failed to fetch roles in guild {}: {:#}", guild.get(), err);
+                    failed = true;
+                }
             }
         }
-        status
+
+        // No roles only means no membership if every guild answered
+        if roles.is_empty() && failed {
+            anyhow::bail!("no guild returned roles for {}", user.username);
+        }
+        Ok(roles)
     }
 
-    async fn fetch_user_roles(&self, user: &User) -> AppResult<HashSet<RoleId>> {
-        tracing::debug!("Updating user roles for {}", user.discord_user.username);
+    /// Returns `None` if the user isn't a member of the guild
+    async fn fetch_guild_roles(
+        &self,
+        tokens: &BasicTokenResponse,
+        guild: GuildId,
+    ) -> anyhow::Result<Option<Vec<RoleId>>> {
+        METRICS.discord_api_call("guild_member");
         let resp = self
             .client
             // https://discord.com/developers/docs/resources/user#get-current-user-guild-member
             .get(format!(
-                "https://discordapp.com/api/users/@me/guilds/{GUILD_ID}/member"
+                "https://discordapp.com/api/users/@me/guilds/{}/member",
+                guild.get()
             ))
-            .bearer_auth(user.tokens.access_token().secret())
+            .bearer_auth(tokens.access_token().secret())
             .send()
             .await
             .context("failed in sending request to target Url")?;
 
+        match resp.status() {
+            StatusCode::NOT_FOUND => return Ok(None),
+            StatusCode::UNAUTHORIZED => return Err(TokenRejected.into()),
+            _ => {}
+        }
+
         let body = resp.text().await?;
         tracing::trace!("Discord response: {}", body);
 
         let guild_member = serde_json::from_str::<GuildMember>(&body)
             .context("failed to deserialize response as JSON")?;
 
-        let roles = guild_member.roles.into_iter().collect();
-
-        Ok(roles)
+        Ok(Some(guild_member.roles))
     }
 }
 
@@ -123,5 +442,8 @@ impl FromRef<AppState> for ServerManager {
 trait StatusFetcher {
     type Status: Into<ServerStatus>;
 
+    /// Status to report before the first fetch completes or when a fetch times out
+    fn unknown_status(&self) -> Self::Status;
+
     async fn fetch_server_status(&self) -> Self::Status;
 }
@@ -1,38 +1,271 @@
+use crate::audit::AuditLog;
+use crate::control::ControlConfig;
+use crate::servers::console::ConsoleRule;
 use crate::servers::factorio::FactorioConfig;
 use crate::servers::StatusFetcher;
 use crate::AppResult;
-use common::discord::RoleId;
-use common::status::ServerStatus;
+use anyhow::{bail, Context};
+use common::audit::{AuditAction, AuditOutcome};
+use common::discord::{GuildId, RoleId};
+use common::status::{Capability, ServerStatus};
 use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::Deserialize;
 use smol_str::SmolStr;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
-use std::fs::File;
-use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::error::TrySendError;
-use tokio::sync::{RwLock, RwLockReadGuard};
+use tokio::sync::{watch, RwLock, RwLockReadGuard};
 use tokio::task::AbortHandle;
 use crate::servers::generic::GenericConfig;
+use crate::servers::minecraft::MinecraftConfig;
+use crate::servers::notifications::WebhookConfig;
+use crate::servers::permissions::{PermissionEntry, Principal, Subject};
+use crate::servers::source_query::SourceQueryConfig;
+
+mod sources;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ServerConfig {
+    /// Identifier used in API paths. Defaults to `name`
+    #[serde(default)]
+    id: Option<SmolStr>,
     pub(crate) name: SmolStr,
     pub(crate) game: GameConfig,
     pub(crate) public_dns: SmolStr,
+    /// Deprecated, use `permissions`. Grants `view` and `view_secrets`.
     pub(crate) required_role: Option<RoleId>,
+    /// Deprecated, use `permissions`. Grants `view` and `view_secrets`.
+    #[serde(default)]
+    pub(crate) required_roles: Vec<RoleId>,
+    /// Who may do what with the server. Nobody can see a server without any entries.
+    #[serde(default)]
+    pub(crate) permissions: Vec<PermissionEntry>,
+    #[serde(default)]
+    pub(crate) poll: PollConfig,
+    #[serde(default)]
+    pub(crate) control: Option<ControlConfig>,
+    /// Stops the server once nobody has played on it for a while. Requires `control`.
+    #[serde(default)]
+    pub(crate) idle: Option<IdlePolicy>,
+    /// Discord webhooks to announce state changes and player joins on
+    #[serde(default)]
+    pub(crate) notifications: Vec<WebhookConfig>,
+    /// Console commands users with the `console` capability may run. Factorio only.
+    #[serde(default)]
+    pub(crate) console: Vec<ConsoleRule>,
+}
+
+impl ServerConfig {
+    pub fn id(&self) -> &SmolStr {
+        self.id.as_ref().unwrap_or(&self.name)
+    }
+
+    /// Everything `principal` may do with this server. Any capability implies `view`.
+    pub fn capabilities(&self, principal: &Principal) -> HashSet<Capability> {
+        let mut capabilities = self
+            .permissions
+            .iter()
+            .filter(|entry| entry.subject.matches(principal))
+            .flat_map(|entry| entry.capabilities.iter().copied())
+            .collect::<HashSet<_>>();
+
+        if self
+            .required_role
+            .iter()
+            .chain(&self.required_roles)
+            .any(|r| principal.roles.contains(r))
+        {
+            capabilities.extend([Capability::View, Capability::ViewSecrets]);
+        }
+        if let Some(ControlConfig { role: Some(role), .. }) = &self.control
+            && principal.roles.contains(role)
+        {
+            capabilities.insert(Capability::Control);
+        }
+
+        if capabilities.is_empty() {
+            return capabilities;
+        }
+        capabilities.insert(Capability::View);
+
+        // Anyone who can see an idle-managed server may wake it up again
+        if self.idle.is_some() {
+            capabilities.insert(Capability::Wake);
+        }
+        // Nothing to control without a backend
+        if self.control.is_none() {
+            capabilities.remove(&Capability::Control);
+            capabilities.remove(&Capability::Wake);
+        }
+        if !matches!(&self.game, GameConfig::Factorio(f) if f.saves_dir.is_some()) {
+            capabilities.remove(&Capability::Saves);
+        }
+        if !matches!(&self.game, GameConfig::Factorio(f) if f.mods_dir.is_some()) {
+            capabilities.remove(&Capability::Mods);
+        }
+        if let Some(scope) = &principal.scope {
+            capabilities.retain(|capability| scope.contains(capability));
+        }
+
+        capabilities
+    }
+
+    pub fn can_view(&self, principal: &Principal) -> bool {
+        self.capabilities(principal).contains(&Capability::View)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlePolicy {
+    /// Minutes the server may run with no players online before it is stopped
+    pub(crate) stop_after_mins: u64,
+}
+
+impl IdlePolicy {
+    pub fn stop_after(&self) -> Duration {
+        Duration::from_secs(self.stop_after_mins.saturating_mul(60))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PollConfig {
+    /// Seconds between status refreshes while the server is healthy
+    #[serde(default = "PollConfig::default_interval_secs")]
+    pub(crate) interval_secs: u64,
+    /// Seconds to wait for a single status fetch before giving up
+    #[serde(default = "PollConfig::default_timeout_secs")]
+    pub(crate) timeout_secs: u64,
+    /// Upper bound for the refresh interval while backing off from an unhealthy server
+    #[serde(default = "PollConfig::default_max_backoff_secs")]
+    pub(crate) max_backoff_secs: u64,
+}
+
+impl PollConfig {
+    fn default_interval_secs() -> u64 {
+        10
+    }
+
+    fn default_timeout_secs() -> u64 {
+        10
+    }
+
+    fn default_max_backoff_secs() -> u64 {
+        120
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    /// Delay before the next refresh after `failures` unhealthy fetches in a row
+    pub fn next_delay(&self, failures: u32) -> Duration {
+        let backoff = self
+            .interval_secs
+            .saturating_mul(1 << failures.min(16))
+            .min(self.max_backoff_secs.max(self.interval_secs));
+        Duration::from_secs(backoff)
+    }
+}
+
+impl Default for PollConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: Self::default_interval_secs(),
+            timeout_secs: Self::default_timeout_secs(),
+            max_backoff_secs: Self::default_max_backoff_secs(),
+        }
+    }
+}
+
+/// Guild every server belonged to before guilds were configurable
+const LEGACY_GUILD_ID: u64 = 808535850030727198;
+
+/// Contents of the config file
+#[derive(Debug, Default, Deserialize)]
+pub struct AppConfig {
+    /// Guilds whose roles grant access to servers
+    pub(crate) guilds: Vec<GuildId>,
+    /// Servers from the server directory are added to these
+    #[serde(default)]
+    pub(crate) servers: Vec<ServerConfig>,
+    /// Roles, groups and users that may view the audit log
+    #[serde(default)]
+    pub(crate) admins: Vec<Subject>,
+}
+
+impl AppConfig {
+    /// Reads the config file, plus the servers in `server_dir` if given. Server ids must be
+    /// unique across all files.
+    fn load(config_path: &Path, server_dir: Option<&Path>) -> anyhow::Result<Self> {
+        let mut config = Self::from_value(sources::read_file(config_path)?)
+            .with_context(|| format!("invalid config in {}", config_path.display()))?;
+
+        let mut defined_in = HashMap::new();
+        for server in &config.servers {
+            check_unique_id(&mut defined_in, server, config_path)?;
+        }
+        if let Some(dir) = server_dir {
+            for (path, server) in sources::read_server_dir(dir)? {
+                let server = serde_json::from_value(server)
+                    .with_context(|| format!("invalid server in {}", path.display()))?;
+                check_unique_id(&mut defined_in, &server, &path)?;
+                config.servers.push(server);
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn from_value(value: serde_json::Value) -> serde_json::Result<Self> {
+        if value.is_array() {
+            tracing::warn!(
+                "Config file is a bare list of servers, which is deprecated. \
+                 Move it to `servers` and list your guilds in `guilds`"
+            );
+            return Ok(Self {
+                guilds: vec![GuildId::from(LEGACY_GUILD_ID)],
+                servers: serde_json::from_value(value)?,
+                admins: Vec::new(),
+            });
+        }
+
+        serde_json::from_value(value)
+    }
+}
+
+/// Remembers that `server` is defined in `path` and fails if its id was already taken
+fn check_unique_id(
+    defined_in: &mut HashMap<SmolStr, PathBuf>,
+    server: &ServerConfig,
+    path: &Path,
+) -> anyhow::Result<()> {
+    match defined_in.insert(server.id().clone(), path.to_owned()) {
+        Some(first) => bail!(
+            "server id {} in {} is already used in {}",
+            server.id(),
+            path.display(),
+            first.display()
+        ),
+        None => Ok(()),
+    }
 }
 
 pub(super) struct ConfigStore {
-    configs: Arc<RwLock<Vec<ServerConfig>>>,
+    config: Arc<RwLock<AppConfig>>,
+    updates: watch::Sender<()>,
     load_task: AbortHandle,
     _watcher: RecommendedWatcher,
 }
 
 impl ConfigStore {
-    pub fn new(config_path: PathBuf) -> AppResult<Self> {
-        let configs = Arc::new(RwLock::new(Vec::new()));
+    /// Loads `config_path` and every config file in `server_dir`, and reloads them whenever
+    /// one changes
+    pub fn new(config_path: PathBuf, server_dir: Option<PathBuf>, audit: AuditLog) -> AppResult<Self> {
+        let config = Arc::new(RwLock::new(AppConfig::default()));
+        let updates = watch::Sender::new(());
 
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
 
@@ -46,23 +279,30 @@ impl ConfigStore {
             notify::Config::default(),
         )?;
         watcher.watch(&config_path, RecursiveMode::NonRecursive)?;
+        if let Some(dir) = &server_dir {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
 
         let handle = {
-            let configs = configs.clone();
+            let config = config.clone();
+            let updates = updates.clone();
             tokio::spawn(async move {
-                Self::load_config_file(&config_path, configs.clone()).await;
+                Self::load_config_file(&config_path, server_dir.as_deref(), config.clone(), &updates, &audit).await;
 
                 tracing::debug!("Initial config loaded. Waiting for file changes...");
                 while let Some(res) = rx.recv().await {
                     tracing::debug!("Event received {:?}", res);
                     match res {
                         Ok(event) => {
-                            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                            // Editors replace the config file, so only removing a server file counts
+                            let server_removed = matches!(event.kind, EventKind::Remove(_))
+                                && event.paths.iter().any(|path| path.parent() == server_dir.as_deref());
+                            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) && !server_removed {
                                 // File contents didn't change, ignore this event
                                 continue;
                             }
 
-                            Self::load_config_file(&config_path, configs.clone()).await
+                            Self::load_config_file(&config_path, server_dir.as_deref(), config.clone(), &updates, &audit).await
                         }
                         Err(e) => {
                             tracing::warn!("watch error: {:?}", e);
@@ -75,29 +315,75 @@ impl ConfigStore {
         };
 
         Ok(Self {
-            configs,
+            config,
+            updates,
             load_task: handle.abort_handle(),
             _watcher: watcher,
         })
     }
 
     pub async fn configs(&self) -> RwLockReadGuard<'_, Vec<ServerConfig>> {
-        self.configs.read().await
+        RwLockReadGuard::map(self.config.read().await, |config| &config.servers)
+    }
+
+    pub async fn guilds(&self) -> Vec<GuildId> {
+        self.config.read().await.guilds.clone()
     }
 
-    async fn load_config_file(config_path: &Path, configs: Arc<RwLock<Vec<ServerConfig>>>) {
-        // TODO figure out a nice way to use tokio's File
-        let config_file = File::open(config_path).expect("failed to open server config file");
-        let reader = BufReader::new(config_file);
+    pub async fn is_admin(&self, principal: &Principal) -> bool {
+        self.config
+            .read()
+            .await
+            .admins
+            .iter()
+            .any(|admin| admin.matches(principal))
+    }
+
+    /// Returns a receiver that is notified every time a new config is loaded
+    pub fn subscribe(&self) -> watch::Receiver<()> {
+        self.updates.subscribe()
+    }
 
-        match serde_json::from_reader(reader) {
-            Ok(new_configs) => {
+    async fn load_config_file(
+        config_path: &Path,
+        server_dir: Option<&Path>,
+        config: Arc<RwLock<AppConfig>>,
+        updates: &watch::Sender<()>,
+        audit: &AuditLog,
+    ) {
+        match AppConfig::load(config_path, server_dir) {
+            Ok(new_config) => {
                 tracing::info!("Loaded new servers config");
-                tracing::debug!("{:?}", new_configs);
-                *configs.write().await = new_configs;
+                tracing::debug!("{:?}", new_config);
+                if new_config.guilds.is_empty() {
+                    tracing::warn!("No guilds configured, Discord roles won't grant access to any servers");
+                }
+                for server in &new_config.servers {
+                    if server.idle.is_some() && server.control.is_none() {
+                        tracing::warn!("{} has an idle policy but no control, it won't be stopped", server.name);
+                    }
+                    if !server.console.is_empty() && !matches!(server.game, GameConfig::Factorio(_)) {
+                        tracing::warn!("{} has console rules, but only Factorio servers have a console", server.name);
+                    }
+                    if let GameConfig::Factorio(factorio) = &server.game
+                        && factorio.backups.is_some()
+                        && factorio.saves_dir.is_none()
+                    {
+                        tracing::warn!("{} has a backup policy but no saves_dir, nothing will be backed up", server.name);
+                    }
+                }
+                let detail = format!("Loaded {} servers", new_config.servers.len());
+                *config.write().await = new_config;
+                updates.send_replace(());
+                audit
+                    .record(None, AuditAction::ConfigChange, None, AuditOutcome::Ok, &detail)
+                    .await;
             }
             Err(e) => {
-                tracing::warn!("Error parsing config file: {}", e);
+                tracing::warn!("Error loading config: {:#}", e);
+                audit
+                    .record(None, AuditAction::ConfigChange, None, AuditOutcome::Error, &format!("{e:#}"))
+                    .await;
             }
         }
     }
@@ -114,7 +400,9 @@ impl Drop for ConfigStore {
 #[serde(tag = "type")]
 pub enum GameConfig {
     Factorio(FactorioConfig),
-    Generic(GenericConfig)
+    Generic(GenericConfig),
+    Minecraft(MinecraftConfig),
+    SourceQuery(SourceQueryConfig),
 }
 
 impl Display for GameConfig {
@@ -122,6 +410,8 @@ impl Display for GameConfig {
         match self {
             GameConfig::Factorio(_) => write!(f, "Factorio"),
             GameConfig::Generic(c) => write!(f, "{}", c.game_name),
+            GameConfig::Minecraft(_) => write!(f, "Minecraft"),
+            GameConfig::SourceQuery(_) => write!(f, "Source Query"),
         }
     }
 }
@@ -129,10 +419,204 @@ impl Display for GameConfig {
 impl StatusFetcher for GameConfig {
     type Status = ServerStatus;
 
+    fn unknown_status(&self) -> ServerStatus {
+        match self {
+            GameConfig::Factorio(config) => config.unknown_status().into(),
+            GameConfig::Generic(config) => config.unknown_status().into(),
+            GameConfig::Minecraft(config) => config.unknown_status().into(),
+            GameConfig::SourceQuery(config) => config.unknown_status().into(),
+        }
+    }
+
     async fn fetch_server_status(&self) -> ServerStatus {
         match self {
             GameConfig::Factorio(config) => config.fetch_server_status().await.into(),
             GameConfig::Generic(config) => config.fetch_server_status().await.into(),
+            GameConfig::Minecraft(config) => config.fetch_server_status().await.into(),
+            GameConfig::SourceQuery(config) => config.fetch_server_status().await.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use serde_json::json;
+
+    fn server(name: &str, id: Option<&str>) -> serde_json::Value {
+        json!({
+            "id": id,
+            "name": name,
+            "public_dns": "games.example.com",
+            "game": { "type": "Generic", "game_name": "Terraria", "game_password": "" },
+        })
+    }
+
+    /// A server with `fields` set on top of the basics
+    fn config(fields: serde_json::Value) -> ServerConfig {
+        let mut server = server("mc", None);
+        server.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+        serde_json::from_value(server).unwrap()
+    }
+
+    fn write_config(dir: &TempDir, servers: &[serde_json::Value]) -> PathBuf {
+        let config = json!({ "guilds": [], "servers": servers });
+        dir.write("config.json", config.to_string())
+    }
+
+    #[test]
+    fn adds_servers_from_the_server_dir() {
+        let dir = TempDir::new();
+        let config_path = write_config(&dir, &[server("main", None)]);
+        std::fs::create_dir(dir.path().join("servers")).unwrap();
+        dir.write("servers/extra.json", server("extra", None).to_string());
+
+        let config = AppConfig::load(&config_path, Some(&dir.path().join("servers"))).unwrap();
+        let ids = config.servers.iter().map(ServerConfig::id).collect::<Vec<_>>();
+        assert_eq!(ids, ["main", "extra"]);
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() {
+        let dir = TempDir::new();
+        let servers_dir = dir.path().join("servers");
+        std::fs::create_dir(&servers_dir).unwrap();
+
+        let config_path = write_config(&dir, &[server("a", None), server("a", None)]);
+        let err = AppConfig::load(&config_path, None).unwrap_err();
+        assert!(err.to_string().contains("server id a in"), "{err:#}");
+
+        // An explicit id clashes with another server's name
+        let config_path = write_config(&dir, &[server("a", Some("b"))]);
+        dir.write("servers/b.json", server("b", None).to_string());
+        let err = AppConfig::load(&config_path, Some(&servers_dir)).unwrap_err();
+        assert!(err.to_string().contains("b.json is already used in"), "{err:#}");
+
+        dir.write("servers/b.json", server("b", Some("c")).to_string());
+        assert!(AppConfig::load(&config_path, Some(&servers_dir)).is_ok());
+    }
+
+    #[test]
+    fn grants_capabilities() {
+        use Capability::*;
+
+        let factorio = |dirs: serde_json::Value| {
+            let mut game = json!({
+                "type": "Factorio",
+                "rcon_host": "localhost:27015",
+                "rcon_password": "",
+                "game_password": "",
+            });
+            game.as_object_mut().unwrap().extend(dirs.as_object().unwrap().clone());
+            game
+        };
+        let control = json!({ "type": "Systemd", "unit": "mc.service" });
+        let everything = json!([{
+            "group": "ops",
+            "capabilities": ["view", "console", "control", "wake", "saves", "mods"],
+        }]);
+
+        let cases: Vec<(&str, serde_json::Value, &[Capability])> = vec![
+            ("no permissions", json!({}), &[]),
+            ("other role", json!({ "required_role": "2" }), &[]),
+            ("legacy role", json!({ "required_role": "1" }), &[View, ViewSecrets]),
+            ("legacy roles", json!({ "required_roles": ["2", "1"] }), &[View, ViewSecrets]),
+            (
+                "legacy control role",
+                json!({ "control": { "type": "Systemd", "unit": "mc.service", "role": "1" } }),
+                &[View, Control],
+            ),
+            (
+                "by user",
+                json!({ "permissions": [{ "user": "7", "capabilities": ["console"] }] }),
+                &[View, Console],
+            ),
+            (
+                "by role",
+                json!({ "permissions": [{ "role": "1", "capabilities": ["view_secrets"] }] }),
+                &[View, ViewSecrets],
+            ),
+            (
+                "other group",
+                json!({ "permissions": [{ "group": "guests", "capabilities": ["view"] }] }),
+                &[],
+            ),
+            (
+                "idle implies wake",
+                json!({
+                    "permissions": [{ "role": "1", "capabilities": ["view"] }],
+                    "control": control,
+                    "idle": { "stop_after_mins": 30 },
+                }),
+                &[View, Wake],
+            ),
+            (
+                "idle without backend",
+                json!({
+                    "permissions": [{ "role": "1", "capabilities": ["view"] }],
+                    "idle": { "stop_after_mins": 30 },
+                }),
+                &[View],
+            ),
+            ("without backend or dirs", json!({ "permissions": everything }), &[View, Console]),
+            (
+                "with backend",
+                json!({ "permissions": everything, "control": control }),
+                &[View, Console, Control, Wake],
+            ),
+            (
+                "factorio without dirs",
+                json!({ "permissions": everything, "game": factorio(json!({})) }),
+                &[View, Console],
+            ),
+            (
+                "factorio with saves",
+                json!({ "permissions": everything, "game": factorio(json!({ "saves_dir": "/" })) }),
+                &[View, Console, Saves],
+            ),
+            (
+                "factorio with mods",
+                json!({ "permissions": everything, "game": factorio(json!({ "mods_dir": "/m" })) }),
+                &[View, Console, Mods],
+            ),
+        ];
+
+        let principal = Principal {
+            user: Some(7.into()),
+            roles: HashSet::from([1.into()]),
+            groups: HashSet::from(["ops".into()]),
+            scope: None,
+        };
+        for (name, fields, expected) in cases {
+            let capabilities = config(fields).capabilities(&principal);
+            assert_eq!(capabilities, expected.iter().copied().collect(), "{name}");
+        }
+    }
+
+    #[test]
+    fn token_scope_limits_capabilities() {
+        use Capability::*;
+
+        let server = config(json!({
+            "permissions": [{ "role": "1", "capabilities": ["view_secrets", "console"] }],
+        }));
+        let scoped = |scope: &[Capability]| Principal {
+            scope: Some(scope.iter().copied().collect()),
+            ..Principal::discord(7.into(), HashSet::from([1.into()]))
+        };
+
+        let cases: [(&[Capability], &[Capability]); 4] = [
+            (&[View, Console], &[View, Console]),
+            (&[View, Control], &[View]),
+            // Without `view` the token can't even see the server
+            (&[Console], &[Console]),
+            (&[], &[]),
+        ];
+        for (scope, expected) in cases {
+            let capabilities = server.capabilities(&scoped(scope));
+            assert_eq!(capabilities, expected.iter().copied().collect(), "{scope:?}");
         }
+        assert!(!server.can_view(&scoped(&[Console])));
     }
 }
@@ -0,0 +1,150 @@
+use anyhow::{anyhow, bail, Context};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Formats config files may be written in, chosen by extension
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a config file into a JSON value, whatever its format. Files without a known
+/// extension are read as JSON, like before other formats were supported.
+pub(super) fn read_file(path: &Path) -> anyhow::Result<Value> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let value = match Format::of(path).unwrap_or(Format::Json) {
+        Format::Json => serde_json::from_str(&contents).map_err(anyhow::Error::from),
+        Format::Toml => toml::from_str(&contents).map_err(anyhow::Error::from),
+        Format::Yaml => serde_yaml_ng::from_str(&contents).map_err(anyhow::Error::from),
+    };
+    value.with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Servers from every config file in `dir`, in file name order. A file holds a single server,
+/// a list of them, or a `servers` list.
+pub(super) fn read_server_dir(dir: &Path) -> anyhow::Result<Vec<(PathBuf, Value)>> {
+    let mut paths = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+
+    let mut servers = Vec::new();
+    for path in paths {
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| name.starts_with('.'));
+        // Skips editor backups and the like
+        if hidden || !path.is_file() || Format::of(&path).is_none() {
+            continue;
+        }
+
+        let value = match read_file(&path)? {
+            Value::Object(mut object) if !object.contains_key("name") => object
+                .remove("servers")
+                .ok_or_else(|| anyhow!("{} has neither a server nor `servers`", path.display()))?,
+            value => value,
+        };
+        match value {
+            Value::Array(list) => servers.extend(list.into_iter().map(|server| (path.clone(), server))),
+            Value::Object(_) => servers.push((path.clone(), value)),
+            _ => bail!("{} doesn't contain servers", path.display()),
+        }
+    }
+
+    Ok(servers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use serde_json::json;
+
+    /// File names and the `name` of every server read from `dir`
+    fn servers(dir: &TempDir) -> Vec<(String, Value)> {
+        read_server_dir(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|(path, server)| {
+                let file = path.file_name().unwrap().to_str().unwrap().to_owned();
+                (file, server["name"].clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_formats_by_extension() {
+        let dir = TempDir::new();
+        let cases = [
+            ("server.json", r#"{ "name": "a", "port": 1 }"#),
+            ("server.toml", "name = \"a\"\nport = 1\n"),
+            ("server.yaml", "name: a\nport: 1\n"),
+            ("server.yml", "name: a\nport: 1\n"),
+            // Config files used to be JSON whatever they were called
+            ("config.conf", r#"{ "name": "a", "port": 1 }"#),
+        ];
+        for (name, contents) in cases {
+            let value = read_file(&dir.write(name, contents)).unwrap();
+            assert_eq!(value, json!({ "name": "a", "port": 1 }), "{name}");
+        }
+
+        let err = read_file(&dir.write("broken.toml", "name = ")).unwrap_err();
+        assert!(format!("{err:#}").contains("failed to parse"), "{err:#}");
+    }
+
+    #[test]
+    fn reads_single_servers_and_lists() {
+        let dir = TempDir::new();
+        dir.write("1-single.toml", "name = \"single\"");
+        dir.write("2-list.json", r#"[{ "name": "first" }, { "name": "second" }]"#);
+        dir.write("3-servers.yaml", "servers:\n  - name: listed\n");
+        dir.write("4-empty.json", "[]");
+
+        let expected = [
+            ("1-single.toml", "single"),
+            ("2-list.json", "first"),
+            ("2-list.json", "second"),
+            ("3-servers.yaml", "listed"),
+        ]
+        .map(|(file, name)| (file.to_owned(), json!(name)));
+        assert_eq!(servers(&dir), expected);
+    }
+
+    #[test]
+    fn skips_hidden_and_other_files() {
+        let dir = TempDir::new();
+        dir.write("server.json", r#"{ "name": "kept" }"#);
+        dir.write(".server.json", "not read");
+        dir.write("server.json~", "not read");
+        dir.write("server.json.bak", "not read");
+        dir.write("README.md", "not read");
+        std::fs::create_dir(dir.path().join("nested.json")).unwrap();
+
+        assert_eq!(servers(&dir), [("server.json".to_owned(), json!("kept"))]);
+    }
+
+    #[test]
+    fn rejects_files_without_servers() {
+        for contents in [r#"{ "guilds": [] }"#, "42", r#"{ "servers": 1 }"#] {
+            let dir = TempDir::new();
+            dir.write("server.json", contents);
+            assert!(read_server_dir(dir.path()).is_err(), "{contents}");
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::servers::permissions::{Principal, Subject};
+use crate::servers::unix_now;
+use common::console::{ConsoleEntry, ConsoleOutcome};
+use serde::Deserialize;
+use smol_str::SmolStr;
+use sqlx::SqlitePool;
+
+/// Prefix that allows every command, including Lua through `/c`
+const ANY_COMMAND: &str = "*";
+/// Entries returned by [ConsoleLog::recent]
+const RECENT_ENTRIES: i64 = 100;
+
+/// Which console commands a role, group or user may run. Only applies to users that also have
+/// the `console` capability.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConsoleRule {
+    #[serde(flatten)]
+    pub(crate) subject: Subject,
+    /// Command prefixes like `/players` or `/ban`, or `*` for everything
+    pub(crate) commands: Vec<SmolStr>,
+}
+
+/// Whether any rule matching `principal` allows `command`, which must already be trimmed. A
+/// prefix only matches whole words, so `/ban` allows `/ban griefer` but not `/banlist`.
+pub fn is_allowed(rules: &[ConsoleRule], principal: &Principal, command: &str) -> bool {
+    rules
+        .iter()
+        .filter(|rule| rule.subject.matches(principal))
+        .flat_map(|rule| &rule.commands)
+        .any(|prefix| {
+            prefix == ANY_COMMAND
+                || command
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+        })
+}
+
+/// Record of every console command and its response, kept in SQLite
+pub(super) struct ConsoleLog {
+    pool: SqlitePool,
+}
+
+impl ConsoleLog {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn record(
+        &self,
+        server_id: &str,
+        actor: &str,
+        username: &str,
+        command: &str,
+        outcome: ConsoleOutcome,
+        response: &str,
+    ) -> anyhow::Result<ConsoleEntry> {
+        let at = unix_now();
+        sqlx::query(
+            "INSERT INTO console_log
+             (server_id, occurred_at, actor, username, command, outcome, response)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(server_id)
+        .bind(at)
+        .bind(actor)
+        .bind(username)
+        .bind(command)
+        .bind(outcome_str(outcome))
+        .bind(response)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(ConsoleEntry {
+            at,
+            username: username.into(),
+            command: command.to_owned(),
+            outcome,
+            response: response.to_owned(),
+        })
+    }
+
+    /// The latest entries for the server, oldest first
+    pub async fn recent(&self, server_id: &str) -> anyhow::Result<Vec<ConsoleEntry>> {
+        let mut entries = sqlx::query_as::<_, (i64, String, String, String, String)>(
+            "SELECT occurred_at, username, command, outcome, response FROM console_log
+             WHERE server_id = ?
+             ORDER BY occurred_at DESC, rowid DESC
+             LIMIT ?",
+        )
+        .bind(server_id)
+        .bind(RECENT_ENTRIES)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(at, username, command, outcome, response)| ConsoleEntry {
+            at,
+            username: username.into(),
+            command,
+            outcome: match outcome.as_str() {
+                "ok" => ConsoleOutcome::Ok,
+                "denied" => ConsoleOutcome::Denied,
+                _ => ConsoleOutcome::Error,
+            },
+            response,
+        })
+        .collect::<Vec<_>>();
+
+        entries.reverse();
+        Ok(entries)
+    }
+}
+
+fn outcome_str(outcome: ConsoleOutcome) -> &'static str {
+    match outcome {
+        ConsoleOutcome::Ok => "ok",
+        ConsoleOutcome::Denied => "denied",
+        ConsoleOutcome::Error => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::discord::{RoleId, UserId};
+    use std::collections::HashSet;
+
+    const ADMIN: u64 = 10;
+    const MODERATOR: u64 = 20;
+
+    fn rule(role: u64, commands: &[&str]) -> ConsoleRule {
+        ConsoleRule {
+            subject: Subject::Role(RoleId::from(role)),
+            commands: commands.iter().map(|&command| command.into()).collect(),
+        }
+    }
+
+    fn principal(roles: &[u64]) -> Principal {
+        let roles = roles.iter().map(|&role| RoleId::from(role)).collect::<HashSet<_>>();
+        Principal::discord(UserId::from(1), roles)
+    }
+
+    #[test]
+    fn matches_whole_word_prefixes() {
+        let rules = [rule(MODERATOR, &["/ban", "/players online"])];
+        let moderator = principal(&[MODERATOR]);
+        let cases = [
+            ("/ban", true),
+            ("/ban griefer", true),
+            ("/ban\tgriefer", true),
+            ("/banlist", false),
+            ("/bans", false),
+            ("/players online", true),
+            ("/players", false),
+            ("/players onlineX", false),
+            ("/unban griefer", false),
+            ("/c game.print(1)", false),
+            ("", false),
+        ];
+        for (command, allowed) in cases {
+            assert_eq!(is_allowed(&rules, &moderator, command), allowed, "{command:?}");
+        }
+    }
+
+    #[test]
+    fn star_allows_everything() {
+        let rules = [rule(ADMIN, &[ANY_COMMAND])];
+        let admin = principal(&[ADMIN]);
+        for command in ["/c game.print(1)", "/banlist", "hello", ""] {
+            assert!(is_allowed(&rules, &admin, command), "{command:?}");
+        }
+    }
+
+    #[test]
+    fn needs_a_matching_rule() {
+        let rules = [rule(ADMIN, &[ANY_COMMAND]), rule(MODERATOR, &["/ban"])];
+        assert!(!is_allowed(&rules, &principal(&[]), "/ban griefer"));
+        assert!(!is_allowed(&rules, &principal(&[30]), "/ban griefer"));
+        assert!(!is_allowed(&rules, &principal(&[MODERATOR]), "/c game.print(1)"));
+        assert!(is_allowed(&rules, &principal(&[MODERATOR, ADMIN]), "/c game.print(1)"));
+        assert!(!is_allowed(&[], &principal(&[ADMIN]), "/ban griefer"));
+    }
+
+    #[test]
+    fn leading_whitespace_doesnt_match() {
+        // Callers trim first, so an untrimmed command never sneaks past a prefix
+        let rules = [rule(MODERATOR, &["/ban"])];
+        let moderator = principal(&[MODERATOR]);
+        assert!(!is_allowed(&rules, &moderator, " /ban griefer"));
+        assert!(!is_allowed(&rules, &moderator, "\n/ban griefer"));
+        assert!(is_allowed(&rules, &moderator, " /ban griefer".trim()));
+    }
+}
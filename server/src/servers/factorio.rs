@@ -1,18 +1,29 @@
-use crate::servers::StatusFetcher;
+use crate::metrics::METRICS;
+use crate::secret::Secret;
+use crate::servers::{StatusFetcher, UNKNOWN_TEXT};
 use crate::AppResult;
-use anyhow::anyhow;
-use common::factorio::FactorioStatus;
-use common::secret::Secret;
+use anyhow::{anyhow, Context};
+use common::factorio::{FactorioMod, FactorioResearch, FactorioStatus, ModOverview, SaveList};
 use common::status::HealthStatus;
+use futures::Stream;
 use moka::future::Cache;
 use once_cell::sync::Lazy;
 use rcon::Connection;
 use serde::Deserialize;
 use smol_str::SmolStr;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+mod mods;
+mod saves;
+
+pub use mods::Upload;
+use saves::BackupPolicy;
 
 static CLIENTS: Lazy<Cache<FactorioConfig, Arc<Mutex<Connection<TcpStream>>>>> = Lazy::new(|| {
     Cache::builder()
@@ -21,11 +32,84 @@ static CLIENTS: Lazy<Cache<FactorioConfig, Arc<Mutex<Connection<TcpStream>>>>> =
         .build()
 });
 
+/// Last tick seen on each server and when, to measure UPS between polls
+static LAST_TICKS: Lazy<Cache<SmolStr, (u64, Instant)>> = Lazy::new(|| {
+    Cache::builder()
+        .max_capacity(10)
+        .time_to_idle(Duration::from_secs(5 * 60))
+        .build()
+});
+
+/// Technologies that unlock each science pack after automation, in vanilla order
+const SCIENCE_PACKS: [&str; 6] = [
+    "logistic-science-pack",
+    "military-science-pack",
+    "chemical-science-pack",
+    "production-science-pack",
+    "utility-science-pack",
+    "space-science-pack",
+];
+
+/// Collects everything `/players`, `/time` and `/version` don't tell us as a single JSON object.
+/// Factorio 2.0 moved `table_to_json` to `helpers` and made evolution per surface.
+static DETAILS_COMMAND: Lazy<String> = Lazy::new(|| {
+    let packs = SCIENCE_PACKS.map(|pack| format!("'{pack}'")).join(", ");
+    format!(
+        "/sc \
+        local json = helpers and helpers.table_to_json or game.table_to_json; \
+        local force = game.forces.player; \
+        local enemy = game.forces.enemy; \
+        local surface = game.surfaces[1]; \
+        local research = force.current_research; \
+        local sciences = {{}}; \
+        for _, name in pairs({{{packs}}}) do \
+            local tech = force.technologies[name]; \
+            if tech and tech.researched then sciences[name] = true end \
+        end; \
+        rcon.print(json({{ \
+            tick = game.tick, \
+            mods = script.active_mods, \
+            research = research and {{name = research.name, progress = force.research_progress}} or nil, \
+            evolution = helpers and enemy.get_evolution_factor(surface) or enemy.evolution_factor, \
+            seed = surface.map_gen_settings.seed, \
+            sciences = sciences \
+        }}))"
+    )
+});
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
 pub struct FactorioConfig {
     pub rcon_host: SmolStr,
     pub rcon_password: Secret,
     pub game_password: Secret,
+    /// Gather research, evolution, mods and the like with a Lua command on every poll. Off by
+    /// default: running Lua permanently disables achievements for the loaded save, and every save
+    /// made from it, as soon as this is turned on.
+    #[serde(default)]
+    pub detailed_status: bool,
+    /// Directory the server writes its saves to. Enables listing and downloading them, and
+    /// showing the current save.
+    #[serde(default)]
+    pub saves_dir: Option<PathBuf>,
+    /// Copies autosaves before they're overwritten. Requires `saves_dir`.
+    #[serde(default)]
+    pub backups: Option<BackupPolicy>,
+    /// The server's `mods` directory with `mod-list.json`. Enables managing mods.
+    #[serde(default)]
+    pub mods_dir: Option<PathBuf>,
+}
+
+/// Output of [DETAILS_COMMAND]. Lua tables with no entries come back as `{}`, so the lists are
+/// maps instead.
+#[derive(Debug, Deserialize)]
+struct Details {
+    tick: u64,
+    mods: BTreeMap<SmolStr, SmolStr>,
+    research: Option<FactorioResearch>,
+    evolution: f32,
+    seed: u32,
+    #[serde(default)]
+    sciences: HashMap<SmolStr, bool>,
 }
 
 impl FactorioConfig {
@@ -37,16 +121,110 @@ impl FactorioConfig {
 
         Ok(Arc::new(Mutex::new(conn)))
     }
-}
 
-const UNKNOWN_TEXT: SmolStr = SmolStr::new_static("unknown");
+    async fn connection(&self) -> AppResult<Arc<Mutex<Connection<TcpStream>>>> {
+        CLIENTS
+            .try_get_with_by_ref(self, self.connect())
+            .await
+            .map_err(|err| Arc::try_unwrap(err).unwrap_or_else(|e| anyhow!("{e}").into()))
+    }
+
+    /// Runs a console command over the pooled connection and returns the server's response
+    pub async fn run_command(&self, command: &str) -> AppResult<String> {
+        let mutex = self.connection().await?;
+        let result = mutex.lock().await.cmd(command).await;
+        if result.is_err() {
+            // Start over with a fresh connection next time
+            CLIENTS.invalidate(self).await;
+        }
+
+        Ok(result?)
+    }
+
+    /// Saves and backups on disk. `None` if the server has no saves directory.
+    pub async fn list_saves(&self) -> AppResult<Option<SaveList>> {
+        let Some(saves_dir) = &self.saves_dir else {
+            return Ok(None);
+        };
+        let backups = match &self.backups {
+            Some(policy) => saves::list(&policy.dir(saves_dir)).await?,
+            None => Vec::new(),
+        };
+
+        Ok(Some(SaveList {
+            saves: saves::list(saves_dir).await?,
+            backups,
+        }))
+    }
+
+    /// Path of the save or backup called `name`, if the server has saves and the name is valid.
+    /// The file may not exist.
+    pub fn save_path(&self, name: &str, backup: bool) -> Option<PathBuf> {
+        let saves_dir = self.saves_dir.as_ref()?;
+        let dir = match (backup, &self.backups) {
+            (false, _) => saves_dir.clone(),
+            (true, Some(policy)) => policy.dir(saves_dir),
+            (true, None) => return None,
+        };
+        saves::resolve(&dir, name)
+    }
+
+    /// Makes the server write its current save, returns what it answered
+    pub async fn save(&self) -> AppResult<String> {
+        self.run_command("/server-save").await
+    }
+
+    /// Mods on disk compared with `running`, the mods the server reports. `None` if the server
+    /// has no mods directory.
+    pub async fn list_mods(&self, running: Option<&[FactorioMod]>) -> AppResult<Option<ModOverview>> {
+        match &self.mods_dir {
+            Some(mods_dir) => Ok(Some(mods::overview(mods_dir, running).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns `false` if the server has no mods directory or no such mod
+    pub async fn set_mod_enabled(&self, name: &str, enabled: bool) -> AppResult<bool> {
+        match &self.mods_dir {
+            Some(mods_dir) => Ok(mods::set_enabled(mods_dir, name, enabled).await?),
+            None => Ok(false),
+        }
+    }
+
+    /// Stores a mod zip called `file_name`. `None` if the server has no mods directory or the
+    /// name isn't `{name}_{version}.zip`.
+    pub async fn upload_mod<B, E>(
+        &self,
+        file_name: &str,
+        body: impl Stream<Item = Result<B, E>> + Unpin,
+    ) -> AppResult<Option<Upload>>
+    where
+        B: AsRef<[u8]>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let Some(mods_dir) = &self.mods_dir else {
+            return Ok(None);
+        };
+        if !mods::is_mod_file_name(file_name) {
+            return Ok(None);
+        }
+        Ok(Some(mods::store_upload(mods_dir, file_name, body).await?))
+    }
+
+    /// Applies the backup policy, if there is one. Errors are only logged.
+    pub async fn back_up_saves(&self) {
+        let (Some(saves_dir), Some(policy)) = (&self.saves_dir, &self.backups) else {
+            return;
+        };
+        if let Err(e) = saves::back_up_autosaves(saves_dir, policy).await {
+            tracing::error!("Failed to back up saves of {}: {:#}", self.rcon_host, e);
+        }
+    }
+}
 
 impl FactorioConfig {
     async fn populate_status(&self, status: &mut FactorioStatus) -> AppResult<()> {
-        let mutex = CLIENTS
-            .try_get_with_by_ref(self, self.connect())
-            .await
-            .map_err(|err| Arc::try_unwrap(err).unwrap_or_else(|e| anyhow!("{e}").into()))?;
+        let mutex = self.connection().await?;
 
         let mut conn = mutex.lock().await;
         
@@ -63,6 +241,63 @@ impl FactorioConfig {
         status.game_time = conn.cmd("/time").await?.into();
         status.game_version = conn.cmd("/version").await?.into();
 
+        if let Some(saves_dir) = &self.saves_dir {
+            match saves::list(saves_dir).await {
+                Ok(saves) => status.save_name = saves::current_save_name(&saves),
+                Err(e) => tracing::warn!("Failed to list saves of {}: {:#}", self.rcon_host, e),
+            }
+        }
+
+        if self.detailed_status {
+            // The basics are still worth showing if the Lua part fails, e.g. on a modded
+            // surface setup
+            if let Err(e) = self.populate_details(&mut conn, status).await {
+                tracing::warn!("Failed to fetch details of {}: {:#}", self.rcon_host, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn populate_details(
+        &self,
+        conn: &mut Connection<TcpStream>,
+        status: &mut FactorioStatus,
+    ) -> anyhow::Result<()> {
+        let mut text = conn.cmd(&DETAILS_COMMAND).await?;
+        if !text.trim_start().starts_with('{') {
+            // The first Lua command on a save only warns that it disables achievements, which has
+            // already happened by then, so the command has to be repeated to get the details
+            tracing::debug!("Repeating details command after: {}", text.trim());
+            text = conn.cmd(&DETAILS_COMMAND).await?;
+        }
+        let details = serde_json::from_str::<Details>(text.trim())
+            .with_context(|| format!("unexpected details output: {}", text.trim()))?;
+
+        status.mods = details
+            .mods
+            .into_iter()
+            .map(|(name, version)| FactorioMod { name, version })
+            .collect();
+        status.research = details.research;
+        status.science_packs = SCIENCE_PACKS
+            .iter()
+            .filter(|pack| details.sciences.get(**pack).copied().unwrap_or(false))
+            .map(|pack| SmolStr::new_static(pack))
+            .collect();
+        status.evolution = Some(details.evolution);
+        status.map_seed = Some(details.seed);
+
+        let now = Instant::now();
+        if let Some((last_tick, last_at)) = LAST_TICKS.get(&self.rcon_host).await {
+            let elapsed = now.duration_since(last_at).as_secs_f32();
+            if elapsed >= 1.0 && details.tick >= last_tick {
+                let ups = (details.tick - last_tick) as f32 / elapsed;
+                status.ups = Some((ups * 10.0).round() / 10.0);
+            }
+        }
+        LAST_TICKS.insert(self.rcon_host.clone(), (details.tick, now)).await;
+
         Ok(())
     }
 }
@@ -70,8 +305,8 @@ impl FactorioConfig {
 impl StatusFetcher for FactorioConfig {
     type Status = FactorioStatus;
 
-    async fn fetch_server_status(&self) -> FactorioStatus {
-        let mut status = FactorioStatus {
+    fn unknown_status(&self) -> FactorioStatus {
+        FactorioStatus {
             name: self.rcon_host.clone(),
             health: HealthStatus::Unknown,
             game_password: self.game_password.secret().clone(),
@@ -79,9 +314,21 @@ impl StatusFetcher for FactorioConfig {
             players_online: Vec::new(),
             game_time: UNKNOWN_TEXT,
             game_version: UNKNOWN_TEXT,
-        };
+            mods: Vec::new(),
+            research: None,
+            science_packs: Vec::new(),
+            evolution: None,
+            map_seed: None,
+            save_name: None,
+            ups: None,
+        }
+    }
+
+    async fn fetch_server_status(&self) -> FactorioStatus {
+        let mut status = self.unknown_status();
 
         if let Err(e) = self.populate_status(&mut status).await {
+            METRICS.rcon_error("factorio");
             tracing::error!("Failed to fetch server status: {}", e);
         }
 
@@ -0,0 +1,439 @@
+use anyhow::Context;
+use common::factorio::{FactorioMod, ModInfo, ModOverview};
+use futures::{Stream, StreamExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use smol_str::SmolStr;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+const MOD_LIST: &str = "mod-list.json";
+/// Largest mod zip we accept. The biggest overhaul mods are a few hundred MB.
+const MAX_UPLOAD_BYTES: u64 = 1024 * 1024 * 1024;
+/// Every zip starts with a local file header
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Serializes edits of `mod-list.json`, so two toggles can't undo each other
+static MOD_LIST_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// `mod-list.json`. Fields we don't know are kept as they are.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ModList {
+    mods: Vec<ModListEntry>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ModListEntry {
+    name: SmolStr,
+    enabled: bool,
+    /// Loads this version instead of the newest one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<SmolStr>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+async fn read_mod_list(mods_dir: &Path) -> anyhow::Result<ModList> {
+    let path = mods_dir.join(MOD_LIST);
+    match fs::read(&path).await {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("failed to parse {}", path.display())),
+        // Factorio writes it on first start, until then every installed mod is enabled
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ModList::default()),
+        Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Versions of every mod in the directory, from `{name}_{version}.zip` files and unpacked
+/// `{name}_{version}` directories
+async fn installed_versions(mods_dir: &Path) -> anyhow::Result<BTreeMap<SmolStr, Vec<SmolStr>>> {
+    let mut entries = fs::read_dir(mods_dir)
+        .await
+        .with_context(|| format!("failed to read {}", mods_dir.display()))?;
+
+    let mut installed = BTreeMap::<SmolStr, Vec<SmolStr>>::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        let stem = match file_name.strip_suffix(".zip") {
+            Some(stem) => stem,
+            None if entry.file_type().await?.is_dir() => file_name,
+            None => continue,
+        };
+        if let Some((name, version)) = split_mod_file(stem) {
+            installed.entry(name.into()).or_default().push(version.into());
+        }
+    }
+
+    for versions in installed.values_mut() {
+        versions.sort_by_key(|version| version_key(version));
+    }
+    Ok(installed)
+}
+
+/// Splits `{name}_{version}`. Mod names may contain underscores, versions can't.
+fn split_mod_file(stem: &str) -> Option<(&str, &str)> {
+    let (name, version) = stem.rsplit_once('_')?;
+    let is_version = !version.is_empty()
+        && version.split('.').all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()));
+    (!name.is_empty() && is_version).then_some((name, version))
+}
+
+fn version_key(version: &str) -> Vec<u32> {
+    version.split('.').map(|part| part.parse().unwrap_or(0)).collect()
+}
+
+/// Merges `mod-list.json`, the mods directory and what the running server reports. `running`
+/// is `None` if the server didn't report its mods.
+pub async fn overview(mods_dir: &Path, running: Option<&[FactorioMod]>) -> anyhow::Result<ModOverview> {
+    let list = read_mod_list(mods_dir).await?;
+    let installed = installed_versions(mods_dir).await?;
+
+    let names = list
+        .mods
+        .iter()
+        .map(|entry| entry.name.clone())
+        .chain(installed.keys().cloned())
+        .chain(running.into_iter().flatten().map(|m| m.name.clone()))
+        .collect::<BTreeSet<_>>();
+
+    let mods = names
+        .into_iter()
+        .map(|name| {
+            let entry = list.mods.iter().find(|entry| entry.name == name);
+            let versions = installed.get(&name).cloned().unwrap_or_default();
+            // Mods missing from the list are enabled as soon as Factorio sees them
+            let enabled = entry.is_none_or(|entry| entry.enabled);
+            let expected = entry
+                .and_then(|entry| entry.version.clone())
+                .or_else(|| versions.last().cloned());
+            let running_version = running
+                .and_then(|running| running.iter().find(|m| m.name == name))
+                .map(|m| m.version.clone());
+
+            let mismatch = running.is_some()
+                && match (&running_version, enabled) {
+                    (Some(_), false) => true,
+                    (None, true) => !versions.is_empty(),
+                    // Mods that ship with the game aren't in the directory
+                    (Some(running), true) => expected.as_ref().is_some_and(|e| e != running),
+                    (None, false) => false,
+                };
+
+            ModInfo {
+                name,
+                enabled,
+                installed: versions,
+                expected,
+                running: running_version,
+                mismatch,
+            }
+        })
+        .collect();
+
+    Ok(ModOverview {
+        mods,
+        running_known: running.is_some(),
+    })
+}
+
+/// Enables or disables the mod in `mod-list.json`, adding it if it's installed but not listed
+/// yet. Takes effect on the next start. Returns `false` if there's no such mod.
+pub async fn set_enabled(mods_dir: &Path, name: &str, enabled: bool) -> anyhow::Result<bool> {
+    let _guard = MOD_LIST_LOCK.lock().await;
+    let mut list = read_mod_list(mods_dir).await?;
+    match list.mods.iter_mut().find(|entry| entry.name == name) {
+        Some(entry) => entry.enabled = enabled,
+        None if installed_versions(mods_dir).await?.contains_key(name) => {
+            list.mods.push(ModListEntry {
+                name: name.into(),
+                enabled,
+                version: None,
+                extra: Map::new(),
+            });
+        }
+        None => return Ok(false),
+    }
+
+    let path = mods_dir.join(MOD_LIST);
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, serde_json::to_vec_pretty(&list)?).await?;
+    fs::rename(&partial, &path)
+        .await
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(true)
+}
+
+/// Whether `file_name` looks like a mod zip, `{name}_{version}.zip`
+pub fn is_mod_file_name(file_name: &str) -> bool {
+    Path::new(file_name).file_name().is_some_and(|file| file == file_name)
+        && !file_name.starts_with('.')
+        && file_name
+            .strip_suffix(".zip")
+            .and_then(split_mod_file)
+            .is_some()
+}
+
+/// What became of an uploaded mod
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Upload {
+    Stored,
+    NotZip,
+    TooLarge,
+}
+
+/// Writes an uploaded mod zip into the mods directory, replacing the same version. Nothing is
+/// left behind if the upload fails or is rejected.
+pub async fn store_upload<B, E>(
+    mods_dir: &Path,
+    file_name: &str,
+    body: impl Stream<Item = Result<B, E>> + Unpin,
+) -> anyhow::Result<Upload>
+where
+    B: AsRef<[u8]>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    store_limited(mods_dir, file_name, body, MAX_UPLOAD_BYTES).await
+}
+
+async fn store_limited<B, E>(
+    mods_dir: &Path,
+    file_name: &str,
+    mut body: impl Stream<Item = Result<B, E>> + Unpin,
+    max_bytes: u64,
+) -> anyhow::Result<Upload>
+where
+    B: AsRef<[u8]>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let path = mods_dir.join(file_name);
+    // Factorio ignores anything that doesn't end in .zip, so it won't load a partial upload
+    let partial = path.with_extension("zip.partial");
+
+    let result = async {
+        let mut file = fs::File::create(&partial).await?;
+        let mut head = Vec::with_capacity(ZIP_MAGIC.len());
+        let mut written = 0u64;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            let chunk = chunk.as_ref();
+            if head.len() < ZIP_MAGIC.len() {
+                let missing = ZIP_MAGIC.len() - head.len();
+                head.extend_from_slice(&chunk[..missing.min(chunk.len())]);
+                if !ZIP_MAGIC.starts_with(&head) {
+                    return Ok(Upload::NotZip);
+                }
+            }
+            written += chunk.len() as u64;
+            if written > max_bytes {
+                return Ok(Upload::TooLarge);
+            }
+            file.write_all(chunk).await?;
+        }
+        if head != ZIP_MAGIC {
+            return Ok(Upload::NotZip);
+        }
+        file.flush().await?;
+        fs::rename(&partial, &path).await?;
+        anyhow::Ok(Upload::Stored)
+    };
+
+    let result = result.await;
+    if !matches!(result, Ok(Upload::Stored)) {
+        let _ = fs::remove_file(&partial).await;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use futures::stream;
+
+    #[test]
+    fn splits_mod_file_names() {
+        let cases = [
+            ("flib_0.15.0", Some(("flib", "0.15.0"))),
+            ("my_cool_mod_1.2.3", Some(("my_cool_mod", "1.2.3"))),
+            ("Krastorio2_1", Some(("Krastorio2", "1"))),
+            ("flib", None),
+            ("flib_", None),
+            ("_1.0.0", None),
+            ("flib_1..0", None),
+            ("flib_1.0.", None),
+            ("flib_v1.0", None),
+            ("flib_1.0.0-beta", None),
+            ("flib_1.0_extra", None),
+        ];
+        for (stem, expected) in cases {
+            assert_eq!(split_mod_file(stem), expected, "{stem:?}");
+        }
+    }
+
+    #[test]
+    fn checks_upload_file_names() {
+        assert!(is_mod_file_name("my_mod_1.2.3.zip"));
+        assert!(!is_mod_file_name("my_mod_1.2.3"));
+        assert!(!is_mod_file_name("my_mod_1.2.3.tar"));
+        assert!(!is_mod_file_name("../my_mod_1.2.3.zip"));
+        assert!(!is_mod_file_name("mods/my_mod_1.2.3.zip"));
+        assert!(!is_mod_file_name(".hidden_1.0.0.zip"));
+        assert!(!is_mod_file_name("mod-list.json"));
+    }
+
+    fn running(mods: &[(&str, &str)]) -> Vec<FactorioMod> {
+        mods.iter()
+            .map(|&(name, version)| FactorioMod {
+                name: name.into(),
+                version: version.into(),
+            })
+            .collect()
+    }
+
+    /// A mods directory covering every combination the overview cares about
+    fn mods_dir() -> TempDir {
+        let dir = TempDir::new();
+        dir.write(
+            MOD_LIST,
+            r#"{"mods": [
+                {"name": "base", "enabled": true},
+                {"name": "disabled-running", "enabled": false},
+                {"name": "off", "enabled": false},
+                {"name": "pinned", "enabled": true, "version": "1.0.0"}
+            ]}"#,
+        );
+        for file in [
+            "disabled-running_1.0.0.zip",
+            "new-mod_2.0.0.zip",
+            "off_1.0.0.zip",
+            "pinned_1.0.0.zip",
+            "pinned_1.1.0.zip",
+            "updated_1.0.0.zip",
+            "updated_1.10.0.zip",
+            "updated_1.9.0.zip",
+            "not-a-mod.txt",
+        ] {
+            dir.write(file, "");
+        }
+        std::fs::create_dir(dir.path().join("unpacked_1.0.0")).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn flags_mods_that_need_a_restart() {
+        let dir = mods_dir();
+        let running = running(&[
+            ("base", "2.0.0"),
+            ("disabled-running", "1.0.0"),
+            ("pinned", "1.0.0"),
+            ("updated", "1.9.0"),
+            ("unpacked", "1.0.0"),
+        ]);
+        let overview = overview(dir.path(), Some(&running)).await.unwrap();
+        assert!(overview.running_known);
+
+        let cases = [
+            // (name, enabled, expected version, mismatch)
+            ("base", true, None, false),
+            ("disabled-running", false, Some("1.0.0"), true),
+            ("new-mod", true, Some("2.0.0"), true),
+            ("off", false, Some("1.0.0"), false),
+            ("pinned", true, Some("1.0.0"), false),
+            ("unpacked", true, Some("1.0.0"), false),
+            ("updated", true, Some("1.10.0"), true),
+        ];
+        let names = overview.mods.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, cases.map(|case| case.0));
+        for (info, (name, enabled, expected, mismatch)) in overview.mods.iter().zip(cases) {
+            assert_eq!(info.enabled, enabled, "{name}");
+            assert_eq!(info.expected.as_deref(), expected, "{name}");
+            assert_eq!(info.mismatch, mismatch, "{name}");
+        }
+        let updated = &overview.mods[6];
+        assert_eq!(updated.installed, ["1.0.0", "1.9.0", "1.10.0"]);
+        assert_eq!(updated.running.as_deref(), Some("1.9.0"));
+    }
+
+    #[tokio::test]
+    async fn never_flags_mods_without_running_info() {
+        let dir = mods_dir();
+        let overview = overview(dir.path(), None).await.unwrap();
+        assert!(!overview.running_known);
+        assert!(overview.mods.iter().all(|m| !m.mismatch && m.running.is_none()));
+    }
+
+    #[tokio::test]
+    async fn toggles_mods() {
+        let dir = mods_dir();
+        assert!(set_enabled(dir.path(), "off", true).await.unwrap());
+        // Installed but not listed yet
+        assert!(set_enabled(dir.path(), "new-mod", false).await.unwrap());
+        assert!(!set_enabled(dir.path(), "missing", true).await.unwrap());
+
+        let overview = overview(dir.path(), None).await.unwrap();
+        let enabled = |name: &str| overview.mods.iter().find(|m| m.name == name).unwrap().enabled;
+        assert!(enabled("off"));
+        assert!(!enabled("new-mod"));
+        assert!(!dir.path().join("mod-list.json.partial").exists());
+    }
+
+    async fn upload(
+        dir: &TempDir,
+        chunks: &[&'static [u8]],
+        max_bytes: u64,
+    ) -> anyhow::Result<Upload> {
+        let body = stream::iter(chunks.iter().map(|&chunk| Ok::<_, std::io::Error>(chunk)));
+        store_limited(dir.path(), "flib_1.0.0.zip", body, max_bytes).await
+    }
+
+    #[tokio::test]
+    async fn stores_zip_uploads() {
+        let dir = TempDir::new();
+        // The magic is split across chunks
+        let result = upload(&dir, &[b"PK", b"\x03\x04", b"rest of the zip"], 1024).await;
+        assert_eq!(result.unwrap(), Upload::Stored);
+        assert_eq!(dir.files(""), ["flib_1.0.0.zip"]);
+        let stored = std::fs::read(dir.path().join("flib_1.0.0.zip")).unwrap();
+        assert_eq!(stored, b"PK\x03\x04rest of the zip");
+
+        // The same version again replaces it
+        assert_eq!(upload(&dir, &[b"PK\x03\x04new"], 1024).await.unwrap(), Upload::Stored);
+        assert_eq!(std::fs::read(dir.path().join("flib_1.0.0.zip")).unwrap(), b"PK\x03\x04new");
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_uploads_without_leaving_files() {
+        let cases: [(&[&'static [u8]], Upload); 5] = [
+            (&[b"not a zip at all"], Upload::NotZip),
+            (&[b"P", b"Kx"], Upload::NotZip),
+            (&[b"PK"], Upload::NotZip),
+            (&[], Upload::NotZip),
+            (&[b"PK\x03\x04", b"0123456789"], Upload::TooLarge),
+        ];
+        for (chunks, expected) in cases {
+            let dir = TempDir::new();
+            assert_eq!(upload(&dir, chunks, 8).await.unwrap(), expected, "{chunks:?}");
+            assert!(dir.files("").is_empty(), "{chunks:?} left {:?}", dir.files(""));
+        }
+    }
+
+    #[tokio::test]
+    async fn removes_partial_upload_on_error() {
+        let dir = TempDir::new();
+        let body = stream::iter([
+            Ok(&b"PK\x03\x04"[..]),
+            Err(std::io::Error::other("connection reset")),
+        ]);
+        assert!(store_limited(dir.path(), "flib_1.0.0.zip", body, 1024).await.is_err());
+        assert!(dir.files("").is_empty());
+    }
+}
@@ -0,0 +1,272 @@
+use anyhow::Context;
+use common::factorio::SaveFile;
+use serde::Deserialize;
+use smol_str::SmolStr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+const AUTOSAVE_PREFIX: &str = "_autosave";
+/// Autosaves modified more recently than this may still be being written
+const SETTLE_TIME: Duration = Duration::from_secs(10);
+
+/// Keeps copies of autosaves, since Factorio overwrites them in rotation and a corrupted one
+/// can take the previous good state with it
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct BackupPolicy {
+    /// Defaults to `backups` inside the saves directory
+    #[serde(default)]
+    dir: Option<PathBuf>,
+    /// Backups to keep across all autosave slots, the oldest are deleted first. `0` disables
+    /// backups.
+    #[serde(default = "BackupPolicy::default_keep")]
+    keep: usize,
+}
+
+impl BackupPolicy {
+    fn default_keep() -> usize {
+        20
+    }
+
+    pub fn dir(&self, saves_dir: &Path) -> PathBuf {
+        self.dir.clone().unwrap_or_else(|| saves_dir.join("backups"))
+    }
+}
+
+/// The `.zip` files in `dir`, newest first. A missing directory has no saves.
+pub async fn list(dir: &Path) -> anyhow::Result<Vec<SaveFile>> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", dir.display())),
+    };
+
+    let mut saves = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let metadata = entry.metadata().await?;
+        if !metadata.is_file() || path.extension().is_none_or(|ext| ext != "zip") {
+            continue;
+        }
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        saves.push(SaveFile {
+            name: name.into(),
+            size: metadata.len(),
+            modified: unix_secs(metadata.modified()?),
+        });
+    }
+
+    saves.sort_by(|a, b| b.modified.cmp(&a.modified));
+    Ok(saves)
+}
+
+/// Path of the save called `name` in `dir`, if `name` is a plain `.zip` file name that can't
+/// escape the directory
+pub fn resolve(dir: &Path, name: &str) -> Option<PathBuf> {
+    let is_plain = Path::new(name).file_name().is_some_and(|file| file == name)
+        && !name.starts_with('.')
+        && name.ends_with(".zip");
+    is_plain.then(|| dir.join(name))
+}
+
+/// Name of the save the server most likely runs, the newest in `saves` that isn't an autosave
+pub fn current_save_name(saves: &[SaveFile]) -> Option<SmolStr> {
+    saves
+        .iter()
+        .filter(|save| !save.name.starts_with(AUTOSAVE_PREFIX))
+        .max_by_key(|save| save.modified)
+        .map(|save| save.name.trim_end_matches(".zip").into())
+}
+
+/// Copies autosaves that changed since their last backup to `{slot}-{modified}.zip` and deletes
+/// the oldest backups beyond the policy's limit. Autosaves too old to be among the kept backups
+/// aren't copied, so they aren't deleted and copied again on every call.
+pub async fn back_up_autosaves(saves_dir: &Path, policy: &BackupPolicy) -> anyhow::Result<()> {
+    if policy.keep == 0 {
+        return Ok(());
+    }
+    let backup_dir = policy.dir(saves_dir);
+    let settled = unix_secs(SystemTime::now() - SETTLE_TIME);
+
+    let autosaves = list(saves_dir)
+        .await?
+        .into_iter()
+        .filter(|save| save.name.starts_with(AUTOSAVE_PREFIX) && save.modified <= settled)
+        .collect::<Vec<_>>();
+    let backups = list(&backup_dir).await?;
+    let mut times = backups
+        .iter()
+        .filter(|backup| backup.name.starts_with(AUTOSAVE_PREFIX))
+        .map(backup_time)
+        .chain(autosaves.iter().map(|save| save.modified))
+        .collect::<Vec<_>>();
+    times.sort_unstable_by(|a, b| b.cmp(a));
+    let oldest_kept = times.get(policy.keep - 1).copied().unwrap_or(i64::MIN);
+
+    for save in autosaves.iter().filter(|save| save.modified >= oldest_kept) {
+        let stem = save.name.trim_end_matches(".zip");
+        let target = backup_dir.join(format!("{stem}-{}.zip", save.modified));
+        if fs::try_exists(&target).await? {
+            continue;
+        }
+
+        fs::create_dir_all(&backup_dir)
+            .await
+            .with_context(|| format!("failed to create {}", backup_dir.display()))?;
+        // Copy under a temporary name first so a half-written backup is never listed
+        let partial = target.with_extension("zip.partial");
+        fs::copy(saves_dir.join(&*save.name), &partial)
+            .await
+            .with_context(|| format!("failed to back up {}", save.name))?;
+        fs::rename(&partial, &target).await?;
+        tracing::info!("Backed up {} to {}", save.name, target.display());
+    }
+
+    let mut backups = list(&backup_dir)
+        .await?
+        .into_iter()
+        .filter(|backup| backup.name.starts_with(AUTOSAVE_PREFIX))
+        .collect::<Vec<_>>();
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup_time(backup)));
+    for backup in backups.iter().skip(policy.keep) {
+        tracing::info!("Deleting old backup {}", backup.name);
+        fs::remove_file(backup_dir.join(&*backup.name)).await?;
+    }
+
+    Ok(())
+}
+
+/// When the autosave in a backup was written, from its name. Copies get a new modification
+/// time, so the file's own is only a fallback.
+fn backup_time(backup: &SaveFile) -> i64 {
+    backup
+        .name
+        .trim_end_matches(".zip")
+        .rsplit_once('-')
+        .and_then(|(_, modified)| modified.parse().ok())
+        .unwrap_or(backup.modified)
+}
+
+fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{set_modified, TempDir};
+
+    fn policy(keep: usize) -> BackupPolicy {
+        BackupPolicy { dir: None, keep }
+    }
+
+    /// Writes a save that was last modified `age_secs` ago and returns its modification time
+    fn write_save(dir: &TempDir, name: &str, age_secs: u64) -> i64 {
+        let modified = SystemTime::now() - Duration::from_secs(age_secs);
+        set_modified(&dir.write(name, name), modified);
+        unix_secs(modified)
+    }
+
+    #[test]
+    fn picks_the_newest_manual_save() {
+        let save = |name: &str, modified| SaveFile { name: name.into(), size: 0, modified };
+        let saves = [
+            save("_autosave2.zip", 400),
+            save("old-map.zip", 100),
+            save("main.zip", 300),
+            save("_autosave1.zip", 200),
+        ];
+        assert_eq!(current_save_name(&saves).as_deref(), Some("main"));
+        assert_eq!(current_save_name(&saves[..1]), None);
+        assert_eq!(current_save_name(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn backs_up_settled_autosaves() {
+        let dir = TempDir::new();
+        let first = write_save(&dir, "_autosave1.zip", 300);
+        let second = write_save(&dir, "_autosave2.zip", 200);
+        write_save(&dir, "_autosave3.zip", 0);
+        write_save(&dir, "manual.zip", 100);
+
+        back_up_autosaves(dir.path(), &policy(20)).await.unwrap();
+        let expected = [format!("_autosave1-{first}.zip"), format!("_autosave2-{second}.zip")];
+        assert_eq!(dir.files("backups"), expected);
+        let copied = std::fs::read(dir.path().join("backups").join(&expected[0])).unwrap();
+        assert_eq!(copied, b"_autosave1.zip");
+
+        // Nothing changed, so nothing is copied again
+        back_up_autosaves(dir.path(), &policy(20)).await.unwrap();
+        assert_eq!(dir.files("backups"), expected);
+    }
+
+    #[tokio::test]
+    async fn only_copies_autosaves_that_would_be_kept() {
+        let dir = TempDir::new();
+        write_save(&dir, "_autosave1.zip", 300);
+        write_save(&dir, "_autosave2.zip", 200);
+        let newest = write_save(&dir, "_autosave3.zip", 100);
+
+        for _ in 0..3 {
+            back_up_autosaves(dir.path(), &policy(1)).await.unwrap();
+            assert_eq!(dir.files("backups"), [format!("_autosave3-{newest}.zip")]);
+        }
+    }
+
+    #[tokio::test]
+    async fn prunes_by_autosave_time() {
+        let dir = TempDir::new();
+        std::fs::create_dir(dir.path().join("backups")).unwrap();
+        // Written in a different order than the autosaves were, so file times don't help
+        let names = ["_autosave2-300.zip", "_autosave1-100.zip", "_autosave1-200.zip", "manual.zip"];
+        for name in names {
+            dir.write(&format!("backups/{name}"), name);
+        }
+
+        back_up_autosaves(dir.path(), &policy(2)).await.unwrap();
+        assert_eq!(
+            dir.files("backups"),
+            ["_autosave1-200.zip", "_autosave2-300.zip", "manual.zip"]
+        );
+    }
+
+    #[tokio::test]
+    async fn new_autosaves_replace_the_oldest_backups() {
+        let dir = TempDir::new();
+        std::fs::create_dir(dir.path().join("backups")).unwrap();
+        dir.write("backups/_autosave1-100.zip", "old");
+        dir.write("backups/_autosave2-200.zip", "old");
+        let newest = write_save(&dir, "_autosave1.zip", 60);
+
+        back_up_autosaves(dir.path(), &policy(2)).await.unwrap();
+        assert_eq!(
+            dir.files("backups"),
+            [format!("_autosave1-{newest}.zip"), "_autosave2-200.zip".to_owned()]
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_zero_disables_backups() {
+        let dir = TempDir::new();
+        write_save(&dir, "_autosave1.zip", 300);
+
+        back_up_autosaves(dir.path(), &policy(0)).await.unwrap();
+        assert!(!dir.path().join("backups").exists());
+    }
+
+    #[test]
+    fn reads_backup_time_from_name() {
+        let backup = |name: &str| SaveFile {
+            name: name.into(),
+            size: 0,
+            modified: 42,
+        };
+        assert_eq!(backup_time(&backup("_autosave1-1700000000.zip")), 1_700_000_000);
+        assert_eq!(backup_time(&backup("_autosave1.zip")), 42);
+        assert_eq!(backup_time(&backup("_autosave1-new.zip")), 42);
+    }
+}
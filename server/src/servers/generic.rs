@@ -1,8 +1,9 @@
 use serde::Deserialize;
 use smol_str::SmolStr;
 use common::generic::GenericStatus;
-use common::secret::Secret;
+use crate::secret::Secret;
 use common::status::HealthStatus;
+use crate::servers::probe::ProbeConfig;
 use crate::servers::StatusFetcher;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
@@ -10,18 +11,41 @@ pub struct GenericConfig {
     /// Name of the game, NOT the name of the server
     pub game_name: SmolStr,
     pub game_password: Secret,
+    /// Health check to run. Health is always unknown without one
+    #[serde(default)]
+    pub probe: Option<ProbeConfig>,
 }
 
 impl StatusFetcher for GenericConfig {
     type Status = GenericStatus;
 
-    async fn fetch_server_status(&self) -> GenericStatus {
+    fn unknown_status(&self) -> GenericStatus {
         GenericStatus {
             name: SmolStr::default(), // Filled in later
             game_name: self.game_name.clone(),
             health: HealthStatus::Unknown,
             url: SmolStr::default(), // Filled in later
             game_password: self.game_password.secret().clone(),
+            latency_ms: None,
+        }
+    }
+
+    async fn fetch_server_status(&self) -> GenericStatus {
+        let mut status = self.unknown_status();
+
+        if let Some(probe) = &self.probe {
+            match probe.probe().await {
+                Ok(latency) => {
+                    status.health = HealthStatus::Running;
+                    status.latency_ms = Some(latency.as_millis() as u32);
+                }
+                Err(e) => {
+                    tracing::warn!("Probe for {} failed: {:#}", self.game_name, e);
+                    status.health = HealthStatus::Offline;
+                }
+            }
         }
+
+        status
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,368 @@
+use crate::servers::poller::StatusSnapshot;
+use common::history::{PlayerEvent, PlayerEventKind, ServerHistory, StatusSample};
+use common::status::HealthStatus;
+use smol_str::SmolStr;
+use sqlx::SqlitePool;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+
+/// Statuses are sampled at this interval regardless of whether they changed
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+/// Samples and player events older than this are deleted
+const RETENTION: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Records status samples and player join/leave events from the poller into SQLite
+pub(super) struct HistoryRecorder {
+    pool: SqlitePool,
+    task: AbortHandle,
+}
+
+impl HistoryRecorder {
+    pub fn new(pool: SqlitePool, statuses: watch::Receiver<StatusSnapshot>) -> Self {
+        let task = tokio::spawn(Self::record(pool.clone(), statuses));
+
+        Self {
+            pool,
+            task: task.abort_handle(),
+        }
+    }
+
+    async fn record(pool: SqlitePool, mut statuses: watch::Receiver<StatusSnapshot>) {
+        let mut players = HashMap::new();
+        let mut samples = tokio::time::interval(SAMPLE_INTERVAL);
+        samples.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last_prune = None::<Instant>;
+
+        loop {
+            tokio::select! {
+                _ = samples.tick() => {
+                    let snapshot = statuses.borrow().clone();
+                    if let Err(e) = insert_samples(&pool, &snapshot).await {
+                        tracing::warn!("Failed to record status samples: {:#}", e);
+                    }
+
+                    if last_prune.is_none_or(|t| t.elapsed() >= PRUNE_INTERVAL) {
+                        last_prune = Some(Instant::now());
+                        if let Err(e) = prune(&pool).await {
+                            tracing::warn!("Failed to prune status history: {:#}", e);
+                        }
+                    }
+                }
+                changed = statuses.changed() => {
+                    if changed.is_err() {
+                        tracing::error!("Status poller closed, stopping history recorder");
+                        return;
+                    }
+                    let snapshot = statuses.borrow_and_update().clone();
+                    let events = diff_players(&mut players, &snapshot);
+                    if let Err(e) = insert_player_events(&pool, &events).await {
+                        tracing::warn!("Failed to record player events: {:#}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    pub async fn history(&self, id: &str, from: i64, to: i64) -> anyhow::Result<ServerHistory> {
+        let samples = sqlx::query_as::<_, (i64, String, Option<i64>)>(
+            "SELECT sampled_at, health, players_online FROM status_samples
+             WHERE server_id = ? AND sampled_at BETWEEN ? AND ?
+             ORDER BY sampled_at",
+        )
+        .bind(id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(at, health, players_online)| StatusSample {
+            at,
+            health: health.parse().unwrap_or(HealthStatus::Unknown),
+            players_online: players_online.and_then(|p| p.try_into().ok()),
+        })
+        .collect();
+
+        let player_events = sqlx::query_as::<_, (i64, String, String)>(
+            "SELECT occurred_at, player, kind FROM player_events
+             WHERE server_id = ? AND occurred_at BETWEEN ? AND ?
+             ORDER BY occurred_at",
+        )
+        .bind(id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(at, player, kind)| PlayerEvent {
+            at,
+            player: player.into(),
+            kind: match kind.as_str() {
+                "join" => PlayerEventKind::Join,
+                _ => PlayerEventKind::Leave,
+            },
+        })
+        .collect();
+
+        let last_online = sqlx::query_scalar::<_, Option<i64>>(
+            "SELECT MAX(sampled_at) FROM status_samples WHERE server_id = ? AND health = ?",
+        )
+        .bind(id)
+        .bind(HealthStatus::Running.to_string())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(ServerHistory {
+            samples,
+            player_events,
+            last_online,
+        })
+    }
+}
+
+impl Drop for HistoryRecorder {
+    fn drop(&mut self) {
+        tracing::debug!("Dropping history recorder");
+        self.task.abort();
+    }
+}
+
+pub(crate) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Compares the players of every server against the last known set, updating it in place. The
+/// first known players of a server only seed the set: after a restart they're still online from
+/// before and already have a join recorded.
+fn diff_players(
+    known: &mut HashMap<SmolStr, HashSet<SmolStr>>,
+    snapshot: &StatusSnapshot,
+) -> Vec<(SmolStr, SmolStr, PlayerEventKind)> {
+    let mut events = Vec::new();
+
+    // Servers removed from the config take their players with them. The empty set stays, so
+    // the players count as joining again if the server comes back.
+    for (id, players) in known.iter_mut().filter(|(id, _)| !snapshot.contains_key(*id)) {
+        events.extend(players.drain().map(|p| (id.clone(), p, PlayerEventKind::Leave)));
+    }
+
+    for (id, status) in snapshot {
+        let current = match status.health() {
+            HealthStatus::Running | HealthStatus::Starting => status.player_names(),
+            HealthStatus::Offline => Some(Vec::new()),
+            // A failed poll says nothing about who is playing
+            HealthStatus::Unknown => None,
+        };
+        let Some(current) = current else {
+            continue;
+        };
+        let current = current.into_iter().collect::<HashSet<_>>();
+        let Some(previous) = known.get_mut(id) else {
+            known.insert(id.clone(), current);
+            continue;
+        };
+
+        events.extend(
+            current
+                .difference(previous)
+                .map(|p| (id.clone(), p.clone(), PlayerEventKind::Join)),
+        );
+        events.extend(
+            previous
+                .difference(&current)
+                .map(|p| (id.clone(), p.clone(), PlayerEventKind::Leave)),
+        );
+        *previous = current;
+    }
+
+    events
+}
+
+async fn insert_samples(pool: &SqlitePool, snapshot: &StatusSnapshot) -> anyhow::Result<()> {
+    let now = unix_now();
+    let mut tx = pool.begin().await?;
+    for (id, status) in snapshot {
+        sqlx::query(
+            "INSERT INTO status_samples (server_id, sampled_at, health, players_online) VALUES (?, ?, ?, ?)",
+        )
+        .bind(id.as_str())
+        .bind(now)
+        .bind(status.health().to_string())
+        .bind(status.players_online().map(i64::from))
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn insert_player_events(
+    pool: &SqlitePool,
+    events: &[(SmolStr, SmolStr, PlayerEventKind)],
+) -> anyhow::Result<()> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let now = unix_now();
+    let mut tx = pool.begin().await?;
+    for (id, player, kind) in events {
+        let kind = match kind {
+            PlayerEventKind::Join => "join",
+            PlayerEventKind::Leave => "leave",
+        };
+        sqlx::query("INSERT INTO player_events (server_id, occurred_at, player, kind) VALUES (?, ?, ?, ?)")
+            .bind(id.as_str())
+            .bind(now)
+            .bind(player.as_str())
+            .bind(kind)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn prune(pool: &SqlitePool) -> anyhow::Result<()> {
+    let cutoff = unix_now() - RETENTION.as_secs() as i64;
+    sqlx::query("DELETE FROM status_samples WHERE sampled_at < ?")
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+    sqlx::query("DELETE FROM player_events WHERE occurred_at < ?")
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::source_query::{SourcePlayer, SourceQueryStatus};
+    use common::status::ServerStatus;
+    use HealthStatus::*;
+    use PlayerEventKind::*;
+
+    fn status(health: HealthStatus, players: &[&str]) -> ServerStatus {
+        ServerStatus::SourceQuery(SourceQueryStatus {
+            name: "Valheim".into(),
+            health,
+            url: SmolStr::default(),
+            game_password: SmolStr::default(),
+            server_name: SmolStr::default(),
+            game: SmolStr::default(),
+            map: SmolStr::default(),
+            version: SmolStr::default(),
+            player_count: players.len() as u8,
+            max_players: 10,
+            bots: 0,
+            vac: false,
+            players: players
+                .iter()
+                .map(|&name| SourcePlayer {
+                    name: name.into(),
+                    score: 0,
+                    duration: 0.0,
+                })
+                .collect(),
+            rules: Vec::new(),
+        })
+    }
+
+    fn snapshot(servers: &[(&str, HealthStatus, &[&str])]) -> StatusSnapshot {
+        servers
+            .iter()
+            .map(|&(id, health, players)| (id.into(), status(health, players)))
+            .collect()
+    }
+
+    /// Diffs each snapshot in turn and returns the events of each, sorted
+    fn diff_all(snapshots: &[StatusSnapshot]) -> Vec<Vec<(SmolStr, SmolStr, PlayerEventKind)>> {
+        let mut known = HashMap::new();
+        snapshots
+            .iter()
+            .map(|snapshot| {
+                let mut events = diff_players(&mut known, snapshot);
+                events.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+                events
+            })
+            .collect()
+    }
+
+    fn event(id: &str, player: &str, kind: PlayerEventKind) -> (SmolStr, SmolStr, PlayerEventKind) {
+        (id.into(), player.into(), kind)
+    }
+
+    #[test]
+    fn records_joins_and_leaves() {
+        let events = diff_all(&[
+            snapshot(&[("vh", Running, &[])]),
+            snapshot(&[("vh", Running, &["Alex", "Steve"])]),
+            snapshot(&[("vh", Running, &["Steve"])]),
+            snapshot(&[("vh", Running, &["Steve"])]),
+        ]);
+        assert_eq!(
+            events,
+            [
+                vec![],
+                vec![event("vh", "Alex", Join), event("vh", "Steve", Join)],
+                vec![event("vh", "Alex", Leave)],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn seeds_players_online_before_a_restart() {
+        let events = diff_all(&[
+            // Nothing is known before the first poll
+            snapshot(&[("vh", Unknown, &[])]),
+            snapshot(&[("vh", Running, &["Alex"])]),
+            snapshot(&[("vh", Running, &["Alex", "Steve"])]),
+        ]);
+        assert_eq!(events, [vec![], vec![], vec![event("vh", "Steve", Join)]]);
+    }
+
+    #[test]
+    fn skips_unknown_statuses() {
+        let events = diff_all(&[
+            snapshot(&[("vh", Running, &["Alex"])]),
+            snapshot(&[("vh", Unknown, &[])]),
+            snapshot(&[("vh", Running, &["Alex"])]),
+        ]);
+        assert_eq!(events, [vec![], vec![], vec![]]);
+    }
+
+    #[test]
+    fn offline_servers_have_no_players() {
+        let events = diff_all(&[
+            snapshot(&[("vh", Running, &["Alex"])]),
+            snapshot(&[("vh", Offline, &["Alex"])]),
+            snapshot(&[("vh", Running, &["Alex"])]),
+        ]);
+        assert_eq!(
+            events,
+            [vec![], vec![event("vh", "Alex", Leave)], vec![event("vh", "Alex", Join)]]
+        );
+    }
+
+    #[test]
+    fn removed_servers_lose_their_players() {
+        let events = diff_all(&[
+            snapshot(&[("vh", Running, &["Alex"]), ("cs", Running, &["Steve"])]),
+            snapshot(&[("cs", Running, &["Steve"])]),
+            snapshot(&[("vh", Running, &["Alex"]), ("cs", Running, &["Steve"])]),
+        ]);
+        assert_eq!(
+            events,
+            [vec![], vec![event("vh", "Alex", Leave)], vec![event("vh", "Alex", Join)]]
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use crate::metrics::METRICS;
+use crate::secret::Secret;
+use crate::servers::{StatusFetcher, UNKNOWN_TEXT};
+use crate::AppResult;
+use anyhow::anyhow;
+use common::minecraft::MinecraftStatus;
+use common::status::HealthStatus;
+use moka::future::Cache;
+use once_cell::sync::Lazy;
+use rcon::Connection;
+use serde::Deserialize;
+use smol_str::SmolStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+mod query;
+mod slp;
+
+const DEFAULT_PORT: u16 = 25565;
+const DEFAULT_QUERY_PORT: u16 = 25565;
+
+static CLIENTS: Lazy<Cache<MinecraftRcon, Arc<Mutex<Connection<TcpStream>>>>> = Lazy::new(|| {
+    Cache::builder()
+        .max_capacity(10)
+        .time_to_idle(Duration::from_secs(5 * 60))
+        .build()
+});
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct MinecraftConfig {
+    /// Address of the server as `host[:port]`. Port defaults to 25565
+    pub host: SmolStr,
+    /// Address of the Query listener as `host[:port]`. Port defaults to 25565
+    #[serde(default)]
+    pub query_host: Option<SmolStr>,
+    #[serde(default)]
+    pub rcon: Option<MinecraftRcon>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct MinecraftRcon {
+    /// Address of the RCON listener as `host:port`
+    pub host: SmolStr,
+    pub password: Secret,
+}
+
+impl MinecraftRcon {
+    async fn connect(&self) -> AppResult<Arc<Mutex<Connection<TcpStream>>>> {
+        let conn = Connection::<TcpStream>::builder()
+            .enable_minecraft_quirks(true)
+            .connect(&*self.host, self.password.secret())
+            .await?;
+
+        Ok(Arc::new(Mutex::new(conn)))
+    }
+
+    async fn cmd(&self, cmd: &str) -> AppResult<String> {
+        let mutex = CLIENTS
+            .try_get_with_by_ref(self, self.connect())
+            .await
+            .map_err(|err| Arc::try_unwrap(err).unwrap_or_else(|e| anyhow!("{e}").into()))?;
+
+        let mut conn = mutex.lock().await;
+        match conn.cmd(cmd).await {
+            Ok(resp) => Ok(resp),
+            Err(err) => {
+                // Connection is most likely dead (e.g. server restarted), reconnect next time
+                CLIENTS.invalidate(self).await;
+                Err(err.into())
+            }
+        }
+    }
+}
+
+/// Splits `host[:port]`. IPv6 addresses need brackets to have a port, e.g. `[::1]:25575`.
+fn split_host_port(address: &str, default_port: u16) -> AppResult<(&str, u16)> {
+    if let Some(rest) = address.strip_prefix('[') {
+        let (host, rest) = rest
+            .split_once(']')
+            .ok_or_else(|| anyhow!("missing ] in {address}"))?;
+        return match rest.strip_prefix(':') {
+            Some(port) => Ok((host, port.parse()?)),
+            None if rest.is_empty() => Ok((host, default_port)),
+            None => Err(anyhow!("unexpected {rest:?} after the host in {address}").into()),
+        };
+    }
+
+    match address.rsplit_once(':') {
+        // An IPv6 address without brackets can't have a port
+        Some((host, _)) if host.contains(':') => Ok((address, default_port)),
+        Some((host, port)) => Ok((host, port.parse()?)),
+        None => Ok((address, default_port)),
+    }
+}
+
+impl MinecraftConfig {
+    async fn populate_status(&self, status: &mut MinecraftStatus) -> AppResult<()> {
+        let (host, port) = split_host_port(&self.host, DEFAULT_PORT)?;
+        let response = slp::ping(host, port).await?;
+
+        status.health = HealthStatus::Running;
+        status.motd = response.motd().into();
+        status.version_name = response.version.name.into();
+        status.protocol_version = response.version.protocol;
+        if let Some(players) = response.players {
+            status.players_online = players.online;
+            status.players_max = players.max;
+            status.player_sample = players.sample.into_iter().map(|p| p.name.into()).collect();
+        }
+        status.favicon = response.favicon;
+
+        Ok(())
+    }
+
+    async fn populate_query(&self, query_host: &str, status: &mut MinecraftStatus) -> AppResult<()> {
+        let (host, port) = split_host_port(query_host, DEFAULT_QUERY_PORT)?;
+        let stat = query::full_stat(host, port).await?;
+
+        status.world = stat.world().map(SmolStr::from);
+        let (software, plugins) = stat.software_and_plugins();
+        status.server_software = software.map(SmolStr::from);
+        status.plugins = plugins.into_iter().map(SmolStr::from).collect();
+        status.player_list = Some(stat.players.into_iter().map(SmolStr::from).collect());
+
+        Ok(())
+    }
+
+    async fn populate_rcon(&self, rcon: &MinecraftRcon, status: &mut MinecraftStatus) -> AppResult<()> {
+        if status.player_list.is_none() {
+            let list_text = rcon.cmd("list").await?;
+            status.player_list = Some(parse_player_list(&list_text));
+        }
+
+        status.tps = parse_tps(&rcon.cmd("tps").await?);
+        if status.tps.is_none() {
+            // Vanilla doesn't have /tps, but 1.20.3+ reports the tick time
+            status.tps = parse_tick_query(&rcon.cmd("tick query").await?);
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the output of `list`, e.g. `There are 2 of a max of 20 players online: Alex, Steve`
+fn parse_player_list(text: &str) -> Vec<SmolStr> {
+    let text = strip_formatting_codes(text);
+    let Some((_, names)) = text.split_once(':') else {
+        return Vec::new();
+    };
+
+    names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(SmolStr::from)
+        .collect()
+}
+
+/// Parses the output of Spigot/Paper's `tps`, e.g. `TPS from last 1m, 5m, 15m: 20.0, 20.0, 20.0`
+fn parse_tps(text: &str) -> Option<f32> {
+    let text = strip_formatting_codes(text);
+    let (_, values) = text.rsplit_once(':')?;
+    values
+        .split(',')
+        .next()?
+        .trim()
+        // Paper marks values capped at 20 with a `*`
+        .trim_start_matches('*')
+        .parse()
+        .ok()
+}
+
+/// Parses the output of vanilla `tick query`, which contains a line like
+/// `Average time per tick: 2.3ms (Target: 50.0ms)`
+fn parse_tick_query(text: &str) -> Option<f32> {
+    let text = strip_formatting_codes(text);
+    let (_, rest) = text.split_once("Average time per tick: ")?;
+    let mspt = rest.split("ms").next()?.trim().parse::<f32>().ok()?;
+    Some((1000.0 / mspt).min(20.0))
+}
+
+/// Removes legacy `§x` formatting codes, which are still common in MOTDs and command output
+fn strip_formatting_codes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+impl StatusFetcher for MinecraftConfig {
+    type Status = MinecraftStatus;
+
+    fn unknown_status(&self) -> MinecraftStatus {
+        MinecraftStatus {
+            name: self.host.clone(),
+            health: HealthStatus::Unknown,
+            url: SmolStr::default(),
+            motd: SmolStr::default(),
+            version_name: UNKNOWN_TEXT,
+            protocol_version: 0,
+            players_online: 0,
+            players_max: 0,
+            player_sample: Vec::new(),
+            favicon: None,
+            player_list: None,
+            world: None,
+            server_software: None,
+            plugins: Vec::new(),
+            tps: None,
+        }
+    }
+
+    async fn fetch_server_status(&self) -> MinecraftStatus {
+        let mut status = self.unknown_status();
+
+        if let Err(e) = self.populate_status(&mut status).await {
+            tracing::error!("Failed to fetch server status: {}", e);
+            // Unlike RCON there's no auth that could fail, so no answer means it's down
+            status.health = HealthStatus::Offline;
+            return status;
+        }
+
+        if let Some(query_host) = &self.query_host
+            && let Err(e) = self.populate_query(query_host, &mut status).await
+        {
+            tracing::warn!("Failed to query server {}: {}", query_host, e);
+        }
+
+        if let Some(rcon) = &self.rcon
+            && let Err(e) = self.populate_rcon(rcon, &mut status).await
+        {
+            METRICS.rcon_error("minecraft");
+            tracing::warn!("Failed to fetch status over RCON {}: {}", rcon.host, e);
+        }
+
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_host_and_port() {
+        let cases = [
+            ("mc.example.com", Some(("mc.example.com", 25565))),
+            ("mc.example.com:25566", Some(("mc.example.com", 25566))),
+            ("10.0.0.5:25575", Some(("10.0.0.5", 25575))),
+            ("[::1]:25575", Some(("::1", 25575))),
+            ("[2001:db8::1]", Some(("2001:db8::1", 25565))),
+            ("2001:db8::1", Some(("2001:db8::1", 25565))),
+            ("mc.example.com:rcon", None),
+            ("mc.example.com:70000", None),
+            ("[::1", None),
+            ("[::1]25575", None),
+        ];
+        for (address, expected) in cases {
+            assert_eq!(split_host_port(address, DEFAULT_PORT).ok(), expected, "{address:?}");
+        }
+    }
+
+    #[test]
+    fn parses_player_lists() {
+        let cases: [(&str, &[&str]); 6] = [
+            ("There are 2 of a max of 20 players online: Alex, Steve", &["Alex", "Steve"]),
+            ("There are 0 of a max of 20 players online: ", &[]),
+            // Before 1.13 the names were on their own line
+            ("There are 1/20 players online:\nnotch", &["notch"]),
+            // EssentialsX groups players and colours everything
+            (
+                "\u{a7}6There are \u{a7}c2\u{a7}6 out of maximum \u{a7}c20\u{a7}6 players online.\n\
+                 \u{a7}6default\u{a7}r: \u{a7}fAlex\u{a7}r, \u{a7}fSteve",
+                &["Alex", "Steve"],
+            ),
+            ("Unknown or incomplete command, see below for error", &[]),
+            ("", &[]),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_player_list(text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn parses_tps() {
+        let paper = "\u{a7}6TPS from last 1m, 5m, 15m: \u{a7}a*20.0, \u{a7}a*20.0, \u{a7}a*20.0";
+        let lagging = "\u{a7}6TPS from last 1m, 5m, 15m: \u{a7}a19.98, \u{a7}a19.99, \u{a7}e17.5";
+        let cases = [
+            (paper, Some(20.0)),
+            (lagging, Some(19.98)),
+            ("TPS from last 1m, 5m, 15m: 12.3, 15.0, 18.1", Some(12.3)),
+            // Forge
+            ("Overall: Mean tick time: 2.151 ms. Mean TPS: 20.000", Some(20.0)),
+            ("Unknown or incomplete command, see below for error\ntps<--[HERE]", None),
+            ("", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_tps(text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn parses_tick_query() {
+        let normal = "The game is running normally\n\
+            Target tick rate: 20.0 per second.\n\
+            Average time per tick: 2.3ms (Target: 50.0ms)\n\
+            Percentiles: P50: 2.1ms P95: 3.4ms P99: 5.0ms, sample: 100";
+        let overloaded = "The game is running normally\n\
+            Target tick rate: 20.0 per second.\n\
+            Average time per tick: 80.0ms (Target: 50.0ms)";
+        let cases = [
+            (normal, Some(20.0)),
+            (overloaded, Some(12.5)),
+            ("Average time per tick: lots (Target: 50.0ms)", None),
+            ("Unknown or incomplete command, see below for error", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_tick_query(text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn strips_formatting_codes() {
+        let text = "\u{a7}aGreen \u{a7}lbold\u{a7}r text";
+        assert_eq!(strip_formatting_codes(text), "Green bold text");
+        assert_eq!(strip_formatting_codes("trailing \u{a7}"), "trailing ");
+        assert_eq!(strip_formatting_codes("no codes"), "no codes");
+    }
+}
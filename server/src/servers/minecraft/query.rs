@@ -0,0 +1,237 @@
+//! Client for the UDP GameSpy4 "full stat" Query protocol.
+//! Requires `enable-query=true` in server.properties.
+//! https://minecraft.wiki/w/Query
+
+use anyhow::{bail, Context};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const TYPE_HANDSHAKE: u8 = 9;
+const TYPE_STAT: u8 = 0;
+
+/// Constant padding before the key/value section of a full stat response
+const KV_PADDING: &[u8] = b"splitnum\0\x80\0";
+/// Constant padding before the player section of a full stat response
+const PLAYER_PADDING: &[u8] = b"\x01player_\0\0";
+
+#[derive(Debug)]
+pub struct FullStat {
+    pub values: HashMap<String, String>,
+    pub players: Vec<String>,
+}
+
+impl FullStat {
+    pub fn world(&self) -> Option<&str> {
+        self.values.get("map").map(String::as_str)
+    }
+
+    /// Splits the `plugins` value, which looks like `Paper on Bukkit 1.21: PluginA 1.0; PluginB 2.3`
+    /// on modded servers and is empty on vanilla ones.
+    pub fn software_and_plugins(&self) -> (Option<&str>, Vec<&str>) {
+        let Some(plugins) = self.values.get("plugins").filter(|p| !p.is_empty()) else {
+            return (None, Vec::new());
+        };
+
+        match plugins.split_once(": ") {
+            Some((software, list)) => (
+                Some(software.trim()),
+                list.split("; ")
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .collect(),
+            ),
+            None => (Some(plugins.trim()), Vec::new()),
+        }
+    }
+}
+
+pub async fn full_stat(host: &str, port: u16) -> anyhow::Result<FullStat> {
+    tokio::time::timeout(TIMEOUT, full_stat_inner(host, port))
+        .await
+        .context("timed out waiting for query response")?
+}
+
+async fn full_stat_inner(host: &str, port: u16) -> anyhow::Result<FullStat> {
+    let address = tokio::net::lookup_host((host, port))
+        .await?
+        .next()
+        .context("failed to resolve query address")?;
+    let local: SocketAddr = if address.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" }.parse()?;
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(address).await?;
+
+    // Only the lower 4 bits of each byte are used by the server
+    let session_id = std::process::id() as i32 & 0x0F0F_0F0F;
+    let mut buf = vec![0; 64 * 1024];
+
+    let mut request = Vec::with_capacity(15);
+    request.extend_from_slice(&MAGIC);
+    request.push(TYPE_HANDSHAKE);
+    request.extend_from_slice(&session_id.to_be_bytes());
+    socket.send(&request).await?;
+
+    let len = socket.recv(&mut buf).await?;
+    let token = parse_header(&buf[..len], TYPE_HANDSHAKE, session_id)?;
+    let token = read_cstr(token)
+        .0
+        .trim()
+        .parse::<i32>()
+        .context("invalid challenge token")?;
+
+    request.truncate(MAGIC.len());
+    request.push(TYPE_STAT);
+    request.extend_from_slice(&session_id.to_be_bytes());
+    request.extend_from_slice(&token.to_be_bytes());
+    // Padding requests a full stat instead of a basic one
+    request.extend_from_slice(&[0; 4]);
+    socket.send(&request).await?;
+
+    let len = socket.recv(&mut buf).await?;
+    parse_full_stat(parse_header(&buf[..len], TYPE_STAT, session_id)?)
+}
+
+/// Parses the body of a full stat response, after the header
+fn parse_full_stat(body: &[u8]) -> anyhow::Result<FullStat> {
+    let mut body = body
+        .strip_prefix(KV_PADDING)
+        .context("malformed full stat response")?;
+
+    let mut values = HashMap::new();
+    loop {
+        let (key, rest) = read_cstr(body);
+        body = rest;
+        if key.is_empty() {
+            break;
+        }
+        let (value, rest) = read_cstr(body);
+        body = rest;
+        values.insert(key, value);
+    }
+
+    let mut body = body
+        .strip_prefix(PLAYER_PADDING)
+        .context("malformed full stat player section")?;
+
+    let mut players = Vec::new();
+    loop {
+        let (player, rest) = read_cstr(body);
+        body = rest;
+        if player.is_empty() {
+            break;
+        }
+        players.push(player);
+    }
+
+    Ok(FullStat { values, players })
+}
+
+fn parse_header(packet: &[u8], expected_type: u8, session_id: i32) -> anyhow::Result<&[u8]> {
+    if packet.len() < 5 {
+        bail!("query response too short");
+    }
+    if packet[0] != expected_type {
+        bail!("unexpected query response type {}", packet[0]);
+    }
+    if packet[1..5] != session_id.to_be_bytes() {
+        bail!("query response for a different session");
+    }
+    Ok(&packet[5..])
+}
+
+/// Reads a null terminated string, returning it and the remaining bytes
+fn read_cstr(data: &[u8]) -> (String, &[u8]) {
+    match data.iter().position(|&b| b == 0) {
+        Some(end) => (
+            String::from_utf8_lossy(&data[..end]).into_owned(),
+            &data[end + 1..],
+        ),
+        None => (String::from_utf8_lossy(data).into_owned(), &[]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION_ID: i32 = 0x0102_0304;
+
+    /// A full stat body as sent by a vanilla server, after the header
+    const FULL_STAT: &[u8] = b"splitnum\0\x80\0hostname\0A Minecraft Server\0gametype\0SMP\0\
+        game_id\0MINECRAFT\0version\x001.21.4\0plugins\0\0map\0world\0numplayers\x002\0\
+        maxplayers\x0020\0hostport\x0025565\0hostip\x00127.0.0.1\0\0\x01player_\0\0Alex\0Steve\0\0";
+
+    #[test]
+    fn parses_header() {
+        let packet = b"\x09\x01\x02\x03\x049513307\0";
+        assert_eq!(parse_header(packet, TYPE_HANDSHAKE, SESSION_ID).unwrap(), b"9513307\0");
+
+        let cases: &[(&str, &[u8])] = &[
+            ("empty", b""),
+            ("truncated", b"\x09\x01\x02\x03"),
+            ("wrong type", b"\x00\x01\x02\x03\x04"),
+            ("other session", b"\x09\x01\x02\x03\x05"),
+        ];
+        for (name, case) in cases {
+            assert!(parse_header(case, TYPE_HANDSHAKE, SESSION_ID).is_err(), "accepted {name}");
+        }
+    }
+
+    #[test]
+    fn parses_full_stat() {
+        let stat = parse_full_stat(FULL_STAT).unwrap();
+        assert_eq!(stat.values["hostname"], "A Minecraft Server");
+        assert_eq!(stat.values["numplayers"], "2");
+        assert_eq!(stat.world(), Some("world"));
+        assert_eq!(stat.players, ["Alex", "Steve"]);
+        assert_eq!(stat.software_and_plugins(), (None, Vec::new()));
+    }
+
+    #[test]
+    fn parses_empty_player_list() {
+        let body = b"splitnum\0\x80\0map\0world\0\0\x01player_\0\0\0";
+        let stat = parse_full_stat(body).unwrap();
+        assert_eq!(stat.world(), Some("world"));
+        assert!(stat.players.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_full_stat() {
+        let player_section = FULL_STAT.len() - b"\x01player_\0\0Alex\0Steve\0\0".len();
+        let cases: &[(&str, &[u8])] = &[
+            ("empty", b""),
+            ("missing padding", &FULL_STAT[KV_PADDING.len()..]),
+            ("truncated values", &FULL_STAT[..KV_PADDING.len() + 20]),
+            ("missing player section", &FULL_STAT[..player_section]),
+        ];
+        for (name, case) in cases {
+            assert!(parse_full_stat(case).is_err(), "accepted {name}");
+        }
+    }
+
+    #[test]
+    fn splits_plugins() {
+        let stat = |plugins: &str| FullStat {
+            values: HashMap::from([("plugins".to_owned(), plugins.to_owned())]),
+            players: Vec::new(),
+        };
+        let cases: &[(&str, Option<&str>, &[&str])] = &[
+            ("", None, &[]),
+            ("Paper on Bukkit 1.21", Some("Paper on Bukkit 1.21"), &[]),
+            ("Paper on Bukkit 1.21: ", Some("Paper on Bukkit 1.21"), &[]),
+            (
+                "Paper on Bukkit 1.21: WorldEdit 7.3; LuckPerms 5.4",
+                Some("Paper on Bukkit 1.21"),
+                &["WorldEdit 7.3", "LuckPerms 5.4"],
+            ),
+        ];
+        for &(plugins, software, list) in cases {
+            let expected = (software, list.to_vec());
+            assert_eq!(stat(plugins).software_and_plugins(), expected, "{plugins:?}");
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Minimal client for the Java Edition Server List Ping protocol.
+//! https://minecraft.wiki/w/Java_Edition_protocol/Server_List_Ping
+
+use crate::servers::minecraft::strip_formatting_codes;
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Protocol version sent in the handshake. -1 is the convention for "just pinging"
+const PING_PROTOCOL_VERSION: i32 = -1;
+
+/// Responses with favicons are usually a few dozen KiB, anything past this is bogus
+const MAX_PACKET_LENGTH: usize = 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct PingResponse {
+    pub version: PingVersion,
+    pub players: Option<PingPlayers>,
+    #[serde(default)]
+    pub description: serde_json::Value,
+    pub favicon: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PingVersion {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PingPlayers {
+    pub max: u32,
+    pub online: u32,
+    #[serde(default)]
+    pub sample: Vec<PingPlayer>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PingPlayer {
+    pub name: String,
+}
+
+impl PingResponse {
+    /// Flattens the description chat component into plain text
+    pub fn motd(&self) -> String {
+        let mut text = String::new();
+        flatten_chat(&self.description, &mut text);
+        strip_formatting_codes(&text)
+    }
+}
+
+pub async fn ping(host: &str, port: u16) -> anyhow::Result<PingResponse> {
+    tokio::time::timeout(TIMEOUT, ping_inner(host, port))
+        .await
+        .context("timed out waiting for server list ping")?
+}
+
+async fn ping_inner(host: &str, port: u16) -> anyhow::Result<PingResponse> {
+    let mut stream = TcpStream::connect((host, port))
+        .await
+        .context("failed to connect to server")?;
+
+    // Handshake with next state = 1 (status)
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, 0x00);
+    write_varint(&mut handshake, PING_PROTOCOL_VERSION);
+    write_string(&mut handshake, host);
+    handshake.extend_from_slice(&port.to_be_bytes());
+    write_varint(&mut handshake, 1);
+    write_packet(&mut stream, &handshake).await?;
+
+    // Status request has no fields
+    write_packet(&mut stream, &[0x00]).await?;
+
+    let length = read_varint(&mut stream).await?;
+    let length = usize::try_from(length).context("negative packet length")?;
+    if length > MAX_PACKET_LENGTH {
+        bail!("status response too large ({length} bytes)");
+    }
+
+    let mut packet = vec![0; length];
+    stream.read_exact(&mut packet).await?;
+    parse_status(&packet).await
+}
+
+/// Parses the body of a status response packet, after its length
+async fn parse_status(mut packet: &[u8]) -> anyhow::Result<PingResponse> {
+    let packet_id = read_varint(&mut packet).await?;
+    if packet_id != 0x00 {
+        bail!("unexpected packet id {packet_id:#x} in status response");
+    }
+
+    let json_length = read_varint(&mut packet).await?;
+    let json = usize::try_from(json_length)
+        .ok()
+        .and_then(|len| packet.get(..len))
+        .context("status response string length out of bounds")?;
+
+    serde_json::from_slice(json).context("failed to deserialize status response")
+}
+
+async fn write_packet(stream: &mut TcpStream, data: &[u8]) -> anyhow::Result<()> {
+    let mut packet = Vec::with_capacity(data.len() + 5);
+    write_varint(&mut packet, data.len() as i32);
+    packet.extend_from_slice(data);
+    stream.write_all(&packet).await?;
+    Ok(())
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buf.push(value as u8);
+            return;
+        }
+        buf.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, value: &str) {
+    write_varint(buf, value.len() as i32);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+async fn read_varint<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<i32> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte = reader.read_u8().await?;
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    bail!("VarInt is too big")
+}
+
+fn flatten_chat(component: &serde_json::Value, out: &mut String) {
+    match component {
+        serde_json::Value::String(text) => out.push_str(text),
+        serde_json::Value::Array(parts) => parts.iter().for_each(|part| flatten_chat(part, out)),
+        serde_json::Value::Object(obj) => {
+            if let Some(text) = obj.get("text") {
+                flatten_chat(text, out);
+            }
+            if let Some(extra) = obj.get("extra") {
+                flatten_chat(extra, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_varints() {
+        let cases: &[(&[u8], i32)] = &[
+            (&[0x00], 0),
+            (&[0x01], 1),
+            (&[0x7f], 127),
+            (&[0x80, 0x01], 128),
+            (&[0xff, 0x01], 255),
+            (&[0xdd, 0xc7, 0x01], 25565),
+            (&[0xff, 0xff, 0xff, 0xff, 0x07], i32::MAX),
+            (&[0xff, 0xff, 0xff, 0xff, 0x0f], -1),
+            (&[0x80, 0x80, 0x80, 0x80, 0x08], i32::MIN),
+        ];
+        for &(mut bytes, expected) in cases {
+            let encoded = bytes;
+            assert_eq!(read_varint(&mut bytes).await.unwrap(), expected, "{encoded:02x?}");
+            assert!(bytes.is_empty(), "{encoded:02x?} left bytes behind");
+
+            let mut written = Vec::new();
+            write_varint(&mut written, expected);
+            assert_eq!(written, encoded);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_varints() {
+        let cases: &[&[u8]] = &[
+            // Truncated
+            &[],
+            &[0x80],
+            &[0xff, 0xff, 0xff, 0xff],
+            // Longer than five bytes
+            &[0x80, 0x80, 0x80, 0x80, 0x80, 0x01],
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        ];
+        for &case in cases {
+            let mut bytes = case;
+            assert!(read_varint(&mut bytes).await.is_err(), "accepted {case:02x?}");
+        }
+    }
+
+    /// A status packet body with the given JSON
+    fn status_packet(json: &str) -> Vec<u8> {
+        let mut packet = vec![0x00];
+        write_string(&mut packet, json);
+        packet
+    }
+
+    #[tokio::test]
+    async fn parses_status() {
+        let json = r#"{
+            "version": {"name": "1.21.4", "protocol": 769},
+            "players": {"max": 20, "online": 2, "sample": [{"name": "Alex", "id": "0"}]},
+            "description": {"text": "A ", "extra": [{"text": "\u00a7aMinecraft"}, " server"]},
+            "favicon": "data:image/png;base64,AA=="
+        }"#;
+        let response = parse_status(&status_packet(json)).await.unwrap();
+        assert_eq!(response.version.name, "1.21.4");
+        assert_eq!(response.version.protocol, 769);
+        let players = response.players.as_ref().unwrap();
+        assert_eq!((players.online, players.max), (2, 20));
+        assert_eq!(players.sample[0].name, "Alex");
+        assert_eq!(response.motd(), "A Minecraft server");
+        assert_eq!(response.favicon.as_deref(), Some("data:image/png;base64,AA=="));
+    }
+
+    #[tokio::test]
+    async fn parses_minimal_status() {
+        let json = r#"{"version": {"name": "Paper 1.20", "protocol": 763}, "description": "Hi"}"#;
+        let response = parse_status(&status_packet(json)).await.unwrap();
+        assert!(response.players.is_none());
+        assert!(response.favicon.is_none());
+        assert_eq!(response.motd(), "Hi");
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_status_packets() {
+        let json = r#"{"version": {"name": "1.21", "protocol": 767}}"#;
+        let packet = status_packet(json);
+
+        let mut wrong_id = packet.clone();
+        wrong_id[0] = 0x01;
+        let mut negative_length = vec![0x00];
+        write_varint(&mut negative_length, -1);
+        let mut bad_length = vec![0x00, 0xff, 0xff, 0xff, 0xff, 0xff];
+        bad_length.extend_from_slice(json.as_bytes());
+        let not_json = status_packet("not json");
+        let no_version = status_packet("{}");
+
+        let cases: Vec<(&str, &[u8])> = vec![
+            ("empty", &[]),
+            ("wrong packet id", &wrong_id),
+            ("truncated string", &packet[..packet.len() - 1]),
+            ("truncated length", &packet[..1]),
+            ("negative length", &negative_length),
+            ("oversized length", &bad_length),
+            ("not JSON", &not_json),
+            ("missing version", &no_version),
+        ];
+        for (name, case) in cases {
+            assert!(parse_status(case).await.is_err(), "accepted {name}");
+        }
+    }
+}
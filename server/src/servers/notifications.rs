@@ -0,0 +1,406 @@
+use crate::secret::Secret;
+use crate::servers::UNKNOWN_TEXT;
+use anyhow::bail;
+use common::status::{HealthStatus, ServerStatus};
+use moka::future::Cache;
+use once_cell::sync::Lazy;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use smol_str::SmolStr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+/// Messages waiting on a rate limited webhook before new ones are dropped
+const QUEUE_SIZE: usize = 20;
+
+static HTTP_CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+/// One delivery task per webhook, shared by every server posting to it.
+/// Evicting a sender lets its task finish the queue and exit.
+static WEBHOOKS: Lazy<Cache<WebhookConfig, mpsc::Sender<String>>> = Lazy::new(|| {
+    Cache::builder()
+        .max_capacity(20)
+        .time_to_idle(Duration::from_secs(60 * 60))
+        .build()
+});
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct WebhookConfig {
+    /// Discord webhook URL. Contains the webhook token, so it is kept secret.
+    pub url: Secret,
+    /// Minimum seconds between two messages to this webhook
+    #[serde(default = "default_min_interval_secs")]
+    pub min_interval_secs: u64,
+}
+
+fn default_min_interval_secs() -> u64 {
+    5
+}
+
+// https://discord.com/developers/docs/topics/rate-limits#exceeding-a-rate-limit
+#[derive(Debug, Deserialize)]
+struct RateLimited {
+    retry_after: f64,
+}
+
+/// Remembers the last observed state of a server so only changes are announced
+#[derive(Debug, Default)]
+pub(super) struct StatusTracker {
+    health: Option<HealthStatus>,
+    players: Option<u32>,
+    version: Option<SmolStr>,
+}
+
+impl StatusTracker {
+    /// Returns a message for every noteworthy change since the previous call. Nothing is
+    /// reported on the first call, so restarting the poller doesn't repeat old news.
+    pub fn observe(&mut self, name: &str, status: &ServerStatus) -> Vec<String> {
+        let health = status.health();
+        // A failed poll says nothing about the server, wait for a real answer
+        if health == HealthStatus::Unknown {
+            return Vec::new();
+        }
+
+        let mut messages = Vec::new();
+        if let Some(previous) = self.health.replace(health)
+            && previous != health
+        {
+            messages.push(match health {
+                HealthStatus::Running => format!("**{name}** is up"),
+                HealthStatus::Starting => format!("**{name}** is starting"),
+                HealthStatus::Offline => format!("**{name}** went offline"),
+                HealthStatus::Unknown => unreachable!(),
+            });
+        }
+
+        if health != HealthStatus::Running {
+            // Nobody can be playing, so the next join after it's back counts as the first
+            self.players = Some(0);
+            return messages;
+        }
+
+        let players = status.players_online();
+        if self.players == Some(0) && players.is_some_and(|p| p > 0) {
+            let who = match status.player_names() {
+                Some(names) if !names.is_empty() => names.join(", "),
+                _ => "Someone".to_owned(),
+            };
+            messages.push(format!("{who} joined **{name}**"));
+        }
+        self.players = players;
+
+        if let Some(version) = status.version().filter(|v| **v != UNKNOWN_TEXT)
+            && let Some(previous) = self.version.replace(version.clone())
+            && previous != *version
+        {
+            messages.push(format!("**{name}** updated from {previous} to {version}"));
+        }
+
+        messages
+    }
+}
+
+/// Queues `messages` on every webhook. Messages are dropped if a webhook is too far behind.
+pub(super) async fn notify(webhooks: &[WebhookConfig], messages: &[String]) {
+    for webhook in webhooks {
+        let sender = WEBHOOKS
+            .get_with_by_ref(webhook, async {
+                let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+                tokio::spawn(deliver(webhook.clone(), rx));
+                tx
+            })
+            .await;
+
+        for message in messages {
+            match sender.try_send(message.clone()) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!("Webhook queue full, dropping notification: {}", message);
+                }
+                Err(TrySendError::Closed(_)) => {
+                    tracing::error!("Webhook delivery task stopped unexpectedly");
+                    WEBHOOKS.invalidate(webhook).await;
+                }
+            }
+        }
+    }
+}
+
+async fn deliver(webhook: WebhookConfig, mut messages: mpsc::Receiver<String>) {
+    while let Some(message) = messages.recv().await {
+        if let Err(e) = post(&webhook, &message).await {
+            tracing::warn!("Failed to post notification to webhook: {:#}", e);
+        }
+        tokio::time::sleep(Duration::from_secs(webhook.min_interval_secs)).await;
+    }
+}
+
+async fn post(webhook: &WebhookConfig, content: &str) -> anyhow::Result<()> {
+    let body = serde_json::json!({
+        "content": content,
+        // Player names are user controlled, never let them ping anyone
+        "allowed_mentions": { "parse": [] },
+    });
+
+    // Retry once if Discord asks us to slow down
+    for _ in 0..2 {
+        let resp = HTTP_CLIENT
+            .post(webhook.url.secret().as_str())
+            .json(&body)
+            .send()
+            .await
+            // The URL contains the token, keep it out of the logs
+            .map_err(|e| e.without_url())?;
+
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            let limit = resp.json::<RateLimited>().await?;
+            tokio::time::sleep(Duration::from_secs_f64(limit.retry_after.clamp(0.0, 60.0))).await;
+            continue;
+        }
+
+        resp.error_for_status().map_err(|e| e.without_url())?;
+        return Ok(());
+    }
+
+    bail!("still rate limited after retrying")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::response::IntoResponse;
+    use axum::{Json, Router};
+    use common::source_query::{SourcePlayer, SourceQueryStatus};
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+    use tokio::time::Instant;
+
+    fn status(health: HealthStatus, players: &[&str], version: &str) -> ServerStatus {
+        ServerStatus::SourceQuery(SourceQueryStatus {
+            name: "Valheim".into(),
+            health,
+            url: SmolStr::default(),
+            game_password: SmolStr::default(),
+            server_name: SmolStr::default(),
+            game: SmolStr::default(),
+            map: SmolStr::default(),
+            version: version.into(),
+            player_count: players.len() as u8,
+            max_players: 10,
+            bots: 0,
+            vac: false,
+            players: players
+                .iter()
+                .map(|&name| SourcePlayer {
+                    name: name.into(),
+                    score: 0,
+                    duration: 0.0,
+                })
+                .collect(),
+            rules: Vec::new(),
+        })
+    }
+
+    /// Feeds the statuses to a fresh tracker and returns the messages of each
+    fn observe_all(statuses: &[ServerStatus]) -> Vec<Vec<String>> {
+        let mut tracker = StatusTracker::default();
+        statuses.iter().map(|status| tracker.observe("Valheim", status)).collect()
+    }
+
+    #[test]
+    fn first_observation_is_silent() {
+        let messages = observe_all(&[status(HealthStatus::Running, &["Alice"], "0.217")]);
+        assert_eq!(messages, [Vec::<String>::new()]);
+    }
+
+    #[test]
+    fn announces_state_changes() {
+        let messages = observe_all(&[
+            status(HealthStatus::Running, &[], "0.217"),
+            status(HealthStatus::Offline, &[], ""),
+            status(HealthStatus::Starting, &[], ""),
+            status(HealthStatus::Running, &[], "0.217"),
+            status(HealthStatus::Running, &[], "0.217"),
+        ]);
+        assert_eq!(
+            messages,
+            [
+                vec![],
+                vec!["**Valheim** went offline".to_owned()],
+                vec!["**Valheim** is starting".to_owned()],
+                vec!["**Valheim** is up".to_owned()],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_unknown_states() {
+        let messages = observe_all(&[
+            status(HealthStatus::Running, &[], "0.217"),
+            status(HealthStatus::Unknown, &[], ""),
+            status(HealthStatus::Running, &[], "0.217"),
+            status(HealthStatus::Unknown, &[], ""),
+            status(HealthStatus::Offline, &[], ""),
+        ]);
+        assert_eq!(
+            messages,
+            [vec![], vec![], vec![], vec![], vec!["**Valheim** went offline".to_owned()]]
+        );
+    }
+
+    #[test]
+    fn announces_joins_after_empty() {
+        let messages = observe_all(&[
+            status(HealthStatus::Running, &[], "0.217"),
+            status(HealthStatus::Running, &["Alice", "Bob"], "0.217"),
+            // Only the first join after the server was empty is announced
+            status(HealthStatus::Running, &["Alice", "Bob", "Carol"], "0.217"),
+            status(HealthStatus::Running, &[], "0.217"),
+            status(HealthStatus::Running, &["Carol"], "0.217"),
+        ]);
+        assert_eq!(
+            messages,
+            [
+                vec![],
+                vec!["Alice, Bob joined **Valheim**".to_owned()],
+                vec![],
+                vec![],
+                vec!["Carol joined **Valheim**".to_owned()],
+            ]
+        );
+    }
+
+    #[test]
+    fn first_join_after_restart_counts() {
+        let mut online = status(HealthStatus::Running, &[], "0.217");
+        // Some games only report a count
+        if let ServerStatus::SourceQuery(status) = &mut online {
+            status.player_count = 2;
+        }
+        let messages = observe_all(&[
+            status(HealthStatus::Running, &["Alice"], "0.217"),
+            status(HealthStatus::Offline, &[], ""),
+            online,
+        ]);
+        assert_eq!(
+            messages,
+            [
+                vec![],
+                vec!["**Valheim** went offline".to_owned()],
+                vec!["**Valheim** is up".to_owned(), "Someone joined **Valheim**".to_owned()],
+            ]
+        );
+    }
+
+    #[test]
+    fn announces_updates() {
+        let messages = observe_all(&[
+            status(HealthStatus::Running, &[], "0.217"),
+            status(HealthStatus::Running, &[], &UNKNOWN_TEXT),
+            status(HealthStatus::Running, &[], "0.218"),
+        ]);
+        assert_eq!(
+            messages,
+            [vec![], vec![], vec!["**Valheim** updated from 0.217 to 0.218".to_owned()]]
+        );
+    }
+
+    /// Requests a [MockWebhook] received, with when they arrived
+    type Received = Arc<Mutex<Vec<(Instant, Value)>>>;
+
+    /// Local stand-in for a Discord webhook that rate limits the first `rate_limited` requests
+    struct MockWebhook {
+        received: Received,
+        config: WebhookConfig,
+    }
+
+    impl MockWebhook {
+        async fn start(rate_limited: usize, min_interval_secs: u64) -> Self {
+            async fn receive(
+                State((received, rate_limited)): State<(Received, usize)>,
+                Json(body): Json<Value>,
+            ) -> impl IntoResponse {
+                let mut received = received.lock().unwrap();
+                received.push((Instant::now(), body));
+                if received.len() <= rate_limited {
+                    let limit = json!({ "message": "Rate limited", "retry_after": 0.2 });
+                    (StatusCode::TOO_MANY_REQUESTS, Json(limit)).into_response()
+                } else {
+                    StatusCode::NO_CONTENT.into_response()
+                }
+            }
+
+            let received = Received::default();
+            let app = Router::new()
+                .route("/api/webhooks/1/token", axum::routing::post(receive))
+                .with_state((received.clone(), rate_limited));
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/api/webhooks/1/token", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+
+            Self {
+                received,
+                config: WebhookConfig {
+                    url: Secret::new(url.into()),
+                    min_interval_secs,
+                },
+            }
+        }
+
+        fn received(&self) -> Vec<(Instant, Value)> {
+            self.received.lock().unwrap().clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_without_mentions() {
+        let webhook = MockWebhook::start(0, 0).await;
+        post(&webhook.config, "**Valheim** is up").await.unwrap();
+
+        let received = webhook.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(
+            received[0].1,
+            json!({ "content": "**Valheim** is up", "allowed_mentions": { "parse": [] } })
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_once_when_rate_limited() {
+        let webhook = MockWebhook::start(1, 0).await;
+        post(&webhook.config, "hello").await.unwrap();
+
+        let received = webhook.received();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].1, received[1].1);
+        assert!(received[1].0 - received[0].0 >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_still_rate_limited() {
+        let webhook = MockWebhook::start(usize::MAX, 0).await;
+        assert!(post(&webhook.config, "hello").await.is_err());
+        assert_eq!(webhook.received().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn spaces_out_messages() {
+        let webhook = MockWebhook::start(0, 1).await;
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        for message in ["first", "second", "third"] {
+            tx.send(message.to_owned()).await.unwrap();
+        }
+        drop(tx);
+        deliver(webhook.config.clone(), rx).await;
+
+        let received = webhook.received();
+        let contents = received.iter().map(|(_, body)| body["content"].clone()).collect::<Vec<_>>();
+        assert_eq!(contents, ["first", "second", "third"]);
+        for pair in received.windows(2) {
+            assert!(pair[1].0 - pair[0].0 >= Duration::from_secs(1));
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::auth::OidcUserData;
+use common::discord::{RoleId, UserId};
+use common::status::Capability;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::collections::HashSet;
+
+/// The user a permission check is done for, along with their roles across all guilds or the
+/// groups their OpenID Connect provider put them in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Principal {
+    /// Only set for users logged in through Discord
+    pub user: Option<UserId>,
+    pub roles: HashSet<RoleId>,
+    pub groups: HashSet<SmolStr>,
+    /// Caps the capabilities on every server, used for API tokens
+    #[serde(skip)]
+    pub scope: Option<HashSet<Capability>>,
+}
+
+impl Principal {
+    pub fn discord(user: UserId, roles: HashSet<RoleId>) -> Self {
+        Self {
+            user: Some(user),
+            roles,
+            groups: HashSet::new(),
+            scope: None,
+        }
+    }
+
+    /// Groups come from the provider and are fixed until the next login
+    pub fn oidc(user: &OidcUserData) -> Self {
+        Self {
+            user: None,
+            roles: HashSet::new(),
+            groups: user.groups.clone(),
+            scope: None,
+        }
+    }
+}
+
+/// Grants capabilities on a server to a Discord role, an OpenID Connect group or a single user
+#[derive(Debug, Clone, Deserialize)]
+pub struct PermissionEntry {
+    #[serde(flatten)]
+    pub(crate) subject: Subject,
+    pub(crate) capabilities: HashSet<Capability>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subject {
+    /// A role from any configured guild. A guild's ID is its @everyone role.
+    Role(RoleId),
+    /// A value of the groups claim, see `OIDC_GROUPS_CLAIM`
+    Group(SmolStr),
+    User(UserId),
+}
+
+impl Subject {
+    pub fn matches(&self, principal: &Principal) -> bool {
+        match self {
+            Subject::Role(role) => principal.roles.contains(role),
+            Subject::Group(group) => principal.groups.contains(group),
+            Subject::User(user) => principal.user == Some(*user),
+        }
+    }
+}
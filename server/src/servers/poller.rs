@@ -0,0 +1,293 @@
+use crate::audit::AuditLog;
+use crate::control::{Lifecycle, LifecycleAction};
+use crate::metrics::{ServerLabels, METRICS};
+use crate::servers::config::{ConfigStore, GameConfig, ServerConfig};
+use crate::servers::notifications::{notify, StatusTracker};
+use crate::servers::{unix_now, StatusFetcher};
+use common::audit::{AuditAction, AuditOutcome};
+use common::status::{HealthStatus, ServerStatus};
+use smol_str::SmolStr;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::{AbortHandle, JoinHandle, JoinSet};
+use tokio::time::Instant;
+
+/// Latest known status of every configured server, keyed by server ID
+pub(crate) type StatusSnapshot = HashMap<SmolStr, ServerStatus>;
+
+/// Refreshes the status of every configured server in the background so that requests
+/// never have to wait on a slow or unreachable game server.
+pub(super) struct StatusPoller {
+    statuses: watch::Receiver<StatusSnapshot>,
+    supervisor: AbortHandle,
+}
+
+impl StatusPoller {
+    pub fn new(config_store: Arc<ConfigStore>, audit: AuditLog) -> Self {
+        let (tx, rx) = watch::channel(StatusSnapshot::new());
+        let supervisor = tokio::spawn(Self::supervise(config_store, tx, audit));
+
+        Self {
+            statuses: rx,
+            supervisor: supervisor.abort_handle(),
+        }
+    }
+
+    pub fn statuses(&self) -> watch::Ref<'_, StatusSnapshot> {
+        self.statuses.borrow()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<StatusSnapshot> {
+        self.statuses.clone()
+    }
+
+    /// Keeps one polling task running per configured server, restarting them all whenever
+    /// the config is reloaded.
+    async fn supervise(
+        config_store: Arc<ConfigStore>,
+        statuses: watch::Sender<StatusSnapshot>,
+        audit: AuditLog,
+    ) {
+        let mut config_updates = config_store.subscribe();
+        let mut labels = HashSet::new();
+        loop {
+            let configs = config_store.configs().await.clone();
+
+            let new_labels = configs.iter().map(metric_labels).collect::<HashSet<_>>();
+            for removed in labels.difference(&new_labels) {
+                METRICS.remove_server(removed);
+            }
+            labels = new_labels;
+
+            statuses.send_modify(|snapshot| {
+                snapshot.retain(|id, _| configs.iter().any(|c| c.id() == id));
+                for config in &configs {
+                    snapshot
+                        .entry(config.id().clone())
+                        .or_insert_with(|| with_server_info(config, config.game.unknown_status()));
+                }
+            });
+
+            // Dropping the previous set aborts all of its tasks
+            let mut pollers = JoinSet::new();
+            for config in configs {
+                pollers.spawn(poll_server(config, statuses.clone(), audit.clone()));
+            }
+
+            if config_updates.changed().await.is_err() {
+                tracing::error!("Config store closed, stopping status poller");
+                return;
+            }
+            tracing::debug!("Config changed, restarting status pollers");
+        }
+    }
+}
+
+impl Drop for StatusPoller {
+    fn drop(&mut self) {
+        tracing::debug!("Dropping status poller");
+        self.supervisor.abort();
+    }
+}
+
+async fn poll_server(
+    config: ServerConfig,
+    statuses: watch::Sender<StatusSnapshot>,
+    audit: AuditLog,
+) {
+    let mut failures = 0u32;
+    let mut idle_since = None;
+    let mut backup = None::<BackupTask>;
+    let labels = metric_labels(&config);
+    let mut tracker = StatusTracker::default();
+    loop {
+        let status = fetch_server_status(&config).await;
+        METRICS.record_status(&labels, &status, unix_now());
+
+        let messages = tracker.observe(&config.name, &status);
+        if !messages.is_empty() {
+            notify(&config.notifications, &messages).await;
+        }
+
+        failures = match status.health() {
+            HealthStatus::Running | HealthStatus::Starting => 0,
+            HealthStatus::Offline | HealthStatus::Unknown => failures.saturating_add(1),
+        };
+
+        let (health, players) = (status.health(), status.players_online());
+        idle_since = next_idle_since(idle_since, health, players, Instant::now());
+        if let Some(since) = idle_since
+            && matches!((health, players), (HealthStatus::Running, Some(0)))
+            && stop_if_idle(&config, since, &audit).await
+        {
+            idle_since = None;
+        }
+
+        statuses.send_if_modified(|snapshot| match snapshot.get(config.id()) {
+            Some(old) if *old == status => false,
+            _ => {
+                snapshot.insert(config.id().clone(), status);
+                true
+            }
+        });
+
+        // Autosaves only change while the server runs, so polling is a good time to copy them.
+        // Copying can take a while, so it runs on the side and is skipped while one still runs.
+        if let GameConfig::Factorio(factorio) = &config.game
+            && backup.as_ref().is_none_or(|task| task.0.is_finished())
+        {
+            let factorio = factorio.clone();
+            backup = Some(BackupTask(tokio::spawn(async move { factorio.back_up_saves().await })));
+        }
+
+        tokio::time::sleep(config.poll.next_delay(failures)).await;
+    }
+}
+
+/// Backup running next to a poll task. Aborted when the poll task ends, so a removed or
+/// reconfigured server doesn't keep copying with its old config.
+struct BackupTask(JoinHandle<()>);
+
+impl Drop for BackupTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// When the server became empty, given the latest poll. Seeing players, a start or the server
+/// being down resets the timer, so a server that was stopped and started again gets a full idle
+/// period. An unknown state says nothing about players, so it keeps the timer as it is.
+fn next_idle_since(
+    idle_since: Option<Instant>,
+    health: HealthStatus,
+    players: Option<u32>,
+    now: Instant,
+) -> Option<Instant> {
+    match (health, players) {
+        (HealthStatus::Running, Some(0)) => Some(idle_since.unwrap_or(now)),
+        (HealthStatus::Running, Some(_)) => None,
+        (HealthStatus::Starting | HealthStatus::Offline, _) => None,
+        (HealthStatus::Running, None) | (HealthStatus::Unknown, _) => idle_since,
+    }
+}
+
+/// Stops the server if it has been empty for longer than its idle policy allows.
+/// Returns whether the server was stopped.
+async fn stop_if_idle(config: &ServerConfig, idle_since: Instant, audit: &AuditLog) -> bool {
+    let (Some(control), Some(idle)) = (&config.control, &config.idle) else {
+        return false;
+    };
+    if idle_since.elapsed() < idle.stop_after() {
+        return false;
+    }
+
+    tracing::info!("Stopping {} after {} idle minutes", config.name, idle.stop_after_mins);
+    let detail = format!("{} after {} idle minutes", LifecycleAction::Stop, idle.stop_after_mins);
+    let (stopped, outcome, detail) = match control.backend.stop().await {
+        Ok(()) => (true, AuditOutcome::Ok, detail),
+        Err(e) => {
+            tracing::warn!("Failed to stop idle server {}: {:#}", config.name, e);
+            (false, AuditOutcome::Error, format!("{detail}: {e:#}"))
+        }
+    };
+    audit
+        .record(None, AuditAction::Lifecycle, Some(config.id()), outcome, &detail)
+        .await;
+    stopped
+}
+
+async fn fetch_server_status(config: &ServerConfig) -> ServerStatus {
+    tracing::trace!("Updating server status: {:?}", config);
+    let mut status = match tokio::time::timeout(config.poll.timeout(), config.game.fetch_server_status())
+        .await
+    {
+        Ok(status) => status,
+        Err(_) => {
+            tracing::warn!("Timed out fetching status of {}", config.name);
+            config.game.unknown_status()
+        }
+    };
+
+    // The game can't tell us it's stopped or still booting, but the process manager can
+    if let Some(control) = &config.control {
+        match control.backend.state().await {
+            Ok(HealthStatus::Running) => {}
+            Ok(state) => status.set_health(state),
+            Err(e) => tracing::warn!("Failed to fetch lifecycle state of {}: {:#}", config.name, e),
+        }
+    }
+
+    with_server_info(config, status)
+}
+
+/// Fills in the parts of the status that come from our config rather than the game server
+fn with_server_info(config: &ServerConfig, mut status: ServerStatus) -> ServerStatus {
+    match &mut status {
+        ServerStatus::Factorio(status) => {
+            status.name = config.name.clone();
+            status.url = config.public_dns.clone();
+        }
+        ServerStatus::Generic(status) => {
+            status.name = config.name.clone();
+            status.url = config.public_dns.clone();
+        }
+        ServerStatus::Minecraft(status) => {
+            status.name = config.name.clone();
+            status.url = config.public_dns.clone();
+        }
+        ServerStatus::SourceQuery(status) => {
+            status.name = config.name.clone();
+            status.url = config.public_dns.clone();
+        }
+    }
+    status
+}
+
+fn metric_labels(config: &ServerConfig) -> ServerLabels {
+    ServerLabels::new(config.id(), &config.game.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn tracks_idle_time() {
+        let start = Instant::now();
+        let later = start + Duration::from_secs(60);
+        let cases = [
+            // (timer before, health, players, timer after)
+            (None, HealthStatus::Running, Some(0), Some(later)),
+            (Some(start), HealthStatus::Running, Some(0), Some(start)),
+            (Some(start), HealthStatus::Running, Some(3), None),
+            (None, HealthStatus::Running, Some(3), None),
+            (Some(start), HealthStatus::Starting, Some(0), None),
+            (Some(start), HealthStatus::Offline, None, None),
+            (Some(start), HealthStatus::Offline, Some(0), None),
+            (Some(start), HealthStatus::Unknown, None, Some(start)),
+            (Some(start), HealthStatus::Unknown, Some(0), Some(start)),
+            (None, HealthStatus::Unknown, Some(0), None),
+            (Some(start), HealthStatus::Running, None, Some(start)),
+        ];
+        for (before, health, players, after) in cases {
+            assert_eq!(
+                next_idle_since(before, health, players, later),
+                after,
+                "{before:?} {health:?} {players:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn restart_after_stop_gets_a_full_idle_period() {
+        let start = Instant::now();
+        let stopped = start + Duration::from_secs(60);
+        let woken = start + Duration::from_secs(120);
+        let mut idle_since = next_idle_since(None, HealthStatus::Running, Some(0), start);
+        idle_since = next_idle_since(idle_since, HealthStatus::Offline, None, stopped);
+        idle_since = next_idle_since(idle_since, HealthStatus::Running, Some(0), woken);
+        assert_eq!(idle_since, Some(woken));
+    }
+}
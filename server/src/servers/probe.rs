@@ -0,0 +1,155 @@
+use anyhow::{bail, Context};
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde::Deserialize;
+use smol_str::SmolStr;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpStream, UdpSocket};
+
+static HTTP_CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+/// A protocol-agnostic reachability check for servers that don't have a dedicated provider
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct ProbeConfig {
+    #[serde(flatten)]
+    pub kind: ProbeKind,
+    /// Seconds to wait for the probe to complete
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    5
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+#[serde(tag = "type")]
+pub enum ProbeKind {
+    /// Succeeds if a TCP connection to `address` (`host:port`) can be established
+    Tcp { address: SmolStr },
+    /// Sends `send` to `address` (`host:port`) and waits for a reply.
+    /// If `expect` is set the reply must start with it.
+    Udp {
+        address: SmolStr,
+        send: SmolStr,
+        #[serde(default)]
+        expect: Option<SmolStr>,
+        /// Treat `send` and `expect` as hex encoded bytes instead of text
+        #[serde(default)]
+        hex: bool,
+    },
+    /// Succeeds if a GET to `url` responds with `expected_status`
+    Http {
+        url: SmolStr,
+        #[serde(default = "default_expected_status")]
+        expected_status: u16,
+    },
+}
+
+fn default_expected_status() -> u16 {
+    200
+}
+
+impl ProbeConfig {
+    /// Runs the probe, returning the round-trip latency on success
+    pub async fn probe(&self) -> anyhow::Result<Duration> {
+        let start = Instant::now();
+        tokio::time::timeout(Duration::from_secs(self.timeout_secs), self.kind.probe())
+            .await
+            .context("probe timed out")??;
+        Ok(start.elapsed())
+    }
+}
+
+impl ProbeKind {
+    async fn probe(&self) -> anyhow::Result<()> {
+        match self {
+            ProbeKind::Tcp { address } => {
+                TcpStream::connect(&**address).await?;
+            }
+            ProbeKind::Udp {
+                address,
+                send,
+                expect,
+                hex,
+            } => {
+                let payload = decode_payload(send, *hex)?;
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect(&**address).await?;
+                socket.send(&payload).await?;
+
+                let mut buf = vec![0; 64 * 1024];
+                let len = socket.recv(&mut buf).await?;
+
+                if let Some(expect) = expect {
+                    let expect = decode_payload(expect, *hex)?;
+                    if !buf[..len].starts_with(&expect) {
+                        bail!("unexpected UDP response");
+                    }
+                }
+            }
+            ProbeKind::Http {
+                url,
+                expected_status,
+            } => {
+                let resp = HTTP_CLIENT.get(&**url).send().await?;
+                if resp.status().as_u16() != *expected_status {
+                    bail!(
+                        "expected HTTP status {expected_status} but got {}",
+                        resp.status()
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn decode_payload(payload: &str, hex: bool) -> anyhow::Result<Vec<u8>> {
+    if !hex {
+        return Ok(payload.as_bytes().to_vec());
+    }
+
+    let digits = payload.replace(' ', "");
+    // Checked up front, slicing pairs out of non-ASCII text would split characters
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!("hex payload contains something other than hex digits");
+    }
+    if !digits.len().is_multiple_of(2) {
+        bail!("hex payload has an odd number of digits");
+    }
+    digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).context("invalid hex payload")?;
+            u8::from_str_radix(pair, 16).context("invalid hex payload")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_payloads() {
+        let cases: &[(&str, bool, Option<&[u8]>)] = &[
+            ("PING\r\n", false, Some(b"PING\r\n")),
+            ("ünïcode", false, Some("ünïcode".as_bytes())),
+            ("", true, Some(b"")),
+            ("fffe01", true, Some(b"\xff\xfe\x01")),
+            ("FF FE 01", true, Some(b"\xff\xfe\x01")),
+            (" f f ", true, Some(b"\xff")),
+            ("fffe0", true, None),
+            ("fffg", true, None),
+            ("0x01", true, None),
+            ("fé", true, None),
+            ("ffé0", true, None),
+        ];
+        for &(payload, hex, expected) in cases {
+            assert_eq!(decode_payload(payload, hex).ok().as_deref(), expected, "{payload:?}");
+        }
+    }
+}
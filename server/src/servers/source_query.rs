@@ -0,0 +1,103 @@
+use crate::secret::Secret;
+use crate::servers::{StatusFetcher, UNKNOWN_TEXT};
+use crate::AppResult;
+use common::source_query::{SourcePlayer, SourceQueryStatus};
+use common::status::HealthStatus;
+use serde::Deserialize;
+use smol_str::SmolStr;
+
+mod a2s;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+pub struct SourceQueryConfig {
+    /// Address of the query port as `host:port`. Depending on the game this is either the game
+    /// port or a dedicated query port (e.g. game port + 1 for Valheim)
+    pub query_host: SmolStr,
+    #[serde(default)]
+    pub game_password: Option<Secret>,
+}
+
+impl SourceQueryConfig {
+    async fn populate_status(&self, status: &mut SourceQueryStatus) -> AppResult<()> {
+        let mut client = a2s::Client::connect(&self.query_host).await?;
+
+        let info = client.info().await?;
+        status.health = HealthStatus::Running;
+        status.server_name = info.name.into();
+        status.game = info.game.into();
+        status.map = info.map.into();
+        status.version = info.version.into();
+        status.player_count = info.players;
+        status.max_players = info.max_players;
+        status.bots = info.bots;
+        status.vac = info.vac;
+
+        // Plenty of games answer A2S_INFO but leave these half implemented,
+        // so don't let them affect the health of the server
+        match client.players().await {
+            Ok(players) => {
+                status.players = players
+                    .into_iter()
+                    // Connecting players show up without a name for a few seconds
+                    .filter(|p| !p.name.is_empty())
+                    .map(|p| SourcePlayer {
+                        name: p.name.into(),
+                        score: p.score,
+                        duration: p.duration,
+                    })
+                    .collect()
+            }
+            Err(e) => tracing::warn!("Failed to fetch players from {}: {}", self.query_host, e),
+        }
+
+        match client.rules().await {
+            Ok(rules) => {
+                status.rules = rules
+                    .into_iter()
+                    .map(|(k, v)| (k.into(), v.into()))
+                    .collect()
+            }
+            Err(e) => tracing::warn!("Failed to fetch rules from {}: {}", self.query_host, e),
+        }
+
+        Ok(())
+    }
+}
+
+impl StatusFetcher for SourceQueryConfig {
+    type Status = SourceQueryStatus;
+
+    fn unknown_status(&self) -> SourceQueryStatus {
+        SourceQueryStatus {
+            name: self.query_host.clone(),
+            health: HealthStatus::Unknown,
+            url: SmolStr::default(),
+            game_password: self
+                .game_password
+                .as_ref()
+                .map(|p| p.secret().clone())
+                .unwrap_or_default(),
+            server_name: SmolStr::default(),
+            game: UNKNOWN_TEXT,
+            map: UNKNOWN_TEXT,
+            version: UNKNOWN_TEXT,
+            player_count: 0,
+            max_players: 0,
+            bots: 0,
+            vac: false,
+            players: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+
+    async fn fetch_server_status(&self) -> SourceQueryStatus {
+        let mut status = self.unknown_status();
+
+        if let Err(e) = self.populate_status(&mut status).await {
+            tracing::error!("Failed to fetch server status: {}", e);
+            status.health = HealthStatus::Offline;
+        }
+
+        status
+    }
+}
@@ -0,0 +1,490 @@
+//! Client for Valve's A2S server queries.
+//! https://developer.valvesoftware.com/wiki/Server_queries
+
+use anyhow::{bail, Context};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+const SIMPLE_HEADER: i32 = -1;
+const SPLIT_HEADER: i32 = -2;
+
+const A2S_INFO: u8 = b'T';
+const A2S_PLAYER: u8 = b'U';
+const A2S_RULES: u8 = b'V';
+
+const S2A_INFO: u8 = b'I';
+const S2A_PLAYER: u8 = b'D';
+const S2A_RULES: u8 = b'E';
+const S2C_CHALLENGE: u8 = b'A';
+
+/// Sent in place of a challenge to request one from the server
+const NO_CHALLENGE: i32 = -1;
+
+/// App ID of The Ship, which has extra fields in its info response
+const THE_SHIP_APP_ID: i16 = 2400;
+
+#[derive(Debug)]
+pub struct Info {
+    pub name: String,
+    pub map: String,
+    pub game: String,
+    pub players: u8,
+    pub max_players: u8,
+    pub bots: u8,
+    pub vac: bool,
+    pub version: String,
+}
+
+#[derive(Debug)]
+pub struct Player {
+    pub name: String,
+    pub score: i32,
+    pub duration: f32,
+}
+
+pub struct Client {
+    socket: UdpSocket,
+    buf: Vec<u8>,
+}
+
+impl Client {
+    pub async fn connect(host: &str) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket
+            .connect(host)
+            .await
+            .context("failed to resolve query address")?;
+
+        Ok(Self {
+            socket,
+            buf: vec![0; 64 * 1024],
+        })
+    }
+
+    pub async fn info(&mut self) -> anyhow::Result<Info> {
+        let mut request = Vec::with_capacity(29);
+        request.extend_from_slice(&SIMPLE_HEADER.to_le_bytes());
+        request.push(A2S_INFO);
+        request.extend_from_slice(b"Source Engine Query\0");
+
+        let mut response = self.request(&request).await?;
+        if response.first() == Some(&S2C_CHALLENGE) {
+            // Newer servers require the challenge to be appended to the original request
+            request.extend_from_slice(&parse_challenge(&response)?.to_le_bytes());
+            response = self.request(&request).await?;
+        }
+
+        parse_info(&response)
+    }
+
+    pub async fn players(&mut self) -> anyhow::Result<Vec<Player>> {
+        let response = self.challenged_request(A2S_PLAYER).await?;
+
+        parse_players(&response)
+    }
+
+    pub async fn rules(&mut self) -> anyhow::Result<Vec<(String, String)>> {
+        let response = self.challenged_request(A2S_RULES).await?;
+
+        parse_rules(&response)
+    }
+
+    /// Sends a request that always needs a challenge, first asking the server for one
+    async fn challenged_request(&mut self, kind: u8) -> anyhow::Result<Vec<u8>> {
+        let mut request = Vec::with_capacity(9);
+        request.extend_from_slice(&SIMPLE_HEADER.to_le_bytes());
+        request.push(kind);
+        request.extend_from_slice(&NO_CHALLENGE.to_le_bytes());
+
+        let response = self.request(&request).await?;
+        if response.first() != Some(&S2C_CHALLENGE) {
+            // Some servers skip the challenge entirely
+            return Ok(response);
+        }
+
+        request.truncate(5);
+        request.extend_from_slice(&parse_challenge(&response)?.to_le_bytes());
+        self.request(&request).await
+    }
+
+    /// Sends a request and returns the response payload without the packet header,
+    /// reassembling split responses if needed.
+    async fn request(&mut self, request: &[u8]) -> anyhow::Result<Vec<u8>> {
+        tokio::time::timeout(TIMEOUT, async {
+            self.socket.send(request).await?;
+
+            let len = self.socket.recv(&mut self.buf).await?;
+            let mut reader = Reader::new(&self.buf[..len]);
+            match reader.i32()? {
+                SIMPLE_HEADER => Ok(reader.remaining().to_vec()),
+                SPLIT_HEADER => self.receive_split(len).await,
+                header => bail!("unknown packet header {header:#x}"),
+            }
+        })
+        .await
+        .context("timed out waiting for query response")?
+    }
+
+    /// Collects all the packets of a split response. `first_len` is the length of the
+    /// packet already sitting in the buffer.
+    async fn receive_split(&mut self, first_len: usize) -> anyhow::Result<Vec<u8>> {
+        let mut response = SplitResponse::default();
+        let mut len = first_len;
+        loop {
+            if let Some(payload) = response.add(&self.buf[..len])? {
+                return Ok(payload);
+            }
+            len = self.socket.recv(&mut self.buf).await?;
+        }
+    }
+}
+
+/// The parts of a response the server split over several packets, which may arrive in any order
+#[derive(Default)]
+struct SplitResponse {
+    id: Option<i32>,
+    parts: Vec<Option<Vec<u8>>>,
+}
+
+impl SplitResponse {
+    /// Adds a packet and returns the reassembled payload once every part arrived
+    fn add(&mut self, packet: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut reader = Reader::new(packet);
+        if reader.i32()? != SPLIT_HEADER {
+            bail!("expected split packet");
+        }
+        let id = reader.i32()?;
+        if id as u32 & 0x8000_0000 != 0 {
+            bail!("compressed responses are not supported");
+        }
+        if *self.id.get_or_insert(id) != id {
+            // Straggler from an older response, ignore it
+            return Ok(None);
+        }
+
+        let total = reader.u8()? as usize;
+        let number = reader.u8()? as usize;
+        let _size = reader.i16()?;
+        if number >= total {
+            bail!("split packet number {number} out of range ({total} total)");
+        }
+        if self.parts.is_empty() {
+            self.parts.resize(total, None);
+        } else if self.parts.len() != total {
+            bail!("split packet claims {total} packets, earlier ones {}", self.parts.len());
+        }
+        self.parts[number] = Some(reader.remaining().to_vec());
+
+        if !self.parts.iter().all(Option::is_some) {
+            return Ok(None);
+        }
+        let payload = self.parts.iter().flatten().flatten().copied().collect::<Vec<u8>>();
+        let mut reader = Reader::new(&payload);
+        if reader.i32()? != SIMPLE_HEADER {
+            bail!("invalid header in reassembled response");
+        }
+        Ok(Some(reader.remaining().to_vec()))
+    }
+}
+
+fn parse_info(response: &[u8]) -> anyhow::Result<Info> {
+    let mut reader = Reader::new(response);
+    if reader.u8()? != S2A_INFO {
+        bail!("unexpected A2S_INFO response type");
+    }
+
+    let _protocol = reader.u8()?;
+    let name = reader.cstr()?;
+    let map = reader.cstr()?;
+    let _folder = reader.cstr()?;
+    let game = reader.cstr()?;
+    let app_id = reader.i16()?;
+    let players = reader.u8()?;
+    let max_players = reader.u8()?;
+    let bots = reader.u8()?;
+    let _server_type = reader.u8()?;
+    let _environment = reader.u8()?;
+    let _visibility = reader.u8()?;
+    let vac = reader.u8()? == 1;
+    if app_id == THE_SHIP_APP_ID {
+        let _mode = reader.u8()?;
+        let _witnesses = reader.u8()?;
+        let _duration = reader.u8()?;
+    }
+    let version = reader.cstr()?;
+
+    Ok(Info {
+        name,
+        map,
+        game,
+        players,
+        max_players,
+        bots,
+        vac,
+        version,
+    })
+}
+
+fn parse_players(response: &[u8]) -> anyhow::Result<Vec<Player>> {
+    let mut reader = Reader::new(response);
+    if reader.u8()? != S2A_PLAYER {
+        bail!("unexpected A2S_PLAYER response type");
+    }
+
+    let count = reader.u8()?;
+    let mut players = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let _index = reader.u8()?;
+        players.push(Player {
+            name: reader.cstr()?,
+            score: reader.i32()?,
+            duration: reader.f32()?,
+        });
+    }
+
+    Ok(players)
+}
+
+fn parse_rules(response: &[u8]) -> anyhow::Result<Vec<(String, String)>> {
+    let mut reader = Reader::new(response);
+    if reader.u8()? != S2A_RULES {
+        bail!("unexpected A2S_RULES response type");
+    }
+
+    let count = reader.i16()?;
+    let mut rules = Vec::with_capacity(count.max(0) as usize);
+    for _ in 0..count {
+        rules.push((reader.cstr()?, reader.cstr()?));
+    }
+
+    Ok(rules)
+}
+
+fn parse_challenge(response: &[u8]) -> anyhow::Result<i32> {
+    let mut reader = Reader::new(response);
+    reader.u8()?;
+    reader.i32()
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let (bytes, rest) = self
+            .data
+            .split_first_chunk::<N>()
+            .context("unexpected end of response")?;
+        self.data = rest;
+        Ok(*bytes)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn i16(&mut self) -> anyhow::Result<i16> {
+        Ok(i16::from_le_bytes(self.take()?))
+    }
+
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+
+    fn cstr(&mut self) -> anyhow::Result<String> {
+        let end = self
+            .data
+            .iter()
+            .position(|&b| b == 0)
+            .context("unterminated string in response")?;
+        let value = String::from_utf8_lossy(&self.data[..end]).into_owned();
+        self.data = &self.data[end + 1..];
+        Ok(value)
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example A2S_INFO response from the Valve wiki, without the packet header
+    const INFO: &[u8] = b"I\x02game2xs.com Counter-Strike Source #1\0de_dust\0cstrike\0\
+        Counter-Strike: Source\0\xf0\x00\x05\x10\x04dl\x00\x011.0.0.22\0";
+    /// The Ship's response, with its three extra bytes before the version
+    const SHIP_INFO: &[u8] = b"I\x07Ship Server\0batavier\0ship\0The Ship\0\
+        \x60\x09\x02\x10\x00dw\x00\x01\x00\x03\x3c1.0.0.4\0";
+    const PLAYERS: &[u8] = b"D\x02\x00Alice\0\x0c\x00\x00\x00\x00\x00\x20\x44\
+        \x01Bob\0\xfe\xff\xff\xff\x00\x00\x80\x3f";
+    const RULES: &[u8] = b"E\x02\x00mp_timelimit\x0030\x00sv_cheats\x000\x00";
+
+    #[test]
+    fn parses_info() {
+        let info = parse_info(INFO).unwrap();
+        assert_eq!(info.name, "game2xs.com Counter-Strike Source #1");
+        assert_eq!(info.map, "de_dust");
+        assert_eq!(info.game, "Counter-Strike: Source");
+        assert_eq!((info.players, info.max_players, info.bots), (5, 16, 4));
+        assert!(info.vac);
+        assert_eq!(info.version, "1.0.0.22");
+    }
+
+    #[test]
+    fn parses_the_ship_info() {
+        let info = parse_info(SHIP_INFO).unwrap();
+        assert_eq!(info.game, "The Ship");
+        assert_eq!((info.players, info.max_players), (2, 16));
+        assert_eq!(info.version, "1.0.0.4");
+    }
+
+    #[test]
+    fn rejects_truncated_info() {
+        for len in 0..INFO.len() {
+            assert!(parse_info(&INFO[..len]).is_err(), "accepted {len} bytes");
+        }
+    }
+
+    #[test]
+    fn rejects_other_response_types() {
+        assert!(parse_info(PLAYERS).is_err());
+        assert!(parse_players(INFO).is_err());
+        assert!(parse_rules(INFO).is_err());
+    }
+
+    #[test]
+    fn parses_challenge() {
+        assert_eq!(parse_challenge(b"A\x4b\xa1\xd5\x22").unwrap(), 0x22d5_a14b);
+        assert_eq!(parse_challenge(b"A\xff\xff\xff\xff").unwrap(), -1);
+        assert!(parse_challenge(b"A\x4b\xa1\xd5").is_err());
+        assert!(parse_challenge(b"").is_err());
+    }
+
+    #[test]
+    fn parses_players() {
+        let players = parse_players(PLAYERS).unwrap();
+        assert_eq!(players.len(), 2);
+        assert_eq!(players[0].name, "Alice");
+        assert_eq!(players[0].score, 12);
+        assert_eq!(players[0].duration, 640.0);
+        assert_eq!(players[1].name, "Bob");
+        assert_eq!(players[1].score, -2);
+        assert_eq!(players[1].duration, 1.0);
+    }
+
+    #[test]
+    fn rejects_truncated_players() {
+        for len in 0..PLAYERS.len() {
+            assert!(parse_players(&PLAYERS[..len]).is_err(), "accepted {len} bytes");
+        }
+        // More players announced than sent
+        assert!(parse_players(b"D\x01").is_err());
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rules = parse_rules(RULES).unwrap();
+        assert_eq!(
+            rules,
+            [("mp_timelimit".to_owned(), "30".to_owned()), ("sv_cheats".to_owned(), "0".to_owned())]
+        );
+        assert!(parse_rules(&RULES[..RULES.len() - 1]).is_err());
+        // A negative count is treated as no rules
+        assert!(parse_rules(b"E\xff\xff").unwrap().is_empty());
+    }
+
+    /// A split packet of response `id`, part `number` of `total`
+    fn split_packet(id: i32, total: u8, number: u8, part: &[u8]) -> Vec<u8> {
+        let mut packet = SPLIT_HEADER.to_le_bytes().to_vec();
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&[total, number]);
+        packet.extend_from_slice(&1248i16.to_le_bytes());
+        packet.extend_from_slice(part);
+        packet
+    }
+
+    /// `RULES` with its header, split into three parts
+    fn split_rules() -> [Vec<u8>; 3] {
+        let mut payload = SIMPLE_HEADER.to_le_bytes().to_vec();
+        payload.extend_from_slice(RULES);
+        let (first, rest) = payload.split_at(10);
+        let (second, third) = rest.split_at(10);
+        [first, second, third].map(<[u8]>::to_vec)
+    }
+
+    /// Feeds the packets to a fresh collector and returns what each one gave
+    fn collect(packets: &[Vec<u8>]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        let mut response = SplitResponse::default();
+        packets.iter().map(|packet| response.add(packet)).collect()
+    }
+
+    #[test]
+    fn reassembles_split_responses() {
+        let [first, second, third] = split_rules();
+        let orders = [[0, 1, 2], [2, 0, 1], [1, 2, 0]];
+        for order in orders {
+            let packets = order.map(|number| {
+                let part = [&first, &second, &third][number];
+                split_packet(7, 3, number as u8, part)
+            });
+            let results = collect(&packets).unwrap();
+            assert_eq!(results, [None, None, Some(RULES.to_vec())], "{order:?}");
+        }
+
+        let single = split_packet(7, 1, 0, &[&first[..], &second, &third].concat());
+        assert_eq!(collect(&[single]).unwrap(), [Some(RULES.to_vec())]);
+    }
+
+    #[test]
+    fn ignores_stragglers_and_duplicates() {
+        let [first, second, third] = split_rules();
+        let packets = [
+            split_packet(7, 3, 0, &first),
+            split_packet(6, 3, 1, b"old"),
+            split_packet(7, 3, 0, &first),
+            split_packet(7, 3, 1, &second),
+            split_packet(7, 3, 2, &third),
+        ];
+        let results = collect(&packets).unwrap();
+        assert_eq!(results, [None, None, None, None, Some(RULES.to_vec())]);
+    }
+
+    #[test]
+    fn rejects_bad_split_packets() {
+        let [first, second, _] = split_rules();
+        let mut compressed = split_packet(7, 2, 0, &first);
+        compressed[7] |= 0x80;
+        let cases = [
+            ("out of range", vec![split_packet(7, 2, 2, &first)]),
+            ("no packets", vec![split_packet(7, 0, 0, &first)]),
+            (
+                "changed count",
+                vec![split_packet(7, 3, 0, &first), split_packet(7, 2, 1, &second)],
+            ),
+            ("compressed", vec![compressed]),
+            ("simple packet", vec![[&SIMPLE_HEADER.to_le_bytes()[..], RULES].concat()]),
+            ("truncated header", vec![split_packet(7, 2, 0, b"")[..10].to_vec()]),
+            // Reassembled into something without the simple header
+            (
+                "bad payload",
+                vec![split_packet(7, 2, 0, &second), split_packet(7, 2, 1, &first)],
+            ),
+        ];
+        for (name, packets) in cases {
+            assert!(collect(&packets).is_err(), "accepted {name}");
+        }
+    }
+}